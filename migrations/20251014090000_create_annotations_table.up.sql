@@ -0,0 +1,10 @@
+-- 时间轴标注（部署、事件等）
+CREATE TABLE IF NOT EXISTS annotations (
+    id BIGSERIAL PRIMARY KEY,
+    key TEXT NOT NULL,
+    timestamp TIMESTAMPTZ NOT NULL,
+    note TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS annotations_key_timestamp_idx ON annotations (key, timestamp);
@@ -0,0 +1,8 @@
+-- 按国家与天聚合的计数（只存国家代码，不存 IP）
+CREATE TABLE IF NOT EXISTS geo_counts (
+    key TEXT NOT NULL,
+    country TEXT NOT NULL,
+    day_window TIMESTAMPTZ NOT NULL,
+    count BIGINT NOT NULL DEFAULT 0,
+    PRIMARY KEY (key, country, day_window)
+);
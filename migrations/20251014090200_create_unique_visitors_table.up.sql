@@ -0,0 +1,7 @@
+-- 每个 key 每天见过的访客（只存加盐哈希，不存 IP）
+CREATE TABLE IF NOT EXISTS unique_visitors (
+    key TEXT NOT NULL,
+    day_window TIMESTAMPTZ NOT NULL,
+    visitor TEXT NOT NULL,
+    PRIMARY KEY (key, day_window, visitor)
+);
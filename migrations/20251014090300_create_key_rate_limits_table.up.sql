@@ -0,0 +1,6 @@
+-- 单个 key 的每分钟计数上限，由管理接口设置
+CREATE TABLE IF NOT EXISTS key_rate_limits (
+    key TEXT PRIMARY KEY,
+    per_minute INTEGER NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
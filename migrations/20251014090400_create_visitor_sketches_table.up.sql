@@ -0,0 +1,8 @@
+-- 每个 key 每天的 HyperLogLog 寄存器，用于估算独立访客数
+CREATE TABLE IF NOT EXISTS visitor_sketches (
+    key TEXT NOT NULL,
+    day_window TIMESTAMPTZ NOT NULL,
+    register SMALLINT NOT NULL,
+    rank SMALLINT NOT NULL,
+    PRIMARY KEY (key, day_window, register)
+);
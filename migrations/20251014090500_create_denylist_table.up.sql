@@ -0,0 +1,7 @@
+-- 管理员屏蔽的 key（存储形式）与客户端 IP 网段
+CREATE TABLE IF NOT EXISTS denylist (
+    kind TEXT NOT NULL,
+    value TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (kind, value)
+);
@@ -0,0 +1,2 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS counters_minute_window_idx;
@@ -0,0 +1,2 @@
+-- 按时间扫描最近的分钟窗口（突增检测）
+CREATE INDEX IF NOT EXISTS counters_minute_window_idx ON counters (minute_window);
@@ -0,0 +1,6 @@
+-- 迁移而来的起始计数，由管理接口设置，与实际计数分开存放
+CREATE TABLE IF NOT EXISTS key_seeds (
+    key TEXT PRIMARY KEY,
+    count BIGINT NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
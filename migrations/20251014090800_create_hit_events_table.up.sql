@@ -0,0 +1,11 @@
+-- 可选的逐次访问记录（HIT_EVENTS_SAMPLE_RATE），只保存粗粒度信息
+CREATE TABLE IF NOT EXISTS hit_events (
+    key TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    referrer_host TEXT,
+    ua_class TEXT NOT NULL,
+    weight INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS hit_events_key_created_at_idx ON hit_events (key, created_at);
+CREATE INDEX IF NOT EXISTS hit_events_created_at_idx ON hit_events (created_at);
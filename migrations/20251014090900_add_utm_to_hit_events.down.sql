@@ -0,0 +1,5 @@
+-- Add down migration script here
+ALTER TABLE hit_events
+    DROP COLUMN IF EXISTS utm_source,
+    DROP COLUMN IF EXISTS utm_medium,
+    DROP COLUMN IF EXISTS utm_campaign;
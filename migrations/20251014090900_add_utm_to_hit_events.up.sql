@@ -0,0 +1,5 @@
+-- 访问事件的 UTM 活动参数
+ALTER TABLE hit_events
+    ADD COLUMN IF NOT EXISTS utm_source TEXT,
+    ADD COLUMN IF NOT EXISTS utm_medium TEXT,
+    ADD COLUMN IF NOT EXISTS utm_campaign TEXT;
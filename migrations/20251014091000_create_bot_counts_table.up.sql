@@ -0,0 +1,7 @@
+-- 来自已知爬虫或代理网段、未计入总数的访问（BOT_IP_ACTION=tally）
+CREATE TABLE IF NOT EXISTS bot_counts (
+    key TEXT NOT NULL,
+    day_window TIMESTAMPTZ NOT NULL,
+    count BIGINT NOT NULL DEFAULT 0,
+    PRIMARY KEY (key, day_window)
+);
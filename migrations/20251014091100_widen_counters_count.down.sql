@@ -0,0 +1,2 @@
+-- Add down migration script here
+ALTER TABLE counters ALTER COLUMN count TYPE INTEGER;
@@ -0,0 +1,2 @@
+-- 单个时间桶的计数可能超过 INTEGER（大的 ?amount=N、抽样或 hour/day 粒度）
+ALTER TABLE counters ALTER COLUMN count TYPE BIGINT;
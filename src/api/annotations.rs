@@ -0,0 +1,103 @@
+//! 时间轴标注接口
+
+use crate::api::auth::require_admin;
+use crate::api::handlers::validate_key;
+use crate::api::types::{Annotation, AnnotationRequest, ApiError};
+use crate::config::AppConfig;
+use crate::error::AppError;
+use axum::{
+    extract::{Extension, Path},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use sqlx::postgres::PgPool;
+use std::sync::Arc;
+
+/// 标注内容的最大长度（字符）
+const MAX_NOTE_LENGTH: usize = 1024;
+
+/// 数据库操作：查询区间内的标注，按时间排序
+///
+/// Rows are looked up by `stored_key` and returned with `key`, so hashed keys never leak.
+pub async fn fetch_annotations(
+    pool: &PgPool,
+    key: &str,
+    stored_key: &str,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> Result<Vec<Annotation>, AppError> {
+    let annotations = sqlx::query_as!(
+        Annotation,
+        r#"
+        SELECT $4::TEXT AS "key!", timestamp, note
+        FROM annotations
+        WHERE key = $1 AND timestamp >= $2 AND timestamp < $3
+        ORDER BY timestamp, id
+        "#,
+        stored_key,
+        from,
+        to,
+        key
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(annotations)
+}
+
+/// 添加时间轴标注接口
+#[utoipa::path(
+    post,
+    summary = "Annotate a Key's Timeline",
+    description = "Attaches a note (e.g. \"deployed v2\") to a point in time on the key's hit timeline. Annotations are returned by `/stats/{key}?annotations=true`. Requires `Authorization: Bearer <ADMIN_TOKEN>`.",
+    path = "/hits/{key}/annotate",
+    tag = "Admin",
+    params(
+        ("key" = String, Path, description = "The key to annotate.")
+    ),
+    request_body = AnnotationRequest,
+    responses(
+        (status = 201, description = "Annotation stored.", body = Annotation),
+        (status = 400, description = "Invalid key or note", body = ApiError),
+        (status = 401, description = "Missing or invalid admin token", body = ApiError),
+        (status = 500, description = "Database error", body = ApiError)
+    )
+)]
+pub async fn annotate_route(
+    Path(key): Path<String>,
+    headers: HeaderMap,
+    Extension(pool): Extension<PgPool>,
+    Extension(config): Extension<Arc<AppConfig>>,
+    Json(request): Json<AnnotationRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let key = validate_key(&key, &config)?;
+    require_admin(&headers, &config)?;
+    let note = request.note.trim();
+    if note.is_empty() {
+        return Err(AppError::InvalidInput(
+            "Note must not be empty.".to_string(),
+        ));
+    }
+    if note.chars().count() > MAX_NOTE_LENGTH {
+        return Err(AppError::InvalidInput(format!(
+            "Note must be at most {} characters.",
+            MAX_NOTE_LENGTH
+        )));
+    }
+    let annotation = sqlx::query_as!(
+        Annotation,
+        r#"
+        INSERT INTO annotations (key, timestamp, note)
+        VALUES ($1, $2, $3)
+        RETURNING $4::TEXT AS "key!", timestamp, note
+        "#,
+        config.stored_key(&key),
+        request.timestamp,
+        note,
+        key
+    )
+    .fetch_one(&pool)
+    .await?;
+    Ok((StatusCode::CREATED, Json(annotation)))
+}
@@ -0,0 +1,41 @@
+//! 管理接口鉴权
+
+use crate::config::AppConfig;
+use crate::error::AppError;
+use axum::http::{header, HeaderMap};
+
+/// 校验请求是否携带正确的 `ADMIN_TOKEN`
+///
+/// The token is expected as `Authorization: Bearer <token>`.
+pub fn require_admin(headers: &HeaderMap, config: &AppConfig) -> Result<(), AppError> {
+    let Some(expected) = config.admin_token.as_deref() else {
+        return Err(AppError::Unauthorized(
+            "Admin endpoints are disabled: ADMIN_TOKEN is not configured.".to_string(),
+        ));
+    };
+    if bearer_matches(headers, expected) {
+        Ok(())
+    } else {
+        Err(AppError::Unauthorized(
+            "Missing or invalid admin token.".to_string(),
+        ))
+    }
+}
+
+/// 请求是否携带 `Authorization: Bearer <expected>`
+pub fn bearer_matches(headers: &HeaderMap, expected: &str) -> bool {
+    let provided = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .unwrap_or_default();
+    constant_time_eq(provided.as_bytes(), expected.as_bytes())
+}
+
+/// 常量时间比较，避免通过响应耗时猜测 token
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
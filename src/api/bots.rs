@@ -0,0 +1,77 @@
+//! 已知爬虫与代理网段的访问统计接口
+
+use crate::api::extract::Query;
+use crate::api::handlers::{ensure_exact_access, ensure_public, get_total_count, validate_key};
+use crate::api::types::{ApiError, BotHits, BotParams};
+use crate::config::{AppConfig, BotIpAction};
+use crate::error::AppError;
+use axum::{
+    extract::{Extension, Path},
+    http::HeaderMap,
+    Json,
+};
+use chrono::Utc;
+use sqlx::postgres::PgPool;
+use std::sync::Arc;
+
+/// 爬虫访问计数接口
+#[utoipa::path(
+    get,
+    summary = "Get Bot Hits for a Key",
+    description = "Returns how many hits came from the IP ranges in `BOT_IP_LISTS`, without incrementing the counter. These hits are not part of the key's total. Only recorded with `BOT_IP_ACTION=tally`; days start at `WINDOW_OFFSET` minutes past UTC midnight. With `COUNT_NOISE` or `KEY_COUNT_NOISE` the counts require the admin token (`Authorization: Bearer <ADMIN_TOKEN>`).",
+    path = "/bots/{key}",
+    tag = "Main",
+    params(
+        ("key" = String, Path, description = "The key to summarize."),
+        BotParams
+    ),
+    responses(
+        (status = 200, description = "Bot hits for the key.", body = BotHits),
+        (status = 400, description = "Invalid key or range, or bot tallying is disabled", body = ApiError),
+        (status = 403, description = "The key has a noise bound and no admin token was given", body = ApiError),
+        (status = 404, description = "Key is below `PUBLIC_MIN_TOTAL`", body = ApiError),
+        (status = 500, description = "Database error", body = ApiError)
+    )
+)]
+pub async fn bots_route(
+    Path(key): Path<String>,
+    Query(params): Query<BotParams>,
+    Extension(pool): Extension<PgPool>,
+    Extension(config): Extension<Arc<AppConfig>>,
+    headers: HeaderMap,
+) -> Result<Json<BotHits>, AppError> {
+    let key = validate_key(&key, &config)?;
+    if config.bot_ip_lists.is_empty() || config.bot_ip_action != BotIpAction::Tally {
+        return Err(AppError::InvalidInput(
+            "Bot tallying is disabled; set BOT_IP_LISTS and BOT_IP_ACTION=tally.".to_string(),
+        ));
+    }
+    let to = params.to.unwrap_or_else(Utc::now);
+    let from = params.from;
+    if from.is_some_and(|from| from >= to) {
+        return Err(AppError::InvalidInput(
+            "`from` must be earlier than `to`.".to_string(),
+        ));
+    }
+
+    let stored_key = config.stored_key(&key);
+    let total = get_total_count(&pool, &stored_key)
+        .await?
+        .saturating_add(config.base_count(&key));
+    ensure_public(total, &config)?;
+    ensure_exact_access(&key, &headers, &config, "Bot counts")?;
+    let count = sqlx::query_scalar!(
+        r#"
+        SELECT COALESCE(SUM(count), 0)::BIGINT AS "count!"
+        FROM bot_counts
+        WHERE key = $1 AND ($2::TIMESTAMPTZ IS NULL OR day_window >= $2) AND day_window < $3
+        "#,
+        stored_key,
+        from,
+        to
+    )
+    .fetch_one(&pool)
+    .await?;
+
+    Ok(Json(BotHits { key, count }))
+}
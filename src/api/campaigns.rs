@@ -0,0 +1,87 @@
+//! UTM 活动统计接口
+
+use crate::api::extract::Query;
+use crate::api::handlers::{ensure_exact_access, ensure_public, get_total_count, validate_key};
+use crate::api::types::{ApiError, CampaignBreakdown, CampaignCount, CampaignParams};
+use crate::config::AppConfig;
+use crate::error::AppError;
+use axum::{
+    extract::{Extension, Path},
+    http::HeaderMap,
+    Json,
+};
+use chrono::Utc;
+use sqlx::postgres::PgPool;
+use std::sync::Arc;
+
+/// 按 UTM 参数统计接口
+#[utoipa::path(
+    get,
+    summary = "Get Campaign Breakdown for a Key",
+    description = "Returns estimated hit counts per `utm_source`/`utm_medium`/`utm_campaign` combination without incrementing the counter. Parameters are read from the counting request's query string, or from its `Referer` when the request has none. Built from the sampled `hit_events` table, so it requires `HIT_EVENTS_SAMPLE_RATE` and only covers the last `HIT_EVENTS_RETENTION_DAYS`. With `COUNT_NOISE` or `KEY_COUNT_NOISE` the breakdown requires the admin token (`Authorization: Bearer <ADMIN_TOKEN>`).",
+    path = "/campaigns/{key}",
+    tag = "Main",
+    params(
+        ("key" = String, Path, description = "The key to summarize."),
+        CampaignParams
+    ),
+    responses(
+        (status = 200, description = "Campaign breakdown for the key.", body = CampaignBreakdown),
+        (status = 400, description = "Invalid key or range, or hit events are disabled", body = ApiError),
+        (status = 403, description = "The key has a noise bound and no admin token was given", body = ApiError),
+        (status = 404, description = "Key is below `PUBLIC_MIN_TOTAL`", body = ApiError),
+        (status = 500, description = "Database error", body = ApiError)
+    )
+)]
+pub async fn campaigns_route(
+    Path(key): Path<String>,
+    Query(params): Query<CampaignParams>,
+    Extension(pool): Extension<PgPool>,
+    Extension(config): Extension<Arc<AppConfig>>,
+    headers: HeaderMap,
+) -> Result<Json<CampaignBreakdown>, AppError> {
+    let key = validate_key(&key, &config)?;
+    if config.hit_events_sample_rate.is_none() {
+        return Err(AppError::InvalidInput(
+            "Campaign stats require HIT_EVENTS_SAMPLE_RATE to be set.".to_string(),
+        ));
+    }
+    let to = params.to.unwrap_or_else(Utc::now);
+    let from = params.from;
+    if from.is_some_and(|from| from >= to) {
+        return Err(AppError::InvalidInput(
+            "`from` must be earlier than `to`.".to_string(),
+        ));
+    }
+
+    let stored_key = config.stored_key(&key);
+    let total = get_total_count(&pool, &stored_key)
+        .await?
+        .saturating_add(config.base_count(&key));
+    ensure_public(total, &config)?;
+    ensure_exact_access(&key, &headers, &config, "Campaign breakdowns")?;
+    let campaigns = sqlx::query_as!(
+        CampaignCount,
+        r#"
+        SELECT
+            utm_source AS source,
+            utm_medium AS medium,
+            utm_campaign AS campaign,
+            SUM(weight)::BIGINT AS "count!"
+        FROM hit_events
+        WHERE key = $1
+            AND ($2::TIMESTAMPTZ IS NULL OR created_at >= $2)
+            AND created_at < $3
+            AND (utm_source IS NOT NULL OR utm_medium IS NOT NULL OR utm_campaign IS NOT NULL)
+        GROUP BY utm_source, utm_medium, utm_campaign
+        ORDER BY 4 DESC, 1, 2, 3
+        "#,
+        stored_key,
+        from,
+        to
+    )
+    .fetch_all(&pool)
+    .await?;
+
+    Ok(Json(CampaignBreakdown { key, campaigns }))
+}
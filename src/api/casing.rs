@@ -0,0 +1,147 @@
+//! JSON 字段命名风格转换
+//!
+//! Response structs declare their default casing with `#[serde(rename_all)]`. When
+//! `JSON_CASING` is set, this layer re-keys every JSON response body so all endpoints
+//! share one convention. Only object keys are rewritten; responses never use user data
+//! (such as counter keys) as object keys, so values are left untouched. MessagePack
+//! responses are recased by [`crate::api::negotiate::Negotiated`] before encoding.
+//!
+//! Bodies of unknown length or larger than [`MAX_JSON_BODY_BYTES`] are passed through
+//! unchanged rather than buffered.
+
+use crate::api::types::ApiError;
+use crate::config::JsonCasing;
+use axum::{
+    body::{to_bytes, Body, HttpBody},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::Value;
+
+/// 读取 JSON 响应体时的最大字节数
+pub const MAX_JSON_BODY_BYTES: usize = 4 * 1024 * 1024;
+
+/// 按配置的命名风格重写 JSON 响应
+pub async fn recase_response(response: Response, casing: JsonCasing) -> Response {
+    let is_json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/json"));
+    let fits = response
+        .body()
+        .size_hint()
+        .upper()
+        .is_some_and(|len| len <= MAX_JSON_BODY_BYTES as u64);
+    if !is_json || !fits {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let Ok(bytes) = to_bytes(body, MAX_JSON_BODY_BYTES).await else {
+        let error = ApiError {
+            message: "Failed to read the response body.".to_string(),
+        };
+        return (StatusCode::INTERNAL_SERVER_ERROR, Json(error)).into_response();
+    };
+    let Ok(value) = serde_json::from_slice::<Value>(&bytes) else {
+        return Response::from_parts(parts, Body::from(bytes));
+    };
+    let Ok(recased) = serde_json::to_vec(&recase_value(value, casing)) else {
+        return Response::from_parts(parts, Body::from(bytes));
+    };
+    parts.headers.remove(header::CONTENT_LENGTH);
+    Response::from_parts(parts, Body::from(recased))
+}
+
+/// 重写 JSON 值中所有对象的键
+pub fn recase_value(value: Value, casing: JsonCasing) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.into_iter()
+                .map(|(k, v)| (recase_key(&k, casing), recase_value(v, casing)))
+                .collect(),
+        ),
+        Value::Array(items) => {
+            Value::Array(items.into_iter().map(|v| recase_value(v, casing)).collect())
+        }
+        other => other,
+    }
+}
+
+fn recase_key(key: &str, casing: JsonCasing) -> String {
+    match casing {
+        JsonCasing::Camel => {
+            let mut out = String::with_capacity(key.len());
+            let mut upper_next = false;
+            for c in key.chars() {
+                if c == '_' {
+                    upper_next = !out.is_empty();
+                } else if upper_next {
+                    out.extend(c.to_uppercase());
+                    upper_next = false;
+                } else {
+                    out.push(c);
+                }
+            }
+            out
+        }
+        JsonCasing::Snake => {
+            let mut out = String::with_capacity(key.len() + 4);
+            for c in key.chars() {
+                if c.is_uppercase() {
+                    if !out.is_empty() {
+                        out.push('_');
+                    }
+                    out.extend(c.to_lowercase());
+                } else {
+                    out.push(c);
+                }
+            }
+            out
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    async fn body(response: Response) -> Vec<u8> {
+        to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap()
+            .to_vec()
+    }
+
+    #[tokio::test]
+    async fn recases_json_bodies() {
+        let response = Json(json!({"rows_affected": 1, "items": [{"day_window": 2}]}));
+        let recased = recase_response(response.into_response(), JsonCasing::Camel).await;
+        let value: Value = serde_json::from_slice(&body(recased).await).unwrap();
+        assert_eq!(
+            value,
+            json!({"rowsAffected": 1, "items": [{"dayWindow": 2}]})
+        );
+    }
+
+    #[tokio::test]
+    async fn oversized_bodies_pass_through() {
+        let payload = json!({"big_value": "x".repeat(MAX_JSON_BODY_BYTES)});
+        let response = Json(&payload).into_response();
+        let recased = recase_response(response, JsonCasing::Camel).await;
+        assert_eq!(recased.status(), StatusCode::OK);
+        let value: Value = serde_json::from_slice(&body(recased).await).unwrap();
+        assert_eq!(value, payload);
+    }
+
+    #[test]
+    fn keys() {
+        assert_eq!(recase_key("per_minute", JsonCasing::Camel), "perMinute");
+        assert_eq!(recase_key("_private", JsonCasing::Camel), "private");
+        assert_eq!(recase_key("perMinute", JsonCasing::Snake), "per_minute");
+        assert_eq!(recase_key("total", JsonCasing::Snake), "total");
+    }
+}
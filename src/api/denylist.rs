@@ -0,0 +1,140 @@
+//! 屏蔽名单的管理接口
+
+use crate::api::auth::require_admin;
+use crate::api::handlers::validate_key;
+use crate::api::types::{ApiError, DenyKind, DenylistEntries, DenylistEntry};
+use crate::config::AppConfig;
+use crate::denylist::Denylist;
+use crate::error::AppError;
+use axum::{
+    extract::Extension,
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use ipnet::IpNet;
+use sqlx::postgres::PgPool;
+use std::net::IpAddr;
+use std::sync::Arc;
+use tracing::info;
+
+/// 把请求中的值转换为存储形式：key 取存储 key，IP 统一为网段
+fn normalize(entry: &DenylistEntry, config: &AppConfig) -> Result<String, AppError> {
+    let value = entry.value.trim();
+    match entry.kind {
+        DenyKind::Key => Ok(config.stored_key(&validate_key(value, config)?)),
+        DenyKind::Ip => value
+            .parse::<IpAddr>()
+            .map(IpNet::from)
+            .or_else(|_| value.parse::<IpNet>().map(|net| net.trunc()))
+            .map(|net| net.to_string())
+            .map_err(|_| {
+                AppError::InvalidInput(format!("`{}` is not an IP address or CIDR range.", value))
+            }),
+    }
+}
+
+/// 列出屏蔽名单
+#[utoipa::path(
+    get,
+    summary = "List the Denylist",
+    description = "Returns every blocked key and IP range. Keys are shown as stored, i.e. as digests when `HASH_KEYS` is on. Requires `Authorization: Bearer <ADMIN_TOKEN>`.",
+    path = "/admin/denylist",
+    tag = "Admin",
+    responses(
+        (status = 200, description = "All denylist entries.", body = DenylistEntries),
+        (status = 401, description = "Missing or invalid admin token", body = ApiError),
+        (status = 500, description = "Database error", body = ApiError)
+    )
+)]
+pub async fn list_denylist_route(
+    headers: HeaderMap,
+    Extension(pool): Extension<PgPool>,
+    Extension(config): Extension<Arc<AppConfig>>,
+) -> Result<Json<DenylistEntries>, AppError> {
+    require_admin(&headers, &config)?;
+    let entries = Denylist::list(&pool)
+        .await?
+        .into_iter()
+        .map(|(kind, value)| DenylistEntry { kind, value })
+        .collect();
+    Ok(Json(DenylistEntries { entries }))
+}
+
+/// 添加屏蔽条目
+#[utoipa::path(
+    post,
+    summary = "Block a Key or IP Range",
+    description = "Adds a key or a client IP range to the denylist. Counting requests for a blocked key are refused; badges for it render as `blocked`. Hits from a blocked IP range get the usual response but are not counted. Other instances pick up the change within 30 seconds. Requires `Authorization: Bearer <ADMIN_TOKEN>`.",
+    path = "/admin/denylist",
+    tag = "Admin",
+    request_body = DenylistEntry,
+    responses(
+        (status = 201, description = "Entry added.", body = DenylistEntry),
+        (status = 200, description = "Entry was already present.", body = DenylistEntry),
+        (status = 400, description = "Invalid key or IP range", body = ApiError),
+        (status = 401, description = "Missing or invalid admin token", body = ApiError),
+        (status = 500, description = "Database error", body = ApiError)
+    )
+)]
+pub async fn add_denylist_route(
+    headers: HeaderMap,
+    Extension(pool): Extension<PgPool>,
+    Extension(config): Extension<Arc<AppConfig>>,
+    Extension(denylist): Extension<Arc<Denylist>>,
+    Json(entry): Json<DenylistEntry>,
+) -> Result<impl IntoResponse, AppError> {
+    require_admin(&headers, &config)?;
+    let value = normalize(&entry, &config)?;
+    let added = denylist.add(&pool, entry.kind, &value).await?;
+    info!("Blocked {} {}", entry.kind.as_str(), entry.value.trim());
+    let status = if added {
+        StatusCode::CREATED
+    } else {
+        StatusCode::OK
+    };
+    Ok((
+        status,
+        Json(DenylistEntry {
+            kind: entry.kind,
+            value,
+        }),
+    ))
+}
+
+/// 删除屏蔽条目
+#[utoipa::path(
+    delete,
+    summary = "Unblock a Key or IP Range",
+    description = "Removes a key or an IP range from the denylist. IP ranges must be given exactly as they were added (a single address stands for its `/32` or `/128` range). Requires `Authorization: Bearer <ADMIN_TOKEN>`.",
+    path = "/admin/denylist",
+    tag = "Admin",
+    request_body = DenylistEntry,
+    responses(
+        (status = 200, description = "Entry removed.", body = DenylistEntry),
+        (status = 400, description = "Invalid key or IP range", body = ApiError),
+        (status = 401, description = "Missing or invalid admin token", body = ApiError),
+        (status = 404, description = "No such entry", body = ApiError),
+        (status = 500, description = "Database error", body = ApiError)
+    )
+)]
+pub async fn remove_denylist_route(
+    headers: HeaderMap,
+    Extension(pool): Extension<PgPool>,
+    Extension(config): Extension<Arc<AppConfig>>,
+    Extension(denylist): Extension<Arc<Denylist>>,
+    Json(entry): Json<DenylistEntry>,
+) -> Result<Json<DenylistEntry>, AppError> {
+    require_admin(&headers, &config)?;
+    let value = normalize(&entry, &config)?;
+    if !denylist.remove(&pool, entry.kind, &value).await? {
+        return Err(AppError::NotFound(
+            "The entry is not on the denylist.".to_string(),
+        ));
+    }
+    info!("Unblocked {} {}", entry.kind.as_str(), entry.value.trim());
+    Ok(Json(DenylistEntry {
+        kind: entry.kind,
+        value,
+    }))
+}
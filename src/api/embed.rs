@@ -0,0 +1,169 @@
+//! 可嵌入的 HTML 计数组件
+//!
+//! `/embed/{key}` counts a hit like `/svg/{key}` and returns a small self-contained HTML
+//! page showing the label and the total, meant to be loaded in an `<iframe>`. With
+//! `live=true` an inline script subscribes to `/ws` with the binary protocol, which carries
+//! each key's new total, and updates the count whenever the key is hit again.
+
+use crate::api::extract::Query;
+use crate::api::handlers::{badge_label, validate_key, SURROGATE_CONTROL};
+use crate::api::hit::HitContext;
+use crate::api::types::{ApiError, CountFormat, EmbedParams};
+use crate::api::ws::BINARY_PROTOCOL;
+use crate::error::AppError;
+use crate::format::{capped_text, escape_xml, format_count, sanitize_text, BLOCKED_MESSAGE};
+use axum::{
+    extract::Path,
+    http::{header, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+
+/// 允许的字号范围（像素）
+const MIN_FONT_SIZE: u32 = 8;
+const MAX_FONT_SIZE: u32 = 72;
+
+/// 断线后重连的间隔（毫秒）
+const RECONNECT_MS: u32 = 5000;
+
+/// 嵌入组件接口
+#[utoipa::path(
+    get,
+    path = "/embed/{key}",
+    tag = "Badge",
+    summary = "Get an Embeddable HTML Counter",
+    description = "Increments the counter like `/svg/{key}` and returns a self-contained HTML page with the label and the total, to be embedded with an `<iframe>`. `color`, `background` and `font_size` style the text. With `live=true` the page keeps the count up to date over `/ws`, so `ALLOWED_ORIGINS` must include this service's own origin when it is set.",
+    params(
+        ("key" = String, Path, description = "The key to count."),
+        EmbedParams
+    ),
+    responses(
+        (status = 200, description = "The HTML page.", content_type = "text/html", body = String),
+        (status = 400, description = "Invalid key or styling parameter", body = ApiError),
+        (status = 500, description = "Database error", body = ApiError)
+    )
+)]
+pub async fn embed_route(
+    Path(key): Path<String>,
+    Query(params): Query<EmbedParams>,
+    hit: HitContext,
+) -> Result<Response, AppError> {
+    let key = validate_key(&key, &hit.config)?;
+    let color = css_color(&params.color, "color")?;
+    let background = css_color(&params.background, "background")?;
+    if !(MIN_FONT_SIZE..=MAX_FONT_SIZE).contains(&params.font_size) {
+        return Err(AppError::InvalidInput(format!(
+            "`font_size` must be between {} and {}.",
+            MIN_FONT_SIZE, MAX_FONT_SIZE
+        )));
+    }
+    let denied = hit.is_denied(&key);
+    let (count, mut headers) = if denied {
+        (BLOCKED_MESSAGE.to_string(), Default::default())
+    } else {
+        let outcome = hit.record(&key).await?;
+        let count = format_count(
+            outcome.total,
+            CountFormat::Plain,
+            params.separator,
+            &hit.config,
+        );
+        (count, outcome.headers)
+    };
+    let label = badge_label(
+        params.label,
+        params.lang.as_deref(),
+        "Hits",
+        &hit.headers,
+        &mut headers,
+    );
+    let label = escape_xml(&sanitize_text(&label));
+    let script = if params.live && !denied {
+        live_script(&key, params.separator.as_char(), &hit)
+    } else {
+        String::new()
+    };
+    let html = format!(
+        concat!(
+            r#"<!DOCTYPE html><html><head><meta charset="utf-8">"#,
+            r#"<meta name="viewport" content="width=device-width,initial-scale=1">"#,
+            "<title>{label}</title><style>",
+            "html,body{{margin:0;background:{background}}}",
+            r#"body{{color:{color};font:{font_size}px/1.4 -apple-system,BlinkMacSystemFont,"Segoe UI",Helvetica,Arial,sans-serif}}"#,
+            ".hits-label{{opacity:.75;margin-right:.4em}}",
+            ".hits-count{{font-weight:600;font-variant-numeric:tabular-nums}}",
+            "</style></head><body>",
+            r#"<span class="hits-label">{label}</span><span class="hits-count" id="hits-count">{count}</span>"#,
+            "{script}</body></html>"
+        ),
+        label = label,
+        background = background,
+        color = color,
+        font_size = params.font_size,
+        count = escape_xml(&count),
+        script = script,
+    );
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("text/html; charset=utf-8"),
+    );
+    headers.insert(
+        header::CACHE_CONTROL,
+        HeaderValue::from_static("no-cache, no-store, must-revalidate"),
+    );
+    headers.insert(SURROGATE_CONTROL, HeaderValue::from_static("no-store"));
+    Ok((StatusCode::OK, headers, html).into_response())
+}
+
+/// 校验颜色参数，只接受 `#` 十六进制值与颜色名，避免注入 CSS
+fn css_color(value: &str, name: &str) -> Result<String, AppError> {
+    let value = value.trim();
+    let valid = match value.strip_prefix('#') {
+        Some(hex) => {
+            matches!(hex.len(), 3 | 4 | 6 | 8) && hex.chars().all(|c| c.is_ascii_hexdigit())
+        }
+        None => !value.is_empty() && value.chars().all(|c| c.is_ascii_alphabetic()),
+    };
+    if !valid {
+        return Err(AppError::InvalidInput(format!(
+            "`{}` must be a hex color such as `#24292f` or a color name.",
+            name
+        )));
+    }
+    Ok(value.to_ascii_lowercase())
+}
+
+/// 订阅 `/ws` 二进制事件并更新计数的脚本，数字格式与服务端一致
+fn live_script(key: &str, separator: Option<char>, hit: &HitContext) -> String {
+    // JSON 字符串可直接作为 JS 字面量，再转义 `</` 以免提前结束 `<script>`
+    let literal = |value: serde_json::Value| value.to_string().replace("</", "<\\/");
+    format!(
+        concat!(
+            "<script>(function(){{",
+            "var key={key},sep={separator},cap={cap},capped={capped};",
+            r#"var el=document.getElementById("hits-count"),decoder=new TextDecoder();"#,
+            r#"function group(n){{var s=String(n);return sep?s.replace(/\B(?=(\d{{3}})+(?!\d))/g,sep):s}}"#,
+            "function fmt(n){{return cap!==null&&n>cap?capped:group(n)}}",
+            "function varint(b,p){{var v=0,m=1,c;do{{c=b[p.i++];v+=(c&127)*m;m*=128}}while(c&128);return v}}",
+            "function connect(){{",
+            r#"var url=new URL("../ws",location.href);url.protocol=url.protocol==="https:"?"wss:":"ws:";"#,
+            "var ws=new WebSocket(url,{protocol});ws.binaryType=\"arraybuffer\";",
+            "ws.onmessage=function(e){{if(typeof e.data===\"string\")return;",
+            "var b=new Uint8Array(e.data),p={{i:0}};",
+            "while(p.i<b.length){{var n=varint(b,p),k=decoder.decode(b.subarray(p.i,p.i+n));p.i+=n;",
+            "var total=varint(b,p);if(k===key)el.textContent=fmt(total)}}}};",
+            "ws.onclose=function(){{setTimeout(connect,{reconnect})}}}}",
+            "connect()}})();</script>"
+        ),
+        key = literal(key.into()),
+        separator = literal(separator.map(String::from).unwrap_or_default().into()),
+        cap = literal(hit.config.display_cap.into()),
+        capped = literal(
+            hit.config
+                .display_cap
+                .map(|cap| capped_text(cap, &hit.config))
+                .into()
+        ),
+        protocol = literal(BINARY_PROTOCOL.into()),
+        reconnect = RECONNECT_MS,
+    )
+}
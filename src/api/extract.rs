@@ -0,0 +1,116 @@
+//! 通用提取器
+//!
+//! axum's own `Query` rejects malformed query strings with a plain-text 400. This
+//! wrapper turns the rejection into [`AppError::InvalidInput`], so clients get the same
+//! JSON error shape as every other failure, with the offending parameter named.
+//! [`ClientIp`] gives handlers the client address resolved through `TRUSTED_PROXIES`.
+
+use crate::client_ip::resolve_client_ip;
+use crate::config::AppConfig;
+use crate::error::AppError;
+use axum::{
+    extract::{ConnectInfo, FromRequestParts},
+    http::request::Parts,
+};
+use serde::de::DeserializeOwned;
+use std::convert::Infallible;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+
+/// 查询参数提取器，反序列化失败时返回 400 `ApiError`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Query<T>(pub T);
+
+impl<T, S> FromRequestParts<S> for Query<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        match axum::extract::Query::<T>::from_request_parts(parts, state).await {
+            Ok(axum::extract::Query(value)) => Ok(Self(value)),
+            Err(rejection) => Err(AppError::InvalidInput(rejection.body_text())),
+        }
+    }
+}
+
+/// 经受信代理解析后的客户端 IP，没有连接信息时为 `None`
+#[derive(Debug, Clone, Copy)]
+pub struct ClientIp(pub Option<IpAddr>);
+
+impl<S> FromRequestParts<S> for ClientIp
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let Some(ConnectInfo(peer)) = parts.extensions.get::<ConnectInfo<SocketAddr>>() else {
+            return Ok(Self(None));
+        };
+        let ip = match parts.extensions.get::<Arc<AppConfig>>() {
+            Some(config) => resolve_client_ip(peer.ip(), &parts.headers, config),
+            None => peer.ip(),
+        };
+        Ok(Self(Some(ip)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::types::{BadgeStyle, HitBadgeParams};
+    use axum::{
+        body::to_bytes,
+        http::{Request, StatusCode},
+        response::IntoResponse,
+    };
+
+    async fn extract(query: &str) -> Result<HitBadgeParams, AppError> {
+        let (mut parts, ()) = Request::builder()
+            .uri(format!("/svg/key?{}", query))
+            .body(())
+            .unwrap()
+            .into_parts();
+        Query::<HitBadgeParams>::from_request_parts(&mut parts, &())
+            .await
+            .map(|Query(params)| params)
+    }
+
+    /// 拒绝时的状态码与 `ApiError` 消息
+    async fn rejection(query: &str) -> (StatusCode, String) {
+        let error = extract(query).await.expect_err("query is rejected");
+        let response = error.into_response();
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        (status, body["message"].as_str().unwrap().to_string())
+    }
+
+    #[tokio::test]
+    async fn valid_query() {
+        let params = extract("style=for-the-badge&scale=2").await.unwrap();
+        assert!(matches!(params.style, BadgeStyle::ForTheBadge));
+        assert_eq!(params.scale, Some(2.0));
+    }
+
+    #[tokio::test]
+    async fn unknown_enum_variant_names_the_parameter() {
+        let (status, message) = rejection("style=bogus").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(message.contains("style"), "{message}");
+        assert!(message.contains("bogus"), "{message}");
+    }
+
+    #[tokio::test]
+    async fn wrong_type_names_the_parameter() {
+        let (status, message) = rejection("scale=big").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(message.contains("scale"), "{message}");
+        let (status, message) = rejection("animate=maybe").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(message.contains("animate"), "{message}");
+    }
+}
@@ -0,0 +1,104 @@
+//! 地理分布接口
+
+use crate::api::extract::Query;
+use crate::api::handlers::{ensure_exact_access, ensure_public, get_total_count, validate_key};
+use crate::api::types::{ApiError, CountryCount, GeoBreakdown, GeoParams};
+use crate::config::AppConfig;
+use crate::error::AppError;
+use axum::{
+    extract::{Extension, Path},
+    http::HeaderMap,
+    Json,
+};
+use chrono::Utc;
+use sqlx::postgres::PgPool;
+use std::sync::Arc;
+
+/// 数据库操作：记录一次带国家信息的访问
+pub async fn record_country(
+    pool: &PgPool,
+    stored_key: &str,
+    country: &str,
+    window_offset_minutes: i32,
+) -> Result<(), AppError> {
+    sqlx::query!(
+        r#"
+        INSERT INTO geo_counts (key, country, day_window, count)
+        VALUES (
+            $1,
+            $2,
+            DATE_TRUNC('day', NOW() - make_interval(mins => $3), 'UTC')
+                + make_interval(mins => $3),
+            1
+        )
+        ON CONFLICT (key, country, day_window)
+        DO UPDATE SET count = geo_counts.count + 1
+        "#,
+        stored_key,
+        country,
+        window_offset_minutes
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// 按国家统计接口
+#[utoipa::path(
+    get,
+    summary = "Get Country Breakdown for a Key",
+    description = "Returns per-country hit counts for the key without incrementing the counter. Only hits sent with `geo=true` while `GEOIP_DATABASE` is configured are recorded; days start at `WINDOW_OFFSET` minutes past UTC midnight. With `COUNT_NOISE` or `KEY_COUNT_NOISE` the breakdown requires the admin token (`Authorization: Bearer <ADMIN_TOKEN>`).",
+    path = "/geo/{key}",
+    tag = "Main",
+    params(
+        ("key" = String, Path, description = "The key to summarize."),
+        GeoParams
+    ),
+    responses(
+        (status = 200, description = "Country breakdown for the key.", body = GeoBreakdown),
+        (status = 400, description = "Invalid key or range", body = ApiError),
+        (status = 403, description = "The key has a noise bound and no admin token was given", body = ApiError),
+        (status = 404, description = "Key is below `PUBLIC_MIN_TOTAL`", body = ApiError),
+        (status = 500, description = "Database error", body = ApiError)
+    )
+)]
+pub async fn geo_route(
+    Path(key): Path<String>,
+    Query(params): Query<GeoParams>,
+    Extension(pool): Extension<PgPool>,
+    Extension(config): Extension<Arc<AppConfig>>,
+    headers: HeaderMap,
+) -> Result<Json<GeoBreakdown>, AppError> {
+    let key = validate_key(&key, &config)?;
+    let to = params.to.unwrap_or_else(Utc::now);
+    let from = params.from;
+    if from.is_some_and(|from| from >= to) {
+        return Err(AppError::InvalidInput(
+            "`from` must be earlier than `to`.".to_string(),
+        ));
+    }
+
+    let stored_key = config.stored_key(&key);
+    let total = get_total_count(&pool, &stored_key)
+        .await?
+        .saturating_add(config.base_count(&key));
+    ensure_public(total, &config)?;
+    ensure_exact_access(&key, &headers, &config, "Country breakdowns")?;
+    let countries = sqlx::query_as!(
+        CountryCount,
+        r#"
+        SELECT country, SUM(count)::BIGINT AS "count!"
+        FROM geo_counts
+        WHERE key = $1 AND ($2::TIMESTAMPTZ IS NULL OR day_window >= $2) AND day_window < $3
+        GROUP BY country
+        ORDER BY 2 DESC, country
+        "#,
+        stored_key,
+        from,
+        to
+    )
+    .fetch_all(&pool)
+    .await?;
+
+    Ok(Json(GeoBreakdown { key, countries }))
+}
@@ -1,38 +1,89 @@
 use utoipa::OpenApi;
 
-use crate::api::types::{ApiError, AppInfo, BadgeStyle, ShieldsIoBadge};
+use crate::api::stats::get_period_totals;
+use crate::api::types::{
+    ApiError, AppInfo, BadgeMode, BadgePeriod, BadgeStyle, BadgeTheme, CountFormat, DenyKind,
+    HitsMode, HitsRespond, RenameForce, Separator, ShieldsIoBadge, SnippetFormat, StatsMetric,
+};
+use crate::badge;
 use crate::error::AppError;
+use crate::format::{
+    apply_template, count_up_steps, escape_xml, format_count, format_delta, format_number,
+    format_rollover, format_stale, format_trend, sanitize_text, BLOCKED_MESSAGE, COUNT_PLACEHOLDER,
+    ERROR_COLOR, ERROR_MESSAGE, STALE_COLOR,
+};
+use crate::locale;
+use crate::logo;
+use crate::svg;
 use axum::{
-    extract::{Extension, Path},
-    http::{header, HeaderValue, StatusCode},
+    extract::Path,
+    http::{header, HeaderName, HeaderValue, StatusCode},
     response::IntoResponse,
     Json,
 };
-use shields::render_badge_svg;
 use sqlx::postgres::PgPool;
-use std::sync::Arc;
-use tokio::sync::broadcast;
 
-use crate::api::types::HitBadgeParams;
-use axum::{extract::Query, http::HeaderMap, response::Response};
+use crate::api::auth::bearer_matches;
+use crate::api::extract::Query;
+use crate::api::hit::HitContext;
+use crate::api::negotiate::Negotiated;
+use crate::api::types::{HitBadgeParams, HitsParams, RedirectParams, ShieldsBadgeParams};
+use crate::config::{AppConfig, Granularity};
+use crate::grand_total::GrandTotal;
+use crate::noise::CountNoise;
+use crate::slow_query;
+use axum::{extract::Extension, http::HeaderMap, response::Response};
+use chrono::{DateTime, TimeDelta, Utc};
+use rand::RngCore;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::debug;
+use url::Url;
 
 /// OpenAPI 文档结构体
 #[derive(OpenApi)]
 #[openapi(
     components(
-        schemas(BadgeStyle)
+        schemas(BadgeStyle, BadgeMode, BadgePeriod, BadgeTheme, CountFormat, Separator, RenameForce, HitsRespond, HitsMode, StatsMetric, DenyKind, SnippetFormat)
     ),
     tags(
         (name = "Meta", description = "Meta API Endpoints"),
         (name = "Main", description = "Main API Endpoints"),
         (name = "WebSocket", description = "WebSocket Endpoints"),
-        (name = "Badge", description = "Shields.io Badge Endpoint")
+        (name = "Badge", description = "Shields.io Badge Endpoint"),
+        (name = "Admin", description = "Admin Endpoints (require ADMIN_TOKEN)")
     ),
     paths(
         count_increment_route,
+        count_route,
         app_info_route,
         shields_badge_route,
         direct_svg_badge_route,
+        redirect_route,
+        crate::api::stats::stats_route,
+        crate::api::geo::geo_route,
+        crate::api::campaigns::campaigns_route,
+        crate::api::bots::bots_route,
+        crate::api::annotations::annotate_route,
+        crate::api::rename::rename_route,
+        crate::api::verify::verify_route,
+        crate::api::key_rate_limits::get_rate_limit_route,
+        crate::api::key_rate_limits::set_rate_limit_route,
+        crate::api::key_rate_limits::delete_rate_limit_route,
+        crate::api::denylist::list_denylist_route,
+        crate::api::denylist::add_denylist_route,
+        crate::api::denylist::remove_denylist_route,
+        crate::api::spikes::list_spikes_route,
+        crate::api::spikes::lift_spike_route,
+        crate::api::seeds::get_seed_route,
+        crate::api::seeds::set_seed_route,
+        crate::api::seeds::delete_seed_route,
+        crate::api::webhook::webhook_route,
+        crate::api::selftest::selftest_route,
+        crate::api::sprite::sprite_route,
+        crate::api::snippet::snippet_route,
+        crate::api::embed::embed_route,
+        crate::api::metrics::key_metrics_route,
     ),
     info(
         title = "Hits API",
@@ -52,94 +103,589 @@ Provides a `/badge/{key}` endpoint compatible with shields.io."#,
 )]
 pub struct ApiDoc;
 
+/// 趋势模式默认比较的区间长度（小时）
+const DEFAULT_TREND_HOURS: i32 = 24;
+/// 趋势模式允许的最大区间长度（小时）
+const MAX_TREND_HOURS: i32 = 24 * 366;
+
+/// `message_template` 允许的最大字符数
+const MAX_TEMPLATE_CHARS: usize = 64;
+
+/// `scale` 允许的范围
+const MIN_SCALE: f64 = 0.5;
+const MAX_SCALE: f64 = 10.0;
+
+/// `stale_after` 允许的最大时长（天）
+const MAX_STALE_AFTER_DAYS: i64 = 3660;
+
+/// key 的最大长度（字符）
+pub const MAX_KEY_LENGTH: usize = 256;
+
+/// 按配置规范化 key 并校验是否合法，返回规范化后的 key
+pub fn validate_key(key: &str, config: &AppConfig) -> Result<String, AppError> {
+    let key = config.normalize_key(key);
+    if key.trim().is_empty() {
+        return Err(AppError::InvalidInput("Key must not be empty.".to_string()));
+    }
+    if key.chars().any(char::is_control) {
+        return Err(AppError::InvalidInput(
+            "Key must not contain control characters.".to_string(),
+        ));
+    }
+    if key.chars().count() > MAX_KEY_LENGTH {
+        return Err(AppError::InvalidInput(format!(
+            "Key must be at most {} characters.",
+            MAX_KEY_LENGTH
+        )));
+    }
+    if config.blocked_key_patterns.is_match(&key) {
+        debug!("Rejected blocked key: {}", key);
+        return Err(AppError::Forbidden("This key is not allowed.".to_string()));
+    }
+    Ok(key)
+}
+
+/// SVG 徽章 `mode=total` 的显示文本
+fn total_message(total: i64, params: &HitBadgeParams, config: &AppConfig) -> String {
+    let count = match params.rollover {
+        Some(modulus) => format_rollover(total, modulus),
+        None => format_count(total, params.format, params.separator, config),
+    };
+    match params.delta_from {
+        Some(from) => format!(
+            "{} {}",
+            count,
+            format_delta(total, from, params.format, params.separator)
+        ),
+        None => count,
+    }
+}
+
+/// 徽章标签：未指定 `label` 时按 `lang` 或 `Accept-Language` 翻译 `english`
+///
+/// Adds `Vary: Accept-Language` to `response_headers` when the label depends on it.
+pub fn badge_label(
+    label: Option<String>,
+    lang: Option<&str>,
+    english: &str,
+    headers: &HeaderMap,
+    response_headers: &mut HeaderMap,
+) -> String {
+    if let Some(label) = label {
+        return label;
+    }
+    let (translated, varies) = locale::hits_label(lang, headers);
+    if varies {
+        response_headers.append(header::VARY, HeaderValue::from_static("accept-language"));
+    }
+    translated.unwrap_or(english).to_string()
+}
+
+/// 校验 `delta_from` 参数
+fn validate_delta_from(delta_from: Option<i64>) -> Result<(), AppError> {
+    match delta_from {
+        Some(from) if from < 0 => Err(AppError::InvalidInput(
+            "`delta_from` must not be negative.".to_string(),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// 只读接口：总数低于 `PUBLIC_MIN_TOTAL` 的 key 视为不存在
+pub fn ensure_public(total: i64, config: &AppConfig) -> Result<(), AppError> {
+    if total < config.public_min_total {
+        return Err(AppError::NotFound("Key not found.".to_string()));
+    }
+    Ok(())
+}
+
+/// 只读接口：加噪声的 key 的精确明细只向管理员返回
+///
+/// Per-day, per-country or per-campaign counts add up to the exact total that `COUNT_NOISE`
+/// hides, so `what` is answered with 403 unless the request carries the admin token.
+pub fn ensure_exact_access(
+    key: &str,
+    headers: &HeaderMap,
+    config: &AppConfig,
+    what: &str,
+) -> Result<(), AppError> {
+    if config.noise_bound(key) == 0 {
+        return Ok(());
+    }
+    let admin = config
+        .admin_token
+        .as_deref()
+        .is_some_and(|token| bearer_matches(headers, token));
+    if !admin {
+        return Err(AppError::Forbidden(format!(
+            "{} of keys with COUNT_NOISE require the admin token.",
+            what
+        )));
+    }
+    Ok(())
+}
+
+/// 让 CDN / 代理（如 GitHub camo）不缓存徽章的响应头
+pub const SURROGATE_CONTROL: HeaderName = HeaderName::from_static("surrogate-control");
+
+/// 回显的 `t` 参数只保留短的字母数字 token，否则生成随机 nonce
+fn badge_nonce(token: Option<&str>) -> String {
+    match token {
+        Some(token)
+            if !token.is_empty()
+                && token.len() <= 64
+                && token
+                    .bytes()
+                    .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_') =>
+        {
+            token.to_string()
+        }
+        _ => {
+            let mut nonce = [0u8; 8];
+            rand::rng().fill_bytes(&mut nonce);
+            hex::encode(nonce)
+        }
+    }
+}
+
+/// 校验 `message_template` 参数
+fn validate_message_template(template: Option<&str>) -> Result<(), AppError> {
+    let Some(template) = template else {
+        return Ok(());
+    };
+    if !template.contains(COUNT_PLACEHOLDER) {
+        return Err(AppError::InvalidInput(format!(
+            "`message_template` must contain `{}`.",
+            COUNT_PLACEHOLDER
+        )));
+    }
+    if template.chars().count() > MAX_TEMPLATE_CHARS {
+        return Err(AppError::InvalidInput(format!(
+            "`message_template` must not exceed {} characters.",
+            MAX_TEMPLATE_CHARS
+        )));
+    }
+    Ok(())
+}
+
+/// 校验 `scale` 参数
+fn validate_scale(scale: Option<f64>) -> Result<(), AppError> {
+    match scale {
+        Some(scale) if !(MIN_SCALE..=MAX_SCALE).contains(&scale) => Err(AppError::InvalidInput(
+            format!("`scale` must be between {} and {}.", MIN_SCALE, MAX_SCALE),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// 校验 `rollover` 参数
+fn validate_rollover(rollover: Option<i64>) -> Result<(), AppError> {
+    match rollover {
+        Some(modulus) if modulus <= 0 => Err(AppError::InvalidInput(
+            "`rollover` must be a positive number.".to_string(),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// 解析 `stale_after`，如 `90s`、`30m`、`1h30m`、`7d`、`2w`
+fn parse_stale_after(stale_after: Option<&str>) -> Result<Option<TimeDelta>, AppError> {
+    let Some(text) = stale_after else {
+        return Ok(None);
+    };
+    let invalid = || {
+        AppError::InvalidInput(format!(
+            "`stale_after` must be a duration like `30m`, `1h30m` or `7d` of at most {} days.",
+            MAX_STALE_AFTER_DAYS
+        ))
+    };
+    let mut total = TimeDelta::zero();
+    let mut rest = text.trim();
+    if rest.is_empty() {
+        return Err(invalid());
+    }
+    while !rest.is_empty() {
+        let digits = rest.bytes().take_while(u8::is_ascii_digit).count();
+        let amount: i64 = rest[..digits].parse().map_err(|_| invalid())?;
+        let unit = rest[digits..].chars().next().ok_or_else(invalid)?;
+        let seconds = match unit {
+            's' => 1,
+            'm' => 60,
+            'h' => 60 * 60,
+            'd' => 24 * 60 * 60,
+            'w' => 7 * 24 * 60 * 60,
+            _ => return Err(invalid()),
+        };
+        let part = amount
+            .checked_mul(seconds)
+            .and_then(TimeDelta::try_seconds)
+            .ok_or_else(invalid)?;
+        total = total.checked_add(&part).ok_or_else(invalid)?;
+        rest = &rest[digits + unit.len_utf8()..];
+    }
+    if total <= TimeDelta::zero() || total > TimeDelta::days(MAX_STALE_AFTER_DAYS) {
+        return Err(invalid());
+    }
+    Ok(Some(total))
+}
+
+/// 数据库操作：最近一次计数所在计数行的起始时间，从未计数时为 `None`
+pub async fn get_last_hit(
+    pool: &PgPool,
+    stored_key: &str,
+) -> Result<Option<DateTime<Utc>>, AppError> {
+    let last_hit = sqlx::query_scalar!(
+        "SELECT MAX(minute_window) FROM counters WHERE key = $1",
+        stored_key
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(last_hit)
+}
+
+/// 最近一次计数是否早于 `stale_after`，从未计数的 key 视为过期
+///
+/// Must run before the request's own hit is recorded, otherwise a counting badge would
+/// never look stale. Hits are stored in buckets of the key's granularity and the last hit
+/// is taken to be the end of the newest bucket, so staleness is only as accurate as the
+/// bucket width.
+async fn is_stale(
+    hit: &HitContext,
+    key: &str,
+    stale_after: Option<TimeDelta>,
+) -> Result<bool, AppError> {
+    let Some(stale_after) = stale_after else {
+        return Ok(false);
+    };
+    let last_hit = get_last_hit(&hit.pool, &hit.config.stored_key(key)).await?;
+    let width = TimeDelta::seconds(hit.config.granularity(key).seconds().into());
+    Ok(last_hit.is_none_or(|last_hit| Utc::now() - (last_hit + width) > stale_after))
+}
+
 // 其余 handler 保持不变
 /// 广播通道类型
-pub type Broadcaster = broadcast::Sender<String>;
+pub use crate::api::ws::Broadcaster;
 
-/// 数据库操作：自增并获取计数
-pub async fn increase_and_get_count(
-    pool: PgPool,
-    key: String,
-    broadcaster: Arc<Broadcaster>,
-) -> i64 {
-    let record = sqlx::query!(
+/// 数据库操作：按指定数量增加计数并返回新的总数
+///
+/// `stored_key` is the key as stored in the database (see [`AppConfig::stored_key`]), and
+/// `granularity` the width of the bucket the hits go into (see [`AppConfig::granularity`]).
+/// Buckets are aligned to the key's day boundary `zone` and `offset_minutes` (see
+/// [`AppConfig::day_boundary`]), so an `hour` or `day` row never straddles one of its days.
+/// Upserts slower than `slow_query` are logged (see [`crate::slow_query`]).
+pub async fn add_to_count(
+    pool: &PgPool,
+    stored_key: &str,
+    amount: i32,
+    granularity: Granularity,
+    zone: &str,
+    offset_minutes: i32,
+    slow_query: Option<Duration>,
+) -> Result<i64, AppError> {
+    let query = sqlx::query!(
         r#"
         WITH updated AS (
             INSERT INTO counters (key, count, minute_window)
-            VALUES ($1, 1, DATE_TRUNC('minute', NOW() AT TIME ZONE 'UTC'))
+            VALUES (
+                $1,
+                $2,
+                DATE_TRUNC($3, NOW() - make_interval(mins => $4), $5) + make_interval(mins => $4)
+            )
             ON CONFLICT (key, minute_window)
-            DO UPDATE SET count = counters.count + 1
+            DO UPDATE SET count = counters.count + $2
             RETURNING key
         )
         SELECT
             (SELECT key FROM updated LIMIT 1) as upserted_key,
-            SUM(c.count) AS total_count
+            (COALESCE(SUM(c.count), 0)
+                + COALESCE((SELECT count FROM key_seeds WHERE key = $1), 0))::BIGINT AS total_count
         FROM counters c
         WHERE c.key = $1;
         "#,
-        key
+        stored_key,
+        i64::from(amount),
+        granularity.as_str(),
+        offset_minutes,
+        zone
+    )
+    .fetch_one(pool);
+    let record = slow_query::timed(slow_query, "add_to_count", stored_key, query).await?;
+    Ok(record
+        .total_count
+        .unwrap_or(0)
+        .saturating_add(i64::from(amount)))
+}
+
+/// 数据库操作：自增计数但不读取总数，时间桶与 [`add_to_count`] 相同
+pub async fn increment_count(
+    pool: &PgPool,
+    stored_key: &str,
+    amount: i32,
+    granularity: Granularity,
+    zone: &str,
+    offset_minutes: i32,
+    slow_query: Option<Duration>,
+) -> Result<(), AppError> {
+    let query = sqlx::query!(
+        r#"
+        INSERT INTO counters (key, count, minute_window)
+        VALUES (
+            $1,
+            $2,
+            DATE_TRUNC($3, NOW() - make_interval(mins => $4), $5) + make_interval(mins => $4)
+        )
+        ON CONFLICT (key, minute_window)
+        DO UPDATE SET count = counters.count + $2
+        "#,
+        stored_key,
+        i64::from(amount),
+        granularity.as_str(),
+        offset_minutes,
+        zone
+    )
+    .execute(pool);
+    slow_query::timed(slow_query, "increment_count", stored_key, query).await?;
+    Ok(())
+}
+
+/// 数据库操作：获取总计数（不自增）
+pub async fn get_total_count(pool: &PgPool, stored_key: &str) -> Result<i64, AppError> {
+    let record = sqlx::query!(
+        r#"
+        SELECT (
+            COALESCE((SELECT SUM(count) FROM counters WHERE key = $1), 0)
+                + COALESCE((SELECT count FROM key_seeds WHERE key = $1), 0)
+        )::BIGINT AS "total_count!";
+        "#,
+        stored_key
     )
-    .fetch_one(&pool)
-    .await
-    .unwrap();
-    broadcaster.send(key.clone()).ok();
-    record.total_count.unwrap_or(0) + 1
+    .fetch_one(pool)
+    .await?;
+    Ok(record.total_count)
 }
 
 /// 计数自增接口
 #[utoipa::path(
     get,
     summary = "Increment and Get Total Hits",
-    description = "Increments a counter for the given key and returns the total count. Broadcasts the key via WebSocket.",
+    description = "Increments a counter for the given key and returns the total count. Broadcasts the key via WebSocket. Returns MessagePack instead of JSON when `Accept` lists `application/msgpack`. With `respond=none` the total is not read back and the response is an empty 204; the broadcast still carries the total when WebSocket subscribers are connected. With `mode=unique` each visitor (salted hash of the client IP) is only counted once per day; with `mode=session` only the first request of a browser session is counted, tracked with a cookie.",
     path = "/hits/{key}",
     tag = "Main",
     params(
-        ("key" = String, Path, description = "The unique key for the counter to increment.")
+        ("key" = String, Path, description = "The unique key for the counter to increment."),
+        HitsParams
     ),
     responses(
-        (status = 200, description = "Successfully incremented and returned total count.", body = i64, example = json!(15)),
+        (status = 200, description = "Successfully incremented and returned total count.",
+         content(
+             (i64 = "application/json", example = json!(15)),
+             (i64 = "application/msgpack")
+         ),
+         headers(
+             ("X-Hits-Limit" = i64, description = "The key's daily quota, if `KEY_DAILY_QUOTAS` configures one"),
+             ("X-Hits-Remaining" = i64, description = "Hits left in today's quota after this one, if a quota applies")
+         )
+        ),
+        (status = 204, description = "Successfully incremented (`respond=none`)."),
+        (status = 400, description = "Invalid key or `amount` out of range", body = ApiError),
+        (status = 401, description = "`amount` without a valid `AMOUNT_TOKEN`", body = ApiError),
+        (status = 429, description = "The key's daily quota is used up", body = ApiError),
         (status = 500, description = "Database error", body = ApiError)
     )
 )]
 pub async fn count_increment_route(
     Path(key): Path<String>,
+    Query(params): Query<HitsParams>,
+    negotiated: Negotiated,
+    mut hit: HitContext,
+) -> Result<Response, AppError> {
+    let key = validate_key(&key, &hit.config)?;
+    hit.mode = params.mode;
+    if let Some(amount) = params.amount {
+        hit.amount = validate_amount(amount, &hit)?;
+    }
+    if params.respond == Some(HitsRespond::None) {
+        let headers = hit.record_silently(&key).await?;
+        return Ok((StatusCode::NO_CONTENT, headers).into_response());
+    }
+    let outcome = hit.record(&key).await?;
+    Ok((outcome.headers, negotiated.respond(outcome.total)).into_response())
+}
+
+/// 校验 `amount`：需要 `AMOUNT_TOKEN`，且不超过 `AMOUNT_MAX`
+fn validate_amount(amount: i32, hit: &HitContext) -> Result<i32, AppError> {
+    let authorized = hit
+        .config
+        .amount_token
+        .as_deref()
+        .is_some_and(|token| bearer_matches(&hit.headers, token));
+    if !authorized {
+        return Err(AppError::Unauthorized(
+            "`amount` requires `Authorization: Bearer <AMOUNT_TOKEN>`.".to_string(),
+        ));
+    }
+    if !(1..=hit.config.amount_max).contains(&amount) {
+        return Err(AppError::InvalidInput(format!(
+            "`amount` must be between 1 and {}.",
+            hit.config.amount_max
+        )));
+    }
+    Ok(amount)
+}
+
+/// 只读计数接口
+#[utoipa::path(
+    get,
+    summary = "Get Total Hits without Incrementing",
+    description = "Returns the total count for the given key without incrementing it, for dashboards and scripts that poll values. Keys below `PUBLIC_MIN_TOTAL` are reported as not found. Returns MessagePack instead of JSON when `Accept` lists `application/msgpack`.",
+    path = "/count/{key}",
+    tag = "Main",
+    params(
+        ("key" = String, Path, description = "The key to read.")
+    ),
+    responses(
+        (status = 200, description = "The key's total count.",
+         content(
+             (i64 = "application/json", example = json!(15)),
+             (i64 = "application/msgpack")
+         )
+        ),
+        (status = 400, description = "Invalid key", body = ApiError),
+        (status = 404, description = "Key is below `PUBLIC_MIN_TOTAL`", body = ApiError),
+        (status = 500, description = "Database error", body = ApiError)
+    )
+)]
+pub async fn count_route(
+    Path(key): Path<String>,
+    negotiated: Negotiated,
     Extension(pool): Extension<PgPool>,
-    Extension(broadcaster): Extension<Arc<Broadcaster>>,
-) -> Result<Json<i64>, AppError> {
-    let total_count_i64 = increase_and_get_count(pool, key.clone(), broadcaster.clone()).await;
-    Ok(Json(total_count_i64))
+    Extension(config): Extension<Arc<AppConfig>>,
+    Extension(noise): Extension<Arc<CountNoise>>,
+) -> Result<Response, AppError> {
+    let key = validate_key(&key, &config)?;
+    let stored_key = config.stored_key(&key);
+    let total = get_total_count(&pool, &stored_key)
+        .await?
+        .saturating_add(config.base_count(&key));
+    ensure_public(total, &config)?;
+    Ok(negotiated.respond(noise.apply(&stored_key, total, config.noise_bound(&key))))
 }
 
 /// Shields.io Badge 查询接口
 #[utoipa::path(
     get,
     summary = "Get Total Hits for Shields.io Badge",
-    description = "Retrieves the total count for the given key, formatted as a JSON response suitable for shields.io. This endpoint does NOT increment the counter. It includes Cache-Control headers to prevent caching.",
+    description = "Increments and retrieves the total count for the given key, formatted as a JSON response suitable for shields.io. `label` and `color` override the defaults; `message` replaces the count entirely, turning this into a static badge source that does not touch the counter. `label_color`, `logo`, `logo_color` and `style` are passed through as shields' `labelColor`, `namedLogo`, `logoColor` and `style`, and omitted from the JSON when not given. With `stale_after` (e.g. `1h`) the badge turns grey and reads `(stale)` when the key's previous hit is older than that, or when it has never been hit. It includes Cache-Control headers to prevent caching.",
     path = "/badge/{key}",
     tag = "Badge",
     params(
-        ("key" = String, Path, description = "The unique key for the counter to retrieve.")
+        ("key" = String, Path, description = "The unique key for the counter to retrieve."),
+        ShieldsBadgeParams
     ),
     responses(
-        (status = 200, description = "Successfully retrieved total count for the badge.", body = ShieldsIoBadge,
-         example = json!({"schemaVersion": 1, "label": "hits", "message": "1234", "color": "blue"}),
+        (status = 200, description = "Successfully retrieved total count for the badge. On a database error this is a red `error` badge with `isError: true`.", body = ShieldsIoBadge,
+         example = json!({"schemaVersion": 1, "label": "hits", "message": "1234", "color": "blue", "labelColor": "555", "namedLogo": "github", "logoColor": "white", "style": "flat-square"}),
         ),
-        (status = 500, description = "Database error", body = ApiError)
+        (status = 400, description = "Invalid key", body = ApiError)
     )
 )]
 pub async fn shields_badge_route(
     Path(key): Path<String>,
-    Extension(pool): Extension<PgPool>,
-    Extension(broadcaster): Extension<Arc<Broadcaster>>,
-) -> Result<impl IntoResponse, AppError> {
-    let total_count = increase_and_get_count(pool, key, broadcaster).await;
+    Query(params): Query<ShieldsBadgeParams>,
+    hit: HitContext,
+) -> Response {
+    let label = params.label.clone();
+    match shields_badge(key, params, hit).await {
+        Ok(response) => response,
+        Err(AppError::DatabaseError(e)) => {
+            // shields.io 只渲染 200 响应，用 `isError` 标记为错误徽章
+            let (mut parts, _) = AppError::DatabaseError(e).into_response().into_parts();
+            parts.status = StatusCode::OK;
+            let badge = ShieldsIoBadge {
+                schema_version: 1,
+                label: label.unwrap_or_else(|| "hits".to_string()),
+                message: ERROR_MESSAGE.to_string(),
+                color: ERROR_COLOR.to_string(),
+                is_error: true,
+                label_color: None,
+                named_logo: None,
+                logo_color: None,
+                style: None,
+            };
+            (parts, Json(badge)).into_response()
+        }
+        Err(e) => e.into_response(),
+    }
+}
+
+async fn shields_badge(
+    key: String,
+    params: ShieldsBadgeParams,
+    hit: HitContext,
+) -> Result<Response, AppError> {
+    let key = validate_key(&key, &hit.config)?;
+    if matches!(params.style, Some(BadgeStyle::Square)) {
+        return Err(AppError::InvalidInput(
+            "`style=square` is only available on `/svg/{key}` and `/sprite`.".to_string(),
+        ));
+    }
+    validate_rollover(params.rollover)?;
+    validate_delta_from(params.delta_from)?;
+    let stale_after = parse_stale_after(params.stale_after.as_deref())?;
+    let mut stale = false;
+    let blocked = hit.is_denied(&key);
+    let (message, hit_headers) = match params.message {
+        _ if blocked => (BLOCKED_MESSAGE.to_string(), HeaderMap::new()),
+        Some(message) => (message, HeaderMap::new()),
+        None => {
+            stale = is_stale(&hit, &key, stale_after).await?;
+            let outcome = hit.record(&key).await?;
+            let message = match params.rollover {
+                Some(modulus) => format_rollover(outcome.total, modulus),
+                None => format_number(outcome.total, params.format, params.separator),
+            };
+            let message = match params.delta_from {
+                Some(from) => format!(
+                    "{} {}",
+                    message,
+                    format_delta(outcome.total, from, params.format, params.separator)
+                ),
+                None => message,
+            };
+            let message = if stale {
+                format_stale(&message)
+            } else {
+                message
+            };
+            (message, outcome.headers)
+        }
+    };
+    let mut hit_headers = hit_headers;
+    let label = badge_label(
+        params.label,
+        params.lang.as_deref(),
+        "hits",
+        &hit.headers,
+        &mut hit_headers,
+    );
     let badge = ShieldsIoBadge {
         schema_version: 1,
-        label: "hits".to_string(),
-        message: total_count.to_string(),
-        color: "blue".to_string(),
+        label,
+        message,
+        color: if blocked {
+            ERROR_COLOR.to_string()
+        } else if stale {
+            STALE_COLOR.to_string()
+        } else {
+            params.color.unwrap_or_else(|| "blue".to_string())
+        },
+        is_error: false,
+        label_color: params.label_color,
+        named_logo: params.logo,
+        logo_color: params.logo_color,
+        style: params.style,
     };
-    let mut response = (StatusCode::OK, Json(badge)).into_response();
+    let mut response = (StatusCode::OK, hit_headers, Json(badge)).into_response();
     response.headers_mut().insert(
         header::CACHE_CONTROL,
         HeaderValue::from_static("no-cache, no-store, must-revalidate"),
@@ -150,6 +696,9 @@ pub async fn shields_badge_route(
     response
         .headers_mut()
         .insert(header::EXPIRES, HeaderValue::from_static("0"));
+    response
+        .headers_mut()
+        .insert(SURROGATE_CONTROL, HeaderValue::from_static("no-store"));
     Ok(response)
 }
 
@@ -159,44 +708,234 @@ pub async fn shields_badge_route(
     path = "/svg/{key}",
     tag = "Badge",
     summary = "Get Total Hits as an SVG Badge with Style Options",
-    description = "Retrieves the total count for the given key, increments it, and returns it as an SVG badge. Supports different visual styles via the `style` query parameter (e.g., 'flat', 'social'). Includes Cache-Control headers.",
+    description = "Retrieves the total count for the given key, increments it, and returns it as an SVG badge. Supports different visual styles via the `style` query parameter (e.g., 'flat', 'social'). With `mode=trend` the badge shows the hits of the last `trend_hours` hours with an arrow, colored green, red or grey depending on the change against the previous period of the same length. With `mode=unique` each visitor (salted hash of the client IP) is only counted once per day; with `mode=session` only the first request of a browser session is counted, tracked with a cookie. With `stale_after` (e.g. `1h`) the badge turns grey and reads `(stale)` when the key's previous hit is older than that, or when it has never been hit. Includes Cache-Control headers.",
     params(
         HitBadgeParams
     ),
     responses(
         (status = 200, description = "Successfully generated and returned the SVG badge.", content_type = "image/svg+xml", body = String),
-        (status = 400, description = "Invalid parameters (e.g., unsupported style, although current implementation falls back)", body = ApiError),
-        (status = 500, description = "Database error or other internal error", body = ApiError)
+        (status = 400, description = "Invalid key or parameters (e.g., unsupported style, although current implementation falls back)", body = ApiError),
+        (status = 500, description = "Database error; the body is a red `error` badge", content_type = "image/svg+xml", body = String)
     )
 )]
 pub async fn direct_svg_badge_route(
     Path(key): Path<String>,
     Query(params): Query<HitBadgeParams>,
-    Extension(pool): Extension<PgPool>,
-    Extension(broadcaster): Extension<Arc<Broadcaster>>,
+    hit: HitContext,
+) -> Response {
+    let style = params.style;
+    let label = params.label.clone();
+    match direct_svg_badge(key, params, hit).await {
+        Ok(response) => response,
+        Err(AppError::DatabaseError(e)) => {
+            // 保留错误状态码与缓存头，只把响应体换成徽章，让 `<img>` 仍能显示
+            let (mut parts, _) = AppError::DatabaseError(e).into_response().into_parts();
+            let label = svg::mask_markup(&sanitize_text(label.as_deref().unwrap_or("Hits")));
+            let svg_string = badge::render(
+                style,
+                &shields::BadgeParams {
+                    style: style.into(),
+                    label: Some(label.as_str()),
+                    message: Some(ERROR_MESSAGE),
+                    label_color: None,
+                    message_color: Some(ERROR_COLOR),
+                    link: None,
+                    extra_link: None,
+                    logo: None,
+                    logo_color: None,
+                },
+            );
+            parts.headers.insert(
+                header::CONTENT_TYPE,
+                HeaderValue::from_static("image/svg+xml;charset=utf-8"),
+            );
+            let svg_string = svg::unmask_markup(&svg_string);
+            (parts, svg::with_view_box(&svg_string, false)).into_response()
+        }
+        Err(e) => e.into_response(),
+    }
+}
+
+async fn direct_svg_badge(
+    key: String,
+    params: HitBadgeParams,
+    mut hit: HitContext,
 ) -> Result<Response, AppError> {
-    let total_count = increase_and_get_count(pool, key.clone(), broadcaster).await;
-    let message_text = total_count.to_string();
-    let style = match params.style {
-        BadgeStyle::Flat => shields::BadgeStyle::Flat,
-        BadgeStyle::FlatSquare => shields::BadgeStyle::FlatSquare,
-        BadgeStyle::Plastic => shields::BadgeStyle::Plastic,
-        BadgeStyle::Social => shields::BadgeStyle::Social,
-        BadgeStyle::ForTheBadge => shields::BadgeStyle::ForTheBadge,
+    let key = validate_key(&key, &hit.config)?;
+    hit.mode = match params.mode {
+        BadgeMode::Unique => Some(HitsMode::Unique),
+        BadgeMode::Session => Some(HitsMode::Session),
+        BadgeMode::Total | BadgeMode::Trend | BadgeMode::TodayTotal => None,
+    };
+    let trend_hours = params.trend_hours.unwrap_or(DEFAULT_TREND_HOURS);
+    if !(1..=MAX_TREND_HOURS).contains(&trend_hours) {
+        return Err(AppError::InvalidInput(format!(
+            "`trend_hours` must be between 1 and {}.",
+            MAX_TREND_HOURS
+        )));
+    }
+    validate_rollover(params.rollover)?;
+    validate_delta_from(params.delta_from)?;
+    validate_scale(params.scale)?;
+    validate_message_template(params.message_template.as_deref())?;
+    if params.period.is_some() && matches!(params.mode, BadgeMode::Trend | BadgeMode::TodayTotal) {
+        return Err(AppError::InvalidInput(
+            "`period` cannot be combined with `mode=trend` or `mode=today-total`.".to_string(),
+        ));
+    }
+    let stale_after = parse_stale_after(params.stale_after.as_deref())?;
+    let custom_logo = params
+        .logo
+        .as_deref()
+        .filter(|logo| logo::is_data_uri(logo))
+        .map(logo::sanitize)
+        .transpose()?;
+    // 计数动画的中间帧
+    let mut count_frames = Vec::new();
+    let (message_text, message_color, hit_headers) = if hit.is_denied(&key) {
+        (BLOCKED_MESSAGE.to_string(), ERROR_COLOR, HeaderMap::new())
+    } else {
+        let stale = is_stale(&hit, &key, stale_after).await?;
+        let outcome = hit.record(&key).await?;
+        let count = match params.period {
+            Some(period) => hit.period_total(&key, period).await?,
+            None => outcome.total,
+        };
+        let (message_text, message_color) = match params.mode {
+            BadgeMode::Total | BadgeMode::Unique | BadgeMode::Session => (
+                total_message(count, &params, &hit.config),
+                params.message_color.as_str(),
+            ),
+            BadgeMode::TodayTotal => {
+                let today = hit.period_total(&key, BadgePeriod::Day).await?;
+                (
+                    format!(
+                        "{} / {}",
+                        format_count(today, params.format, params.separator, &hit.config),
+                        total_message(outcome.total, &params, &hit.config)
+                    ),
+                    params.message_color.as_str(),
+                )
+            }
+            BadgeMode::Trend => {
+                let (current, previous) = get_period_totals(
+                    &hit.pool,
+                    &hit.config.stored_key(&key),
+                    hit.config.granularity(&key),
+                    trend_hours,
+                )
+                .await?;
+                format_trend(
+                    current,
+                    previous,
+                    params.format,
+                    params.separator,
+                    &hit.config,
+                )
+            }
+        };
+        let template = params.message_template.as_deref();
+        let message_text = apply_template(template, message_text);
+        if stale {
+            (format_stale(&message_text), STALE_COLOR, outcome.headers)
+        } else {
+            let counts_up = matches!(
+                params.mode,
+                BadgeMode::Total | BadgeMode::Unique | BadgeMode::Session
+            );
+            if params.animate && counts_up {
+                count_frames = count_up_steps(count)
+                    .into_iter()
+                    .map(|step| total_message(step, &params, &hit.config))
+                    .map(|frame| sanitize_text(&apply_template(template, frame)))
+                    .collect();
+            }
+            (message_text, message_color, outcome.headers)
+        }
     };
     // let svg_generate_params = Builder::flat(){
-    let svg_string = render_badge_svg(&shields::BadgeParams {
-        style,
-        label: Some(params.label.as_str()),
-        message: Some(message_text.as_str()),
+    let mut hit_headers = hit_headers;
+    let label = badge_label(
+        params.label,
+        params.lang.as_deref(),
+        "Hits",
+        &hit.headers,
+        &mut hit_headers,
+    );
+    let label_text = svg::mask_markup(&sanitize_text(&label));
+    let message_text = sanitize_text(&message_text);
+    let message_markup = svg::mask_markup(&message_text);
+    let link = params.link.as_deref().map(escape_xml);
+    let extra_link = params.extra_link.as_deref().map(escape_xml);
+    let badge_params = shields::BadgeParams {
+        style: params.style.into(),
+        label: Some(label_text.as_str()),
+        message: Some(message_markup.as_str()),
         label_color: Some(params.label_color.as_str()),
-        message_color: Some(params.message_color.as_str()),
-        link: params.link.as_deref(),
-        extra_link: params.extra_link.as_deref(),
-        logo: params.logo.as_deref(),
+        message_color: Some(message_color),
+        link: link.as_deref(),
+        extra_link: extra_link.as_deref(),
+        // 自定义 logo 先用占位图标排版，渲染后再替换
+        logo: match custom_logo {
+            Some(_) => Some(logo::PLACEHOLDER),
+            None => params.logo.as_deref(),
+        },
         logo_color: params.logo_color.as_deref(),
-    });
-    let mut headers = HeaderMap::new();
+    };
+    let svg_string = badge::render(params.style, &badge_params);
+    let svg_string = match params.theme {
+        BadgeTheme::Light => svg_string,
+        BadgeTheme::Auto => {
+            // 状态色（过期、趋势、屏蔽）在深色模式下保持不变
+            let dark_message_color = match params.dark_message_color.as_deref() {
+                Some(color) if message_color == params.message_color => color,
+                _ => message_color,
+            };
+            let dark = badge::render(
+                params.style,
+                &shields::BadgeParams {
+                    label_color: Some(params.dark_label_color.as_str()),
+                    message_color: Some(dark_message_color),
+                    ..badge_params
+                },
+            );
+            svg::with_dark_theme(&svg_string, &dark)
+        }
+    };
+    let svg_string = match &custom_logo {
+        Some(logo) => svg::replace_image_href(&svg_string, logo),
+        None => svg_string,
+    };
+    let svg_string = svg::unmask_markup(&svg_string);
+    let svg_string = if params.animate {
+        svg::animate_message(&svg_string, &message_text, &count_frames)
+    } else {
+        svg_string
+    };
+    let svg_string = svg::with_view_box(&svg_string, params.scalable);
+    let svg_string = match params.scale {
+        Some(scale) if !params.scalable => svg::with_scale(&svg_string, scale),
+        _ => svg_string,
+    };
+    // 内嵌到同一文档的不同徽章使用不同的 id 与 class
+    let svg_string = svg::prefix_ids(&svg_string, &svg::content_prefix(&svg_string));
+    // raw 输出用于内嵌到 HTML，不带 XML 声明
+    let svg_string = svg::with_namespaces(
+        &svg_string,
+        hit.config.svg_xml_declaration && !params.raw,
+        hit.config.svg_xlink,
+    );
+    let svg_string = if params.raw {
+        svg::minify(&svg_string)
+    } else {
+        svg_string
+    };
+    let svg_string = if hit.config.badge_nonce {
+        svg::insert_comment(&svg_string, &badge_nonce(params.t.as_deref()))
+    } else {
+        svg_string
+    };
+    let mut headers = hit_headers;
     headers.insert(
         header::CONTENT_TYPE,
         HeaderValue::from_static("image/svg+xml;charset=utf-8"),
@@ -207,25 +946,179 @@ pub async fn direct_svg_badge_route(
     );
     headers.insert(header::PRAGMA, HeaderValue::from_static("no-cache"));
     headers.insert(header::EXPIRES, HeaderValue::from_static("0"));
+    headers.insert(SURROGATE_CONTROL, HeaderValue::from_static("no-store"));
     Ok((StatusCode::OK, headers, svg_string).into_response())
 }
 
+/// 校验跳转目标，防止被滥用为开放重定向
+fn validate_redirect_target(raw: &str, allowed_hosts: &[String]) -> Result<Url, AppError> {
+    let url = Url::parse(raw)
+        .map_err(|_| AppError::InvalidInput("`url` must be an absolute URL.".to_string()))?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(AppError::InvalidInput(
+            "Only http and https redirect targets are allowed.".to_string(),
+        ));
+    }
+    if !url.username().is_empty() || url.password().is_some() {
+        return Err(AppError::InvalidInput(
+            "Redirect targets must not contain credentials.".to_string(),
+        ));
+    }
+    let host = url.host_str().unwrap_or_default().to_ascii_lowercase();
+    let allowed = allowed_hosts
+        .iter()
+        .any(|pattern| match pattern.strip_prefix("*.") {
+            Some(domain) => host
+                .strip_suffix(domain)
+                .is_some_and(|prefix| prefix.ends_with('.')),
+            None => host == *pattern,
+        });
+    if !allowed {
+        return Err(AppError::InvalidInput(format!(
+            "Redirects to host '{}' are not allowed.",
+            host
+        )));
+    }
+    Ok(url)
+}
+
+/// 跳转计数接口
+#[utoipa::path(
+    get,
+    summary = "Count a Click and Redirect",
+    description = "Increments the counter for the given key and redirects (302) to `url`. Only http(s) targets whose host is listed in `REDIRECT_ALLOWED_HOSTS` are accepted. Broadcasts the key via WebSocket.",
+    path = "/go/{key}",
+    tag = "Main",
+    params(
+        ("key" = String, Path, description = "The unique key for the counter to increment."),
+        RedirectParams
+    ),
+    responses(
+        (status = 302, description = "Counted and redirected to the target URL."),
+        (status = 400, description = "Invalid key or missing, malformed or disallowed target URL", body = ApiError),
+        (status = 500, description = "Database error", body = ApiError)
+    )
+)]
+pub async fn redirect_route(
+    Path(key): Path<String>,
+    Query(params): Query<RedirectParams>,
+    hit: HitContext,
+) -> Result<Response, AppError> {
+    let key = validate_key(&key, &hit.config)?;
+    let target = validate_redirect_target(&params.url, &hit.config.redirect_allowed_hosts)?;
+    let outcome = hit.record(&key).await?;
+    let location = HeaderValue::from_str(target.as_str())
+        .map_err(|_| AppError::InvalidInput("`url` is not a valid header value.".to_string()))?;
+    let mut headers = outcome.headers;
+    headers.insert(header::LOCATION, location);
+    headers.insert(
+        header::CACHE_CONTROL,
+        HeaderValue::from_static("no-cache, no-store, must-revalidate"),
+    );
+    Ok((StatusCode::FOUND, headers).into_response())
+}
+
 /// 应用信息接口
 #[utoipa::path(
     get,
     summary = "App Info",
-    description = "Returns information about the application, including API docs, WebSocket endpoint, and badge endpoint example.",
+    description = "Returns information about the application, including API docs, WebSocket endpoint, and badge endpoint example. Returns MessagePack instead of JSON when `Accept` lists `application/msgpack`.",
     path = "/",
     responses(
-        (status = 200, description = "Returns information about the application.", body = AppInfo, example = json!({ "project_name": "Hits", "version": "0.1.0", "docs_path": "/scalar", "websocket_url": "ws://<host>:<port>/ws", "badge_url_example": "http://<host>:<port>/badge/your-key"}))
+        (status = 200, description = "Returns information about the application.",
+         content(
+             (AppInfo = "application/json", example = json!({ "project_name": "Hits", "version": "0.1.0", "docs_path": "/scalar", "total_hits": 123456, "websocket_url": "ws://<host>:<port>/ws", "badge_url_example": "http://<host>:<port>/badge/your-key"})),
+             (AppInfo = "application/msgpack")
+         )
+        )
     ),
     tag = "Meta"
 )]
-pub async fn app_info_route() -> impl IntoResponse {
+pub async fn app_info_route(
+    negotiated: Negotiated,
+    Extension(grand_total): Extension<Arc<GrandTotal>>,
+) -> impl IntoResponse {
     let info = AppInfo {
         project_name: "Hits".to_string(),
         version: env!("CARGO_PKG_VERSION").to_string(),
         docs_path: "/scalar".to_string(),
+        total_hits: grand_total.get(),
     };
-    Json(info)
+    negotiated.respond(info)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn normalizing() -> AppConfig {
+        AppConfig {
+            key_lowercase: true,
+            key_strip_trailing_slash: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn keys_are_exact_by_default() {
+        let config = AppConfig::default();
+        assert_eq!(validate_key("Key/", &config).unwrap(), "Key/");
+        assert_eq!(validate_key(" key ", &config).unwrap(), " key ");
+    }
+
+    #[test]
+    fn case_and_trailing_slashes_are_normalized() {
+        let config = normalizing();
+        for variant in ["key", "Key", "KEY", "key/", "Key//"] {
+            assert_eq!(validate_key(variant, &config).unwrap(), "key", "{variant}");
+        }
+        assert_eq!(validate_key("Docs/Intro/", &config).unwrap(), "docs/intro");
+    }
+
+    #[test]
+    fn lowercasing_covers_unicode() {
+        let config = normalizing();
+        assert_eq!(validate_key("ÉTÉ", &config).unwrap(), "été");
+        assert_eq!(validate_key("ΣΟΦΙΑ", &config).unwrap(), "σοφια");
+    }
+
+    #[test]
+    fn unicode_forms_and_percent_escapes_are_kept() {
+        let config = normalizing();
+        // 不做 NFC/NFKC 规范化：组合与分解形式是不同的 key
+        let composed = validate_key("caf\u{e9}", &config).unwrap();
+        let decomposed = validate_key("cafe\u{301}", &config).unwrap();
+        assert_ne!(composed, decomposed);
+        assert_eq!(validate_key("ﬁle", &config).unwrap(), "ﬁle");
+        // 路径参数在此之前已由 axum 解码，剩下的 `%` 是 key 的一部分
+        assert_eq!(validate_key("a%2Fb", &config).unwrap(), "a%2fb");
+    }
+
+    #[test]
+    fn whitespace_is_not_trimmed() {
+        let config = normalizing();
+        assert_eq!(validate_key(" Key ", &config).unwrap(), " key ");
+        assert!(matches!(
+            validate_key("   ", &config),
+            Err(AppError::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn invalid_keys_are_rejected() {
+        let config = normalizing();
+        for key in ["", "/", "//", "a\tb", "a\nb", "a\u{7f}b"] {
+            assert!(
+                matches!(validate_key(key, &config), Err(AppError::InvalidInput(_))),
+                "{key:?}"
+            );
+        }
+        let long = "k".repeat(MAX_KEY_LENGTH + 1);
+        assert!(validate_key(&long, &config).is_err());
+        let longest = "K".repeat(MAX_KEY_LENGTH);
+        assert_eq!(
+            validate_key(&longest, &config).unwrap(),
+            longest.to_lowercase()
+        );
+    }
 }
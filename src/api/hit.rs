@@ -0,0 +1,673 @@
+//! 计数请求的统一入口
+//!
+//! Every handler that increments a counter goes through [`HitContext::record`], which
+//! decides whether the request should be counted before touching the database.
+//! `HEAD` requests are routed to the same handlers and get the same headers, but
+//! never count. [`HitContext::record_silently`] counts the same way without reading
+//! the total back, unless a subscriber is waiting for the broadcast. With
+//! `?mode=unique`, repeated hits from the same visitor on the same day are not counted,
+//! and with `?mode=session` hits from a browser that still holds the session cookie;
+//! `HITS_DEDUP_SECONDS` does the same for short windows. With `RESPECT_DNT`, requests
+//! that opt out of tracking are answered normally but never counted or recorded.
+
+use crate::api::extract::ClientIp;
+use crate::api::geo::record_country;
+use crate::api::handlers::{get_total_count, increment_count, Broadcaster};
+use crate::api::stats::{get_current_period_count, get_today_count};
+use crate::api::types::{BadgePeriod, HitsMode};
+use crate::api::ws::HitEvent;
+use crate::bot_ips::{self, BotNetworks};
+use crate::captcha::{CaptchaVerifier, CAPTCHA_HEADER};
+use crate::challenge::{ChallengeStore, CHALLENGE_HEADER};
+use crate::config::{strip_scheme, AppConfig, BotIpAction};
+use crate::dedup::DedupCache;
+use crate::denylist::Denylist;
+use crate::error::AppError;
+use crate::events::{self, Utm};
+use crate::geo::GeoIp;
+use crate::grand_total::GrandTotal;
+use crate::hll;
+use crate::hot_keys::WriteCoalescer;
+use crate::key_rate_limits::{get_minute_count, KeyRateLimits};
+use crate::noise::CountNoise;
+use crate::privacy::IpHasher;
+use crate::sampling::Sampler;
+use crate::spikes::SpikeGuard;
+use crate::unique::UniqueVisitors;
+use axum::{
+    extract::{Extension, FromRequestParts, Query},
+    http::{header, request::Parts, HeaderMap, HeaderName, HeaderValue, Method},
+    response::{IntoResponse, Response},
+};
+use chrono::{Timelike, Utc};
+use rand::Rng;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use sqlx::postgres::PgPool;
+use std::{net::IpAddr, sync::Arc};
+
+/// 计数所需的依赖与请求信息
+pub struct HitContext {
+    pub pool: PgPool,
+    pub broadcaster: Arc<Broadcaster>,
+    pub config: Arc<AppConfig>,
+    pub challenges: Arc<ChallengeStore>,
+    pub geoip: Arc<GeoIp>,
+    pub grand_total: Arc<GrandTotal>,
+    pub writes: Arc<WriteCoalescer>,
+    pub rate_limits: Arc<KeyRateLimits>,
+    pub sampler: Arc<Sampler>,
+    pub denylist: Arc<Denylist>,
+    pub bot_networks: Arc<BotNetworks>,
+    pub spikes: Arc<SpikeGuard>,
+    pub visitors: Arc<UniqueVisitors>,
+    pub dedup: Arc<DedupCache>,
+    pub hasher: Arc<IpHasher>,
+    pub noise: Arc<CountNoise>,
+    pub captcha: Arc<CaptchaVerifier>,
+    pub method: Method,
+    pub headers: HeaderMap,
+    /// Resolved client address, when the server was started with connect info
+    pub client_ip: Option<IpAddr>,
+    /// Whether the request asked for its country to be recorded (`?geo=true`)
+    pub geo: bool,
+    /// Captcha response token from `?captcha=`; the `X-Hits-Captcha` header takes precedence
+    pub captcha_token: Option<String>,
+    /// How repeated hits are collapsed; set by handlers that accept `?mode=unique|session`
+    pub mode: Option<HitsMode>,
+    /// Campaign parameters from the query string or the `Referer`, stored with hit events
+    pub utm: Utm,
+    /// How much one counted hit adds (1 unless a handler accepted an authorized `?amount=N`)
+    pub amount: i32,
+}
+
+/// 所有计数接口共用的查询参数
+#[derive(Default, Deserialize)]
+struct HitQuery {
+    #[serde(default)]
+    geo: bool,
+    captcha: Option<String>,
+}
+
+/// 剩余配额的响应头
+pub const REMAINING_HEADER: &str = "x-hits-remaining";
+/// 每日配额的响应头
+pub const LIMIT_HEADER: &str = "x-hits-limit";
+
+/// 开启 `RESPECT_DNT` 时，请求是否通过 `DNT: 1` 或 `Sec-GPC: 1` 拒绝追踪
+pub fn opts_out(headers: &HeaderMap, config: &AppConfig) -> bool {
+    config.respect_dnt
+        && ["dnt", "sec-gpc"].iter().any(|name| {
+            headers
+                .get(*name)
+                .is_some_and(|value| value.as_bytes().trim_ascii() == b"1")
+        })
+}
+
+/// 会话 cookie 名：key 可能含有 cookie 名不允许的字符，因此使用摘要
+fn session_cookie_name(stored_key: &str) -> String {
+    let digest = Sha256::digest(stored_key.as_bytes());
+    format!("hits_session_{}", hex::encode(&digest[..8]))
+}
+
+/// 计数结果
+pub struct HitOutcome {
+    /// The total after this request (unchanged when the hit was not counted)
+    pub total: i64,
+    /// Headers the handler must add to its response
+    pub headers: HeaderMap,
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for HitContext {
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Extension(pool) = Extension::<PgPool>::from_request_parts(parts, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+        let Extension(broadcaster) =
+            Extension::<Arc<Broadcaster>>::from_request_parts(parts, state)
+                .await
+                .map_err(IntoResponse::into_response)?;
+        let Extension(config) = Extension::<Arc<AppConfig>>::from_request_parts(parts, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+        let Extension(challenges) =
+            Extension::<Arc<ChallengeStore>>::from_request_parts(parts, state)
+                .await
+                .map_err(IntoResponse::into_response)?;
+        let Extension(geoip) = Extension::<Arc<GeoIp>>::from_request_parts(parts, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+        let Extension(grand_total) = Extension::<Arc<GrandTotal>>::from_request_parts(parts, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+        let Extension(writes) = Extension::<Arc<WriteCoalescer>>::from_request_parts(parts, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+        let Extension(rate_limits) =
+            Extension::<Arc<KeyRateLimits>>::from_request_parts(parts, state)
+                .await
+                .map_err(IntoResponse::into_response)?;
+        let Extension(sampler) = Extension::<Arc<Sampler>>::from_request_parts(parts, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+        let Extension(denylist) = Extension::<Arc<Denylist>>::from_request_parts(parts, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+        let Extension(bot_networks) =
+            Extension::<Arc<BotNetworks>>::from_request_parts(parts, state)
+                .await
+                .map_err(IntoResponse::into_response)?;
+        let Extension(spikes) = Extension::<Arc<SpikeGuard>>::from_request_parts(parts, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+        let Extension(visitors) =
+            Extension::<Arc<UniqueVisitors>>::from_request_parts(parts, state)
+                .await
+                .map_err(IntoResponse::into_response)?;
+        let Extension(dedup) = Extension::<Arc<DedupCache>>::from_request_parts(parts, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+        let Extension(hasher) = Extension::<Arc<IpHasher>>::from_request_parts(parts, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+        let Extension(noise) = Extension::<Arc<CountNoise>>::from_request_parts(parts, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+        let Extension(captcha) =
+            Extension::<Arc<CaptchaVerifier>>::from_request_parts(parts, state)
+                .await
+                .map_err(IntoResponse::into_response)?;
+        let Ok(ClientIp(client_ip)) = ClientIp::from_request_parts(parts, state).await;
+        let utm = match config.hit_events_sample_rate {
+            Some(_) => Utm::from_request(parts.uri.query(), &parts.headers),
+            None => Utm::default(),
+        };
+        // 参数无效时按未开启处理，不影响计数本身
+        let query = Query::<HitQuery>::from_request_parts(parts, state)
+            .await
+            .map(|Query(query)| query)
+            .unwrap_or_default();
+        Ok(Self {
+            pool,
+            broadcaster,
+            config,
+            challenges,
+            geoip,
+            grand_total,
+            writes,
+            rate_limits,
+            sampler,
+            denylist,
+            bot_networks,
+            spikes,
+            visitors,
+            dedup,
+            hasher,
+            noise,
+            captcha,
+            method: parts.method.clone(),
+            headers: parts.headers.clone(),
+            client_ip,
+            geo: query.geo,
+            captcha_token: query.captcha,
+            utm,
+            mode: None,
+            amount: 1,
+        })
+    }
+}
+
+impl HitContext {
+    /// 记录一次访问：满足条件时自增，否则只返回当前总数
+    pub async fn record(&self, key: &str) -> Result<HitOutcome, AppError> {
+        let mut headers = HeaderMap::new();
+        if !self.admit(key, &mut headers).await? {
+            return self.skip(key, headers).await;
+        }
+        let Some(amount) = self.sampled_amount(key) else {
+            return self.skip(key, headers).await;
+        };
+        let total = self
+            .writes
+            .add(
+                &self.pool,
+                &self.config.stored_key(key),
+                amount,
+                self.config.granularity(key),
+                self.config.day_boundary(key),
+            )
+            .await?
+            .saturating_add(self.config.base_count(key));
+        self.grand_total.add(i64::from(amount));
+        let total = self.public_total(key, total);
+        self.broadcast(key, total);
+        self.record_country(key).await?;
+        self.record_visitor(key).await?;
+        self.record_event(key, amount).await?;
+        Ok(HitOutcome { total, headers })
+    }
+
+    /// 记录一次访问但不读取总数，只返回需要附加的响应头
+    pub async fn record_silently(&self, key: &str) -> Result<HeaderMap, AppError> {
+        let mut headers = HeaderMap::new();
+        if !self.admit(key, &mut headers).await? {
+            return Ok(headers);
+        }
+        let Some(amount) = self.sampled_amount(key) else {
+            return Ok(headers);
+        };
+        let stored_key = self.config.stored_key(key);
+        // 合并写入时总数本就由 upsert 返回，不必单独读取
+        let total = if self.writes.is_enabled() {
+            Some(
+                self.writes
+                    .add(
+                        &self.pool,
+                        &stored_key,
+                        amount,
+                        self.config.granularity(key),
+                        self.config.day_boundary(key),
+                    )
+                    .await?,
+            )
+        } else {
+            let (zone, offset_minutes) = self.config.day_boundary(key);
+            increment_count(
+                &self.pool,
+                &stored_key,
+                amount,
+                self.config.granularity(key),
+                zone,
+                offset_minutes,
+                self.config.slow_query_threshold,
+            )
+            .await?;
+            None
+        };
+        self.grand_total.add(i64::from(amount));
+        // 没有订阅者时省去读取总数
+        if self.broadcaster.receiver_count() > 0 {
+            let total = match total {
+                Some(total) => total,
+                None => get_total_count(&self.pool, &stored_key).await?,
+            }
+            .saturating_add(self.config.base_count(key));
+            self.broadcast(key, self.public_total(key, total));
+        }
+        self.record_country(key).await?;
+        self.record_visitor(key).await?;
+        self.record_event(key, amount).await?;
+        Ok(headers)
+    }
+
+    /// 判断本次请求是否计数，并写入挑战与配额相关的响应头
+    async fn admit(&self, key: &str, headers: &mut HeaderMap) -> Result<bool, AppError> {
+        if self.is_denied(key) {
+            return Err(AppError::Forbidden("This key is blocked.".to_string()));
+        }
+        // HEAD 由 GET handler 处理（axum 会丢弃响应体），但不能产生副作用
+        if self.method == Method::HEAD
+            || self.client_ip.is_some_and(|ip| self.denylist.blocks_ip(ip))
+            || opts_out(&self.headers, &self.config)
+            || !self.passes_header_rules()
+            || self.is_bot()
+            || (self.config.browser_only(key) && !self.looks_like_browser_image())
+            || !self.passes_referrer_allowlist(key)
+        {
+            return Ok(false);
+        }
+        if self
+            .client_ip
+            .is_some_and(|ip| self.bot_networks.contains(ip))
+        {
+            if self.config.bot_ip_action == BotIpAction::Tally {
+                bot_ips::record(
+                    &self.pool,
+                    &self.config.stored_key(key),
+                    self.config.window_offset_minutes,
+                )
+                .await?;
+            }
+            return Ok(false);
+        }
+        if self.config.challenge_keys.contains(key) {
+            let redeemed = self
+                .headers
+                .get(CHALLENGE_HEADER)
+                .and_then(|value| value.to_str().ok())
+                .is_some_and(|token| self.challenges.redeem(key, token));
+            if !redeemed {
+                let token = self.challenges.issue(key);
+                if let Ok(value) = HeaderValue::from_str(&token) {
+                    headers.insert(CHALLENGE_HEADER, value);
+                }
+                return Ok(false);
+            }
+        }
+        if self.config.protected_keys.contains(key) && !self.passes_captcha().await {
+            headers.insert(CAPTCHA_HEADER, HeaderValue::from_static("required"));
+            return Ok(false);
+        }
+        if let Some(&limit) = self.config.key_daily_quotas.get(key) {
+            let (zone, offset) = self.config.day_boundary(key);
+            let used = get_today_count(
+                &self.pool,
+                &self.config.stored_key(key),
+                self.config.granularity(key),
+                zone,
+                offset,
+            )
+            .await?;
+            if used + i64::from(self.amount) > limit {
+                return Err(AppError::QuotaExceeded(format!(
+                    "Daily quota of {} hits for this key is used up.",
+                    limit
+                )));
+            }
+            headers.insert(LIMIT_HEADER, HeaderValue::from(limit));
+            headers.insert(
+                REMAINING_HEADER,
+                HeaderValue::from(limit - used - i64::from(self.amount)),
+            );
+        }
+        let stored_key = self.config.stored_key(key);
+        if let Some(limit) = self.rate_limits.get(&stored_key) {
+            // 按自然分钟计算，超出后等到下一分钟开始
+            let used = get_minute_count(&self.pool, &stored_key).await?;
+            if used + i64::from(self.amount) > i64::from(limit) {
+                return Err(AppError::RateLimited(60 - u64::from(Utc::now().second())));
+            }
+        }
+        // 突增限流：超出上限的访问照常响应，只是不计数
+        if let Some(cap) = self.spikes.cap(&stored_key) {
+            if get_minute_count(&self.pool, &stored_key).await? >= cap {
+                return Ok(false);
+            }
+        }
+        // 没有客户端地址时无法识别访客，按普通访问计数
+        let window = self.config.dedup_window(key);
+        if let (false, Some(ip)) = (window.is_zero(), self.client_ip) {
+            if !self.dedup.admit(key, self.hasher.hash(ip), window) {
+                return Ok(false);
+            }
+        }
+        match (self.mode, self.client_ip) {
+            (Some(HitsMode::Unique), Some(ip)) => {
+                self.visitors
+                    .first_visit(&self.pool, &stored_key, &self.hasher.digest(ip))
+                    .await
+            }
+            (Some(HitsMode::Session), _) => Ok(self.start_session(&stored_key, headers)),
+            _ => Ok(true),
+        }
+    }
+
+    /// 请求已带会话 cookie 时返回 false，否则设置 cookie 并返回 true
+    fn start_session(&self, stored_key: &str, headers: &mut HeaderMap) -> bool {
+        let name = session_cookie_name(stored_key);
+        let has_session = self
+            .headers
+            .get_all(header::COOKIE)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(';'))
+            .any(|pair| pair.trim().split_once('=').is_some_and(|(n, _)| n == name));
+        if has_session {
+            return false;
+        }
+        // 徽章通常嵌在其他站点上，跨站请求只会携带 SameSite=None; Secure 的 cookie
+        let cookie = format!(
+            "{}=1; Max-Age={}; Path=/; HttpOnly; Secure; SameSite=None",
+            name,
+            self.config.session_cookie_ttl.as_secs()
+        );
+        if let Ok(value) = HeaderValue::from_str(&cookie) {
+            headers.append(header::SET_COOKIE, value);
+        }
+        true
+    }
+
+    /// `KEY_SAMPLE_RATES` 抽样：返回本次写入的增量，未被抽中时返回 `None`
+    fn sampled_amount(&self, key: &str) -> Option<i32> {
+        match self.config.sample_rate(key) {
+            1 => Some(self.amount),
+            rate => self
+                .sampler
+                .admit(key, rate)
+                // 配置解析时已保证 `rate * AMOUNT_MAX` 不溢出
+                .then_some(rate as i32 * self.amount),
+        }
+    }
+
+    /// key 是否在管理员维护的屏蔽名单中
+    pub fn is_denied(&self, key: &str) -> bool {
+        self.denylist.blocks_key(&self.config.stored_key(key))
+    }
+
+    fn broadcast(&self, key: &str, total: i64) {
+        self.broadcaster
+            .send(HitEvent {
+                key: key.to_string(),
+                total,
+            })
+            .ok();
+    }
+
+    /// `?geo=true` 且 GeoIP 可用时记录国家，查不到国家则忽略
+    async fn record_country(&self, key: &str) -> Result<(), AppError> {
+        if !self.geo || !self.geoip.is_enabled() {
+            return Ok(());
+        }
+        let country = self.client_ip.and_then(|ip| self.geoip.country(ip));
+        match country {
+            Some(country) => {
+                record_country(
+                    &self.pool,
+                    &self.config.stored_key(key),
+                    &country,
+                    self.config.window_offset_minutes,
+                )
+                .await
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// 开启 `TRACK_UNIQUES` 时把访客计入 HyperLogLog sketch
+    async fn record_visitor(&self, key: &str) -> Result<(), AppError> {
+        match (self.config.track_uniques, self.client_ip) {
+            (true, Some(ip)) => {
+                hll::record(
+                    &self.pool,
+                    &self.config.stored_key(key),
+                    self.hasher.hash(ip),
+                    self.config.window_offset_minutes,
+                )
+                .await
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// 开启 `HIT_EVENTS_SAMPLE_RATE` 时按比例写入访问事件，权重为其代表的计数
+    async fn record_event(&self, key: &str, amount: i32) -> Result<(), AppError> {
+        let Some(rate) = self.config.hit_events_sample_rate else {
+            return Ok(());
+        };
+        if !rand::rng().random_ratio(1, rate) {
+            return Ok(());
+        }
+        events::record(
+            &self.pool,
+            &self.config.stored_key(key),
+            &self.headers,
+            &self.utm,
+            amount.saturating_mul(rate as i32),
+        )
+        .await
+    }
+
+    /// 不计数，只返回当前总数
+    async fn skip(&self, key: &str, headers: HeaderMap) -> Result<HitOutcome, AppError> {
+        let total = get_total_count(&self.pool, &self.config.stored_key(key))
+            .await?
+            .saturating_add(self.config.base_count(key));
+        Ok(HitOutcome {
+            total: self.public_total(key, total),
+            headers,
+        })
+    }
+
+    /// 当前自然周期内对外展示的计数，与总数一样加上噪声
+    pub async fn period_total(&self, key: &str, period: BadgePeriod) -> Result<i64, AppError> {
+        let (zone, offset) = self.config.day_boundary(key);
+        let count = get_current_period_count(
+            &self.pool,
+            &self.config.stored_key(key),
+            self.config.granularity(key),
+            period.unit(),
+            zone,
+            offset,
+        )
+        .await?;
+        Ok(self.public_total(key, count))
+    }
+
+    /// 对外展示的总数，配置了 `COUNT_NOISE` 时加上噪声
+    fn public_total(&self, key: &str, total: i64) -> i64 {
+        self.noise.apply(
+            &self.config.stored_key(key),
+            total,
+            self.config.noise_bound(key),
+        )
+    }
+
+    /// 请求是否携带验证码服务接受的 token
+    async fn passes_captcha(&self) -> bool {
+        let token = self
+            .headers
+            .get(CAPTCHA_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .or(self.captcha_token.as_deref())
+            .filter(|token| !token.is_empty());
+        match token {
+            Some(token) => self.captcha.verify(token, self.client_ip).await,
+            None => false,
+        }
+    }
+
+    /// `User-Agent` 是否匹配 `BOT_USER_AGENTS`
+    fn is_bot(&self) -> bool {
+        self.headers
+            .get(header::USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|user_agent| self.config.bot_user_agents.is_match(user_agent))
+    }
+
+    /// 请求是否像浏览器加载图片：`Mozilla/` 开头的 `User-Agent`，`Accept` 含图片类型，
+    /// 且没有 `Sec-Fetch-Dest` 或其值为 `image`（旧浏览器不发送 Fetch Metadata）
+    fn looks_like_browser_image(&self) -> bool {
+        let header = |name| {
+            self.headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_ascii_lowercase)
+        };
+        header(header::USER_AGENT).is_some_and(|user_agent| user_agent.starts_with("mozilla/"))
+            && header(header::ACCEPT).is_some_and(|accept| accept.contains("image/"))
+            && header(HeaderName::from_static("sec-fetch-dest")).is_none_or(|dest| dest == "image")
+    }
+
+    /// 检查 `KEY_REFERRER_ALLOWLIST`：配置了前缀的 key 只计数 `Referer` 匹配的请求
+    fn passes_referrer_allowlist(&self, key: &str) -> bool {
+        let Some(prefixes) = self.config.key_referrer_allowlist.get(key) else {
+            return true;
+        };
+        let Some(referer) = self
+            .headers
+            .get(header::REFERER)
+            .and_then(|value| value.to_str().ok())
+        else {
+            return false;
+        };
+        let referer = strip_scheme(referer).to_ascii_lowercase();
+        prefixes
+            .iter()
+            .any(|prefix| referer.starts_with(prefix.as_str()))
+    }
+
+    /// 检查 `COUNT_REQUIRE_HEADERS` 与 `COUNT_REJECT_HEADERS`
+    fn passes_header_rules(&self) -> bool {
+        self.config
+            .count_require_headers
+            .iter()
+            .all(|rule| rule.matches(&self.headers))
+            && !self
+                .config
+                .count_reject_headers
+                .iter()
+                .any(|rule| rule.matches(&self.headers))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::broadcast;
+
+    /// 不连接数据库的上下文；`admit` 在这些配置下不会查询数据库
+    fn context(method: Method) -> HitContext {
+        let config = Arc::new(AppConfig::default());
+        let (broadcaster, _) = broadcast::channel(16);
+        HitContext {
+            pool: PgPool::connect_lazy("postgres://localhost/unused").unwrap(),
+            broadcaster: Arc::new(broadcaster),
+            challenges: Arc::new(ChallengeStore::new(&config)),
+            geoip: Arc::new(GeoIp::default()),
+            grand_total: Arc::new(GrandTotal::default()),
+            writes: Arc::new(WriteCoalescer::new(false, None)),
+            rate_limits: Arc::new(KeyRateLimits::default()),
+            sampler: Arc::new(Sampler::default()),
+            denylist: Arc::new(Denylist::default()),
+            bot_networks: Arc::new(BotNetworks::default()),
+            spikes: Arc::new(SpikeGuard::default()),
+            visitors: Arc::new(UniqueVisitors::new(&config)),
+            dedup: Arc::new(DedupCache::default()),
+            hasher: Arc::new(IpHasher::new(&config)),
+            noise: Arc::new(CountNoise::new(&config)),
+            captcha: Arc::new(CaptchaVerifier::new(&config)),
+            config,
+            method,
+            headers: HeaderMap::new(),
+            client_ip: None,
+            geo: false,
+            captcha_token: None,
+            mode: None,
+            utm: Utm::default(),
+            amount: 1,
+        }
+    }
+
+    #[tokio::test]
+    async fn head_requests_are_not_counted() {
+        let mut headers = HeaderMap::new();
+        let admitted = context(Method::HEAD)
+            .admit("key", &mut headers)
+            .await
+            .unwrap();
+        assert!(!admitted);
+        assert!(headers.is_empty());
+    }
+
+    #[tokio::test]
+    async fn get_requests_are_counted() {
+        let mut headers = HeaderMap::new();
+        let admitted = context(Method::GET)
+            .admit("key", &mut headers)
+            .await
+            .unwrap();
+        assert!(admitted);
+    }
+}
@@ -0,0 +1,127 @@
+//! key 每分钟上限的管理接口
+
+use crate::api::auth::require_admin;
+use crate::api::handlers::validate_key;
+use crate::api::types::{ApiError, KeyRateLimit, KeyRateLimitRequest};
+use crate::config::AppConfig;
+use crate::error::AppError;
+use crate::key_rate_limits::KeyRateLimits;
+use axum::{
+    extract::{Extension, Path},
+    http::HeaderMap,
+    Json,
+};
+use sqlx::postgres::PgPool;
+use std::sync::Arc;
+use tracing::info;
+
+/// 查询 key 的每分钟上限
+#[utoipa::path(
+    get,
+    summary = "Get a Key's Rate Limit",
+    description = "Returns the maximum number of hits per minute counted for the key, or `null` when none is set. Requires `Authorization: Bearer <ADMIN_TOKEN>`.",
+    path = "/admin/rate-limits/{key}",
+    tag = "Admin",
+    params(
+        ("key" = String, Path, description = "The key to look up.")
+    ),
+    responses(
+        (status = 200, description = "The key's rate limit.", body = KeyRateLimit),
+        (status = 400, description = "Invalid key", body = ApiError),
+        (status = 401, description = "Missing or invalid admin token", body = ApiError)
+    )
+)]
+pub async fn get_rate_limit_route(
+    Path(key): Path<String>,
+    headers: HeaderMap,
+    Extension(config): Extension<Arc<AppConfig>>,
+    Extension(limits): Extension<Arc<KeyRateLimits>>,
+) -> Result<Json<KeyRateLimit>, AppError> {
+    require_admin(&headers, &config)?;
+    let key = validate_key(&key, &config)?;
+    let per_minute = limits.get(&config.stored_key(&key));
+    Ok(Json(KeyRateLimit { key, per_minute }))
+}
+
+/// 设置 key 的每分钟上限
+#[utoipa::path(
+    put,
+    summary = "Set a Key's Rate Limit",
+    description = "Caps how many hits per minute are counted for the key across all instances. Hits beyond the cap are rejected with 429 and a `Retry-After` header until the next minute starts. Other instances pick up the change within 30 seconds. Requires `Authorization: Bearer <ADMIN_TOKEN>`.",
+    path = "/admin/rate-limits/{key}",
+    tag = "Admin",
+    params(
+        ("key" = String, Path, description = "The key to limit.")
+    ),
+    request_body = KeyRateLimitRequest,
+    responses(
+        (status = 200, description = "Rate limit stored.", body = KeyRateLimit),
+        (status = 400, description = "Invalid key or limit", body = ApiError),
+        (status = 401, description = "Missing or invalid admin token", body = ApiError),
+        (status = 500, description = "Database error", body = ApiError)
+    )
+)]
+pub async fn set_rate_limit_route(
+    Path(key): Path<String>,
+    headers: HeaderMap,
+    Extension(pool): Extension<PgPool>,
+    Extension(config): Extension<Arc<AppConfig>>,
+    Extension(limits): Extension<Arc<KeyRateLimits>>,
+    Json(request): Json<KeyRateLimitRequest>,
+) -> Result<Json<KeyRateLimit>, AppError> {
+    require_admin(&headers, &config)?;
+    let key = validate_key(&key, &config)?;
+    if request.per_minute < 1 {
+        return Err(AppError::InvalidInput(
+            "`per_minute` must be at least 1.".to_string(),
+        ));
+    }
+    limits
+        .set(&pool, &config.stored_key(&key), request.per_minute)
+        .await?;
+    info!(
+        "Set rate limit of {} hits/minute for {}",
+        request.per_minute, key
+    );
+    Ok(Json(KeyRateLimit {
+        key,
+        per_minute: Some(request.per_minute),
+    }))
+}
+
+/// 删除 key 的每分钟上限
+#[utoipa::path(
+    delete,
+    summary = "Remove a Key's Rate Limit",
+    description = "Removes the key's per-minute cap. Requires `Authorization: Bearer <ADMIN_TOKEN>`.",
+    path = "/admin/rate-limits/{key}",
+    tag = "Admin",
+    params(
+        ("key" = String, Path, description = "The key to unlimit.")
+    ),
+    responses(
+        (status = 200, description = "Rate limit removed.", body = KeyRateLimit),
+        (status = 400, description = "Invalid key", body = ApiError),
+        (status = 401, description = "Missing or invalid admin token", body = ApiError),
+        (status = 404, description = "The key has no rate limit", body = ApiError),
+        (status = 500, description = "Database error", body = ApiError)
+    )
+)]
+pub async fn delete_rate_limit_route(
+    Path(key): Path<String>,
+    headers: HeaderMap,
+    Extension(pool): Extension<PgPool>,
+    Extension(config): Extension<Arc<AppConfig>>,
+    Extension(limits): Extension<Arc<KeyRateLimits>>,
+) -> Result<Json<KeyRateLimit>, AppError> {
+    require_admin(&headers, &config)?;
+    let key = validate_key(&key, &config)?;
+    if !limits.remove(&pool, &config.stored_key(&key)).await? {
+        return Err(AppError::NotFound("The key has no rate limit.".to_string()));
+    }
+    info!("Removed rate limit for {}", key);
+    Ok(Json(KeyRateLimit {
+        key,
+        per_minute: None,
+    }))
+}
@@ -0,0 +1,42 @@
+//! Prometheus 指标接口
+
+use crate::config::AppConfig;
+use crate::key_metrics::KeyMetrics;
+use crate::slow_query;
+use axum::{
+    extract::Extension,
+    http::{header, HeaderValue},
+    response::IntoResponse,
+};
+use std::sync::Arc;
+
+/// 按 key 导出总数的 Prometheus 接口
+#[utoipa::path(
+    get,
+    summary = "Per-Key Totals in Prometheus Format",
+    description = "Exposes `hits_total{key=\"...\"}` gauges from a periodically refreshed snapshot. Only keys at or above `METRICS_KEYS_MIN_TOTAL` or listed in `METRICS_KEYS_ALLOWLIST` are exported, to bound cardinality. Empty when neither is configured. With `SLOW_QUERY_MS` set, also exposes `hits_slow_queries_total`, the number of counter upserts slower than the threshold.",
+    path = "/metrics/keys",
+    tag = "Meta",
+    responses(
+        (status = 200, description = "Prometheus text exposition.", content_type = "text/plain", body = String)
+    )
+)]
+pub async fn key_metrics_route(
+    Extension(metrics): Extension<Arc<KeyMetrics>>,
+    Extension(config): Extension<Arc<AppConfig>>,
+) -> impl IntoResponse {
+    let mut body = metrics.render();
+    if config.slow_query_threshold.is_some() {
+        body.push_str(&format!(
+            "# HELP hits_slow_queries_total Counter upserts slower than SLOW_QUERY_MS.\n# TYPE hits_slow_queries_total counter\nhits_slow_queries_total {}\n",
+            slow_query::count()
+        ));
+    }
+    (
+        [(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("text/plain; version=0.0.4; charset=utf-8"),
+        )],
+        body,
+    )
+}
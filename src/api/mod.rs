@@ -1,47 +1,219 @@
 //! API 路由与文档集成模块
 
+pub mod annotations;
+pub mod auth;
+pub mod bots;
+pub mod campaigns;
+pub mod casing;
+pub mod denylist;
+pub mod embed;
+pub mod extract;
+pub mod geo;
 pub mod handlers;
+pub mod hit;
+pub mod key_rate_limits;
+pub mod metrics;
+pub mod negotiate;
+pub mod rate_limit;
+pub mod rename;
+pub mod seeds;
+pub mod selftest;
+pub mod snippet;
+pub mod spikes;
+pub mod sprite;
+pub mod stats;
 pub mod types;
+pub mod verify;
+pub mod webhook;
 pub mod ws;
 
 use handlers::ApiDoc;
 use utoipa::OpenApi;
 pub use ws::ws_handler;
 
-use axum::{http::Request, response::Response, routing::get, Extension, Router};
+use crate::bot_ips::BotNetworks;
+use crate::captcha::CaptchaVerifier;
+use crate::challenge::ChallengeStore;
+use crate::client_ip::resolve_client_ip;
+use crate::config::AppConfig;
+use crate::dedup::DedupCache;
+use crate::denylist::Denylist;
+use crate::geo::GeoIp;
+use crate::grand_total::GrandTotal;
+use crate::hot_keys::WriteCoalescer;
+use crate::key_metrics::KeyMetrics;
+use crate::key_rate_limits::KeyRateLimits;
+use crate::noise::CountNoise;
+use crate::privacy::IpHasher;
+use crate::sampling::Sampler;
+use crate::spikes::SpikeGuard;
+use crate::unique::UniqueVisitors;
+use axum::{
+    extract::ConnectInfo,
+    http::Request,
+    middleware::{from_fn_with_state, map_response},
+    response::Response,
+    routing::{delete, get, post},
+    Extension, Router,
+};
 use sqlx::postgres::PgPool;
-use std::sync::Arc;
-use tokio::sync::broadcast;
+use std::{net::SocketAddr, sync::Arc};
 use tower::ServiceBuilder;
 use tower_http::trace::TraceLayer;
 use tracing::{info_span, Span};
 use utoipa_scalar::{Scalar, Servable};
 
 /// 构建 API 路由与中间件
-pub fn create_router(pool: PgPool, broadcaster: Arc<broadcast::Sender<String>>) -> Router {
+///
+/// Handlers publish hit events to `broadcaster`; WebSocket clients subscribe to
+/// `ws_broadcaster`. Both are the same channel unless broadcast coalescing is enabled.
+/// The admin-managed rate limits and denylist are loaded here and reloaded in the
+/// background, next to the spike detection task.
+pub fn create_router(
+    pool: PgPool,
+    broadcaster: Arc<ws::Broadcaster>,
+    ws_broadcaster: Arc<ws::Broadcaster>,
+    config: Arc<AppConfig>,
+    key_metrics: Arc<KeyMetrics>,
+    grand_total: Arc<GrandTotal>,
+) -> Router {
+    use annotations::annotate_route;
+    use bots::bots_route;
+    use campaigns::campaigns_route;
+    use denylist::{add_denylist_route, list_denylist_route, remove_denylist_route};
+    use embed::embed_route;
+    use geo::geo_route;
     use handlers::{
-        app_info_route, count_increment_route, direct_svg_badge_route, shields_badge_route,
+        app_info_route, count_increment_route, count_route, direct_svg_badge_route, redirect_route,
+        shields_badge_route,
     };
-    Router::new()
+    use key_rate_limits::{delete_rate_limit_route, get_rate_limit_route, set_rate_limit_route};
+    use metrics::key_metrics_route;
+    use rename::rename_route;
+    use seeds::{delete_seed_route, get_seed_route, set_seed_route};
+    use selftest::selftest_route;
+    use snippet::snippet_route;
+    use spikes::{lift_spike_route, list_spikes_route};
+    use sprite::sprite_route;
+    use stats::stats_route;
+    use verify::verify_route;
+    use webhook::webhook_route;
+    let json_casing = config.json_casing;
+    let trace_config = config.clone();
+    let challenges = Arc::new(ChallengeStore::new(&config));
+    let geoip = Arc::new(GeoIp::open(&config));
+    let ws_connections = Arc::new(ws::ConnectionLimiter::default());
+    let writes = Arc::new(WriteCoalescer::new(
+        config.coalesce_writes,
+        config.slow_query_threshold,
+    ));
+    let visitors = Arc::new(UniqueVisitors::new(&config));
+    let dedup = Arc::new(DedupCache::default());
+    let hasher = Arc::new(IpHasher::new(&config));
+    let noise = Arc::new(CountNoise::new(&config));
+    let captcha = Arc::new(CaptchaVerifier::new(&config));
+    let sampler = Arc::new(Sampler::default());
+    let rate_limiter = Arc::new(rate_limit::RateLimiter::new(&config));
+    let rate_limits = Arc::new(KeyRateLimits::default());
+    tokio::spawn(crate::key_rate_limits::run(
+        pool.clone(),
+        rate_limits.clone(),
+    ));
+    let denylist = Arc::new(Denylist::default());
+    tokio::spawn(crate::denylist::run(pool.clone(), denylist.clone()));
+    let bot_networks = Arc::new(BotNetworks::default());
+    if !config.bot_ip_lists.is_empty() {
+        tokio::spawn(crate::bot_ips::run(config.clone(), bot_networks.clone()));
+    }
+    let spikes = Arc::new(SpikeGuard::default());
+    tokio::spawn(crate::spikes::run(
+        pool.clone(),
+        config.clone(),
+        spikes.clone(),
+    ));
+    let router = Router::new()
         // API 文档
         .merge(Scalar::with_url("/scalar", ApiDoc::openapi()))
         // API 路由
         .route("/hits/{key}", get(count_increment_route))
+        .route("/hits/{key}/annotate", post(annotate_route))
+        .route("/count/{key}", get(count_route))
+        .route("/rename", post(rename_route))
+        .route("/admin/verify/{key}", get(verify_route))
+        .route(
+            "/admin/denylist",
+            get(list_denylist_route)
+                .post(add_denylist_route)
+                .delete(remove_denylist_route),
+        )
+        .route("/admin/spikes", get(list_spikes_route))
+        .route("/admin/spikes/{key}", delete(lift_spike_route))
+        .route(
+            "/admin/rate-limits/{key}",
+            get(get_rate_limit_route)
+                .put(set_rate_limit_route)
+                .delete(delete_rate_limit_route),
+        )
+        .route(
+            "/admin/seeds/{key}",
+            get(get_seed_route)
+                .put(set_seed_route)
+                .delete(delete_seed_route),
+        )
+        .route("/stats/{key}", get(stats_route))
+        .route("/geo/{key}", get(geo_route))
+        .route("/campaigns/{key}", get(campaigns_route))
+        .route("/bots/{key}", get(bots_route))
         .route("/", get(app_info_route))
+        .route("/selftest", get(selftest_route))
+        .route("/metrics/keys", get(key_metrics_route))
         .route("/badge/{key}", get(shields_badge_route))
         .route("/svg/{key}", get(direct_svg_badge_route))
+        .route("/go/{key}", get(redirect_route))
+        .route("/webhook/{key}", post(webhook_route))
+        .route("/sprite", get(sprite_route))
+        .route("/snippet/{key}", get(snippet_route))
+        .route("/embed/{key}", get(embed_route))
         .route("/ws", get(ws_handler))
         .layer(
             ServiceBuilder::new()
                 .layer(Extension(pool))
-                .layer(Extension(broadcaster.clone()))
+                .layer(Extension(broadcaster))
+                .layer(Extension(config))
+                .layer(Extension(challenges))
+                .layer(Extension(geoip))
+                .layer(Extension(key_metrics))
+                .layer(Extension(grand_total))
+                .layer(Extension(rate_limits))
+                .layer(Extension(denylist))
+                .layer(Extension(bot_networks))
+                .layer(Extension(spikes))
+                .layer(Extension(ws_connections))
+                .layer(Extension(writes))
+                .layer(Extension(visitors))
+                .layer(Extension(dedup))
+                .layer(Extension(hasher))
+                .layer(Extension(noise))
+                .layer(Extension(captcha))
+                .layer(Extension(sampler))
                 .layer(
                     TraceLayer::new_for_http()
-                        .make_span_with(|request: &Request<axum::body::Body>| {
+                        .make_span_with(move |request: &Request<axum::body::Body>| {
+                            // 拒绝追踪的请求不记录客户端地址
+                            let client_ip = request
+                                .extensions()
+                                .get::<ConnectInfo<SocketAddr>>()
+                                .filter(|_| !hit::opts_out(request.headers(), &trace_config))
+                                .map(|ConnectInfo(peer)| {
+                                    resolve_client_ip(peer.ip(), request.headers(), &trace_config)
+                                        .to_string()
+                                })
+                                .unwrap_or_default();
                             info_span!(
                                 "HTTP Request",
                                 method = %request.method(),
                                 uri = %request.uri(),
+                                client_ip = %client_ip,
                             )
                         })
                         .on_response(
@@ -54,7 +226,15 @@ pub fn create_router(pool: PgPool, broadcaster: Arc<broadcast::Sender<String>>)
                                 );
                             },
                         ),
-                ),
+                )
+                // 放在最内层，被拒绝的请求也会被追踪并能读取共享配置
+                .layer(from_fn_with_state(rate_limiter, rate_limit::enforce)),
         )
-        .with_state(broadcaster)
+        .with_state(ws_broadcaster);
+    match json_casing {
+        Some(casing) => router.layer(map_response(move |response: Response| {
+            casing::recase_response(response, casing)
+        })),
+        None => router,
+    }
 }
@@ -0,0 +1,98 @@
+//! 响应格式协商
+//!
+//! Endpoints that take [`Negotiated`] answer with MessagePack when the request's
+//! `Accept` header lists `application/msgpack` (or `application/x-msgpack`), and with
+//! JSON otherwise. Both formats serialize the same response structs; MessagePack maps
+//! use the field names JSON would use, including the `JSON_CASING` override. Error
+//! responses are always JSON.
+
+use crate::api::casing::recase_value;
+use crate::config::{AppConfig, JsonCasing};
+use axum::{
+    extract::{Extension, FromRequestParts},
+    http::{header, request::Parts, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+use std::convert::Infallible;
+use std::sync::Arc;
+use tracing::error;
+
+/// MessagePack 的媒体类型
+pub const MSGPACK_CONTENT_TYPE: &str = "application/msgpack";
+
+/// 从 `Accept` 协商出的响应格式
+#[derive(Debug, Clone, Copy)]
+pub struct Negotiated {
+    msgpack: bool,
+    casing: Option<JsonCasing>,
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for Negotiated {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let casing = Extension::<Arc<AppConfig>>::from_request_parts(parts, state)
+            .await
+            .ok()
+            .and_then(|Extension(config)| config.json_casing);
+        Ok(Self {
+            msgpack: accepts_msgpack(&parts.headers),
+            casing,
+        })
+    }
+}
+
+impl Negotiated {
+    /// 按协商结果序列化响应体
+    pub fn respond<T: Serialize>(self, value: T) -> Response {
+        let vary = [(header::VARY, HeaderValue::from_static("accept"))];
+        if !self.msgpack {
+            return (vary, Json(value)).into_response();
+        }
+        let encoded = match self.casing {
+            Some(casing) => serde_json::to_value(&value)
+                .map_err(|err| err.to_string())
+                .and_then(|value| {
+                    rmp_serde::to_vec_named(&recase_value(value, casing))
+                        .map_err(|err| err.to_string())
+                }),
+            None => rmp_serde::to_vec_named(&value).map_err(|err| err.to_string()),
+        };
+        match encoded {
+            Ok(bytes) => (
+                vary,
+                [(
+                    header::CONTENT_TYPE,
+                    HeaderValue::from_static(MSGPACK_CONTENT_TYPE),
+                )],
+                bytes,
+            )
+                .into_response(),
+            Err(err) => {
+                error!("Failed to encode MessagePack response: {}", err);
+                StatusCode::INTERNAL_SERVER_ERROR.into_response()
+            }
+        }
+    }
+}
+
+/// `Accept` 中是否以非零权重列出了 MessagePack
+fn accepts_msgpack(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(header::ACCEPT)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|range| {
+            let mut params = range.split(';').map(str::trim);
+            let media_type = params.next().unwrap_or_default();
+            let rejected = params
+                .filter_map(|param| param.strip_prefix("q="))
+                .any(|q| q.parse::<f32>().is_ok_and(|q| q <= 0.0));
+            (media_type.eq_ignore_ascii_case(MSGPACK_CONTENT_TYPE)
+                || media_type.eq_ignore_ascii_case("application/x-msgpack"))
+                && !rejected
+        })
+}
@@ -0,0 +1,105 @@
+//! 按客户端 IP 限流
+//!
+//! Each client IP gets a token bucket that holds up to `RATE_LIMIT_BURST` requests and
+//! refills at `RATE_LIMIT_PER_SECOND`. Requests arriving with an empty bucket are rejected
+//! with [`AppError::RateLimited`] before reaching a handler. Buckets that have refilled
+//! completely are dropped periodically, so idle clients cost no memory.
+
+use crate::client_ip::resolve_client_ip;
+use crate::config::AppConfig;
+use crate::error::AppError;
+use axum::{
+    extract::{ConnectInfo, Extension, Request, State},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::debug;
+
+/// 清理满桶的最短间隔
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// 每个客户端 IP 的令牌桶
+pub struct RateLimiter {
+    per_second: f64,
+    burst: f64,
+    buckets: Mutex<Buckets>,
+}
+
+#[derive(Default)]
+struct Buckets {
+    by_ip: HashMap<IpAddr, Bucket>,
+    pruned_at: Option<Instant>,
+}
+
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(config: &AppConfig) -> Self {
+        Self {
+            per_second: config.rate_limit_per_second,
+            burst: config.rate_limit_burst,
+            buckets: Mutex::default(),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.per_second > 0.0
+    }
+
+    /// 取出一个令牌，桶空时返回需要等待的秒数
+    fn acquire(&self, ip: IpAddr) -> Result<(), u64> {
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets
+            .pruned_at
+            .is_none_or(|pruned_at| now.duration_since(pruned_at) >= PRUNE_INTERVAL)
+        {
+            let (per_second, burst) = (self.per_second, self.burst);
+            buckets.by_ip.retain(|_, bucket| {
+                bucket.tokens + now.duration_since(bucket.updated_at).as_secs_f64() * per_second
+                    < burst
+            });
+            buckets.pruned_at = Some(now);
+        }
+        let bucket = buckets.by_ip.entry(ip).or_insert(Bucket {
+            tokens: self.burst,
+            updated_at: now,
+        });
+        let elapsed = now.duration_since(bucket.updated_at).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.per_second).min(self.burst);
+        bucket.updated_at = now;
+        if bucket.tokens < 1.0 {
+            let wait = (1.0 - bucket.tokens) / self.per_second;
+            return Err((wait.ceil() as u64).max(1));
+        }
+        bucket.tokens -= 1.0;
+        Ok(())
+    }
+}
+
+/// 限流中间件，未开启或无法获取客户端地址时直接放行
+pub async fn enforce(
+    State(limiter): State<Arc<RateLimiter>>,
+    Extension(config): Extension<Arc<AppConfig>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let client_ip = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(peer)| resolve_client_ip(peer.ip(), request.headers(), &config));
+    if let (true, Some(ip)) = (limiter.is_enabled(), client_ip) {
+        if let Err(retry_after) = limiter.acquire(ip) {
+            debug!("Rate limited {} for {} seconds", ip, retry_after);
+            return AppError::RateLimited(retry_after).into_response();
+        }
+    }
+    next.run(request).await
+}
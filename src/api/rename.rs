@@ -0,0 +1,222 @@
+//! 键重命名接口
+
+use crate::api::auth::require_admin;
+use crate::api::extract::Query;
+use crate::api::handlers::{get_total_count, validate_key, Broadcaster};
+use crate::api::types::{ApiError, RenameForce, RenameParams, RenameRequest, RenameResult};
+use crate::api::ws::HitEvent;
+use crate::config::AppConfig;
+use crate::error::AppError;
+use crate::key_rate_limits::KeyRateLimits;
+use axum::{extract::Extension, http::HeaderMap, Json};
+use sqlx::postgres::PgPool;
+use std::sync::Arc;
+
+/// 重命名键并保留全部历史
+#[utoipa::path(
+    post,
+    summary = "Rename a Key",
+    description = "Moves all counter rows, country and bot counts, unique-visitor sketches, seeds, annotations, hit events and the per-key rate limit from `from` to `to` in one transaction. Fails with 409 when `to` already has hits or a seed, unless `force=merge` is given, in which case the histories are added together. Both keys are broadcast with their new totals. Requires `Authorization: Bearer <ADMIN_TOKEN>`.",
+    path = "/rename",
+    tag = "Admin",
+    params(RenameParams),
+    request_body = RenameRequest,
+    responses(
+        (status = 200, description = "Key renamed.", body = RenameResult),
+        (status = 400, description = "Invalid key", body = ApiError),
+        (status = 401, description = "Missing or invalid admin token", body = ApiError),
+        (status = 409, description = "Target key already exists", body = ApiError),
+        (status = 500, description = "Database error", body = ApiError)
+    )
+)]
+pub async fn rename_route(
+    Query(params): Query<RenameParams>,
+    headers: HeaderMap,
+    Extension(pool): Extension<PgPool>,
+    Extension(broadcaster): Extension<Arc<Broadcaster>>,
+    Extension(config): Extension<Arc<AppConfig>>,
+    Extension(rate_limits): Extension<Arc<KeyRateLimits>>,
+    Json(request): Json<RenameRequest>,
+) -> Result<Json<RenameResult>, AppError> {
+    require_admin(&headers, &config)?;
+    let from = validate_key(&request.from, &config)?;
+    let to = validate_key(&request.to, &config)?;
+    if from == to {
+        return Err(AppError::InvalidInput(
+            "`from` and `to` must be different keys.".to_string(),
+        ));
+    }
+
+    let stored_from = config.stored_key(&from);
+    let stored_to = config.stored_key(&to);
+    let mut tx = pool.begin().await?;
+    if params.force != Some(RenameForce::Merge) {
+        let exists = sqlx::query_scalar!(
+            r#"
+            SELECT (
+                EXISTS(SELECT 1 FROM counters WHERE key = $1)
+                    OR EXISTS(SELECT 1 FROM key_seeds WHERE key = $1)
+            ) AS "exists!"
+            "#,
+            stored_to
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+        if exists {
+            return Err(AppError::Conflict(format!(
+                "Key `{}` already exists; pass force=merge to merge into it.",
+                to
+            )));
+        }
+    }
+    // 先删后插，冲突时累加，重命名与合并共用同一条语句
+    let counters = sqlx::query!(
+        r#"
+        WITH moved AS (
+            DELETE FROM counters WHERE key = $1
+            RETURNING minute_window, count
+        )
+        INSERT INTO counters (key, count, minute_window)
+        SELECT $2, count, minute_window FROM moved
+        ON CONFLICT (key, minute_window)
+        DO UPDATE SET count = counters.count + EXCLUDED.count
+        "#,
+        stored_from,
+        stored_to
+    )
+    .execute(&mut *tx)
+    .await?;
+    let geo_counts = sqlx::query!(
+        r#"
+        WITH moved AS (
+            DELETE FROM geo_counts WHERE key = $1
+            RETURNING country, day_window, count
+        )
+        INSERT INTO geo_counts (key, country, day_window, count)
+        SELECT $2, country, day_window, count FROM moved
+        ON CONFLICT (key, country, day_window)
+        DO UPDATE SET count = geo_counts.count + EXCLUDED.count
+        "#,
+        stored_from,
+        stored_to
+    )
+    .execute(&mut *tx)
+    .await?;
+    let bot_counts = sqlx::query!(
+        r#"
+        WITH moved AS (
+            DELETE FROM bot_counts WHERE key = $1
+            RETURNING day_window, count
+        )
+        INSERT INTO bot_counts (key, day_window, count)
+        SELECT $2, day_window, count FROM moved
+        ON CONFLICT (key, day_window)
+        DO UPDATE SET count = bot_counts.count + EXCLUDED.count
+        "#,
+        stored_from,
+        stored_to
+    )
+    .execute(&mut *tx)
+    .await?;
+    // 同一天的 HyperLogLog 寄存器取较大值即为两者并集
+    let sketches = sqlx::query!(
+        r#"
+        WITH moved AS (
+            DELETE FROM visitor_sketches WHERE key = $1
+            RETURNING day_window, register, rank
+        )
+        INSERT INTO visitor_sketches (key, day_window, register, rank)
+        SELECT $2, day_window, register, rank FROM moved
+        ON CONFLICT (key, day_window, register)
+        DO UPDATE SET rank = GREATEST(visitor_sketches.rank, EXCLUDED.rank)
+        "#,
+        stored_from,
+        stored_to
+    )
+    .execute(&mut *tx)
+    .await?;
+    let seeds = sqlx::query!(
+        r#"
+        WITH moved AS (
+            DELETE FROM key_seeds WHERE key = $1
+            RETURNING count
+        )
+        INSERT INTO key_seeds (key, count)
+        SELECT $2, count FROM moved
+        ON CONFLICT (key)
+        DO UPDATE SET count = key_seeds.count + EXCLUDED.count, updated_at = NOW()
+        "#,
+        stored_from,
+        stored_to
+    )
+    .execute(&mut *tx)
+    .await?;
+    let annotations = sqlx::query!(
+        "UPDATE annotations SET key = $2 WHERE key = $1",
+        stored_from,
+        stored_to
+    )
+    .execute(&mut *tx)
+    .await?;
+    let events = sqlx::query!(
+        "UPDATE hit_events SET key = $2 WHERE key = $1",
+        stored_from,
+        stored_to
+    )
+    .execute(&mut *tx)
+    .await?;
+    // 合并时保留较严格的上限
+    let per_minute = sqlx::query_scalar!(
+        r#"
+        WITH moved AS (
+            DELETE FROM key_rate_limits WHERE key = $1
+            RETURNING per_minute
+        )
+        INSERT INTO key_rate_limits (key, per_minute)
+        SELECT $2, per_minute FROM moved
+        ON CONFLICT (key)
+        DO UPDATE SET
+            per_minute = LEAST(key_rate_limits.per_minute, EXCLUDED.per_minute),
+            updated_at = NOW()
+        RETURNING per_minute
+        "#,
+        stored_from,
+        stored_to
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    if let Some(per_minute) = per_minute {
+        rate_limits.moved(&stored_from, &stored_to, per_minute);
+    }
+
+    let total = get_total_count(&pool, &stored_to)
+        .await?
+        .saturating_add(config.base_count(&to));
+    broadcaster
+        .send(HitEvent {
+            key: from.clone(),
+            total: config.base_count(&from),
+        })
+        .ok();
+    broadcaster
+        .send(HitEvent {
+            key: to.clone(),
+            total,
+        })
+        .ok();
+
+    Ok(Json(RenameResult {
+        from,
+        to,
+        rows_affected: counters.rows_affected()
+            + geo_counts.rows_affected()
+            + bot_counts.rows_affected()
+            + sketches.rows_affected()
+            + seeds.rows_affected()
+            + annotations.rows_affected()
+            + events.rows_affected()
+            + u64::from(per_minute.is_some()),
+        total,
+    }))
+}
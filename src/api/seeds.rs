@@ -0,0 +1,144 @@
+//! key 起始计数的管理接口
+//!
+//! A seed is a one-off starting count for a key migrated from another service. It is
+//! stored in the `key_seeds` table next to the key's hits, not as a hit itself: totals
+//! returned for the key include it, while the daily series, quotas and the grand total
+//! only count hits recorded here. Unlike `KEY_BASE_COUNTS`, seeds are set at runtime and
+//! follow the key when it is renamed.
+
+use crate::api::auth::require_admin;
+use crate::api::handlers::validate_key;
+use crate::api::types::{ApiError, KeySeed, KeySeedRequest};
+use crate::config::AppConfig;
+use crate::error::AppError;
+use axum::{
+    extract::{Extension, Path},
+    http::HeaderMap,
+    Json,
+};
+use sqlx::postgres::PgPool;
+use std::sync::Arc;
+use tracing::info;
+
+/// 查询 key 的起始计数
+#[utoipa::path(
+    get,
+    summary = "Get a Key's Seed",
+    description = "Returns the starting count carried over for the key, or `null` when none is set. Requires `Authorization: Bearer <ADMIN_TOKEN>`.",
+    path = "/admin/seeds/{key}",
+    tag = "Admin",
+    params(
+        ("key" = String, Path, description = "The key to look up.")
+    ),
+    responses(
+        (status = 200, description = "The key's seed.", body = KeySeed),
+        (status = 400, description = "Invalid key", body = ApiError),
+        (status = 401, description = "Missing or invalid admin token", body = ApiError),
+        (status = 500, description = "Database error", body = ApiError)
+    )
+)]
+pub async fn get_seed_route(
+    Path(key): Path<String>,
+    headers: HeaderMap,
+    Extension(pool): Extension<PgPool>,
+    Extension(config): Extension<Arc<AppConfig>>,
+) -> Result<Json<KeySeed>, AppError> {
+    require_admin(&headers, &config)?;
+    let key = validate_key(&key, &config)?;
+    let count = sqlx::query_scalar!(
+        "SELECT count FROM key_seeds WHERE key = $1",
+        config.stored_key(&key)
+    )
+    .fetch_optional(&pool)
+    .await?;
+    Ok(Json(KeySeed { key, count }))
+}
+
+/// 设置 key 的起始计数
+#[utoipa::path(
+    put,
+    summary = "Seed a Key",
+    description = "Sets a starting count for the key, e.g. the total it had on another service. The seed is added to every total returned for the key but stored apart from its hits, so it does not appear in the daily series, quotas or the grand total. Setting it again replaces the previous seed. Requires `Authorization: Bearer <ADMIN_TOKEN>`.",
+    path = "/admin/seeds/{key}",
+    tag = "Admin",
+    params(
+        ("key" = String, Path, description = "The key to seed.")
+    ),
+    request_body = KeySeedRequest,
+    responses(
+        (status = 200, description = "Seed stored.", body = KeySeed),
+        (status = 400, description = "Invalid key or count", body = ApiError),
+        (status = 401, description = "Missing or invalid admin token", body = ApiError),
+        (status = 500, description = "Database error", body = ApiError)
+    )
+)]
+pub async fn set_seed_route(
+    Path(key): Path<String>,
+    headers: HeaderMap,
+    Extension(pool): Extension<PgPool>,
+    Extension(config): Extension<Arc<AppConfig>>,
+    Json(request): Json<KeySeedRequest>,
+) -> Result<Json<KeySeed>, AppError> {
+    require_admin(&headers, &config)?;
+    let key = validate_key(&key, &config)?;
+    if request.count < 1 {
+        return Err(AppError::InvalidInput(
+            "`count` must be at least 1.".to_string(),
+        ));
+    }
+    sqlx::query!(
+        r#"
+        INSERT INTO key_seeds (key, count)
+        VALUES ($1, $2)
+        ON CONFLICT (key) DO UPDATE SET count = $2, updated_at = NOW()
+        "#,
+        config.stored_key(&key),
+        request.count
+    )
+    .execute(&pool)
+    .await?;
+    info!("Seeded {} with {} hits", key, request.count);
+    Ok(Json(KeySeed {
+        key,
+        count: Some(request.count),
+    }))
+}
+
+/// 删除 key 的起始计数
+#[utoipa::path(
+    delete,
+    summary = "Remove a Key's Seed",
+    description = "Removes the key's starting count; its hits are kept. Requires `Authorization: Bearer <ADMIN_TOKEN>`.",
+    path = "/admin/seeds/{key}",
+    tag = "Admin",
+    params(
+        ("key" = String, Path, description = "The key to unseed.")
+    ),
+    responses(
+        (status = 200, description = "Seed removed.", body = KeySeed),
+        (status = 400, description = "Invalid key", body = ApiError),
+        (status = 401, description = "Missing or invalid admin token", body = ApiError),
+        (status = 404, description = "The key has no seed", body = ApiError),
+        (status = 500, description = "Database error", body = ApiError)
+    )
+)]
+pub async fn delete_seed_route(
+    Path(key): Path<String>,
+    headers: HeaderMap,
+    Extension(pool): Extension<PgPool>,
+    Extension(config): Extension<Arc<AppConfig>>,
+) -> Result<Json<KeySeed>, AppError> {
+    require_admin(&headers, &config)?;
+    let key = validate_key(&key, &config)?;
+    let result = sqlx::query!(
+        "DELETE FROM key_seeds WHERE key = $1",
+        config.stored_key(&key)
+    )
+    .execute(&pool)
+    .await?;
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound("The key has no seed.".to_string()));
+    }
+    info!("Removed seed for {}", key);
+    Ok(Json(KeySeed { key, count: None }))
+}
@@ -0,0 +1,153 @@
+//! 部署自检接口
+
+use crate::api::types::{BadgeStyle, SelfTestCheck, SelfTestReport};
+use crate::badge;
+use axum::{extract::Extension, http::StatusCode, response::IntoResponse, Json};
+use chrono::Utc;
+use shields::{get_text_width, Font};
+use sqlx::postgres::PgPool;
+use xml::reader::{EventReader, XmlEvent};
+
+const SAMPLE_LABEL: &str = "selftest";
+const SAMPLE_MESSAGE: &str = "12345";
+
+/// 部署自检接口
+#[utoipa::path(
+    get,
+    summary = "Self Test",
+    description = "Renders a sample badge in every style and checks it is well-formed SVG, measures sample text with the font metrics, and performs a database write/read round trip on a throwaway key inside a rolled-back transaction. Returns 503 if any check fails.",
+    path = "/selftest",
+    tag = "Meta",
+    responses(
+        (status = 200, description = "All subsystems are functional.", body = SelfTestReport),
+        (status = 503, description = "At least one subsystem failed.", body = SelfTestReport)
+    )
+)]
+pub async fn selftest_route(Extension(pool): Extension<PgPool>) -> impl IntoResponse {
+    let mut checks: Vec<SelfTestCheck> = BadgeStyle::ALL
+        .iter()
+        .map(|&style| {
+            let name = format!("render:{}", style_name(style));
+            check(name, check_render(style))
+        })
+        .collect();
+    checks.push(check("font_metrics".to_string(), check_font_metrics()));
+    checks.push(check("database".to_string(), check_database(&pool).await));
+
+    let ok = checks.iter().all(|check| check.ok);
+    let status = if ok {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(SelfTestReport { ok, checks }))
+}
+
+fn check(name: String, result: Result<(), String>) -> SelfTestCheck {
+    match result {
+        Ok(()) => SelfTestCheck {
+            name,
+            ok: true,
+            detail: None,
+        },
+        Err(detail) => SelfTestCheck {
+            name,
+            ok: false,
+            detail: Some(detail),
+        },
+    }
+}
+
+fn style_name(style: BadgeStyle) -> String {
+    serde_json::to_value(style)
+        .ok()
+        .and_then(|value| value.as_str().map(str::to_string))
+        .unwrap_or_else(|| format!("{:?}", style))
+}
+
+/// 渲染样例徽章并校验为格式良好的 SVG
+fn check_render(style: BadgeStyle) -> Result<(), String> {
+    let svg = badge::render(
+        style,
+        &shields::BadgeParams {
+            style: style.into(),
+            label: Some(SAMPLE_LABEL),
+            message: Some(SAMPLE_MESSAGE),
+            label_color: None,
+            message_color: None,
+            link: None,
+            extra_link: None,
+            logo: None,
+            logo_color: None,
+        },
+    );
+    let mut root = None;
+    for event in EventReader::from_str(&svg) {
+        match event {
+            Ok(XmlEvent::StartElement { name, .. }) if root.is_none() => {
+                root = Some(name.local_name)
+            }
+            Ok(_) => {}
+            Err(e) => return Err(format!("Rendered SVG is not well-formed XML: {}", e)),
+        }
+    }
+    match root.as_deref() {
+        Some("svg") => Ok(()),
+        other => Err(format!("Unexpected root element: {:?}", other)),
+    }
+}
+
+/// 测量样例文本宽度
+fn check_font_metrics() -> Result<(), String> {
+    for font in [
+        Font::VerdanaNormal11,
+        Font::HelveticaBold11,
+        Font::VerdanaNormal10,
+        Font::VerdanaBold10,
+    ] {
+        let width = get_text_width(SAMPLE_LABEL, font.clone());
+        if !width.is_finite() || width <= 0.0 {
+            return Err(format!("Invalid text width {} for {:?}", width, font));
+        }
+    }
+    Ok(())
+}
+
+/// 生成不会与真实 key 冲突的临时 key
+fn throwaway_key() -> String {
+    format!(
+        "__selftest__{}",
+        Utc::now().timestamp_nanos_opt().unwrap_or_default()
+    )
+}
+
+/// 在回滚的事务中对临时 key 做一次写入与读取
+async fn check_database(pool: &PgPool) -> Result<(), String> {
+    let key = throwaway_key();
+    let mut tx = pool.begin().await.map_err(|e| e.to_string())?;
+    sqlx::query!(
+        r#"
+        INSERT INTO counters (key, count, minute_window)
+        VALUES ($1, 1, DATE_TRUNC('minute', NOW() AT TIME ZONE 'UTC'))
+        "#,
+        key
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| e.to_string())?;
+    let record = sqlx::query!(
+        r#"SELECT COALESCE(SUM(count), 0)::BIGINT AS "total!" FROM counters WHERE key = $1"#,
+        key
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| e.to_string())?;
+    tx.rollback().await.map_err(|e| e.to_string())?;
+    if record.total != 1 {
+        return Err(format!(
+            "Expected a total of 1 for the throwaway key, got {}",
+            record.total
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,162 @@
+//! README 嵌入代码接口
+
+use crate::api::extract::Query;
+use crate::api::handlers::validate_key;
+use crate::api::types::{ApiError, SnippetFormat, SnippetParams};
+use crate::config::AppConfig;
+use crate::error::AppError;
+use crate::format::escape_xml;
+use axum::{
+    extract::{Extension, Path, RawQuery},
+    http::{header, HeaderMap, HeaderValue},
+    response::{IntoResponse, Response},
+};
+use std::sync::Arc;
+use url::Url;
+
+/// 徽章默认的替代文本
+const DEFAULT_ALT: &str = "Hits";
+
+/// 嵌入代码接口
+#[utoipa::path(
+    get,
+    path = "/snippet/{key}",
+    tag = "Badge",
+    summary = "Get an Embed Snippet for a Badge",
+    description = "Returns ready-to-paste Markdown, HTML, reStructuredText or AsciiDoc that embeds `/svg/{key}`. Every query parameter except `format` is copied into the badge URL, so `style`, `label`, colors and the other `/svg` options carry over; `link` also wraps the image in a link. The badge URL starts with `PUBLIC_URL`, or with the request's `Host` header when unset. Display-only: counters are NOT incremented.",
+    params(
+        ("key" = String, Path, description = "The key the badge counts."),
+        SnippetParams
+    ),
+    responses(
+        (status = 200, description = "The snippet.", content_type = "text/plain", body = String),
+        (status = 400, description = "Invalid key, or no `PUBLIC_URL` and no usable `Host` header", body = ApiError)
+    )
+)]
+pub async fn snippet_route(
+    Path(key): Path<String>,
+    Query(params): Query<SnippetParams>,
+    RawQuery(query): RawQuery,
+    headers: HeaderMap,
+    Extension(config): Extension<Arc<AppConfig>>,
+) -> Result<Response, AppError> {
+    let key = validate_key(&key, &config)?;
+    let mut badge_url = base_url(&config, &headers)?;
+    badge_url
+        .path_segments_mut()
+        .map_err(|_| AppError::InvalidInput("`PUBLIC_URL` cannot be a base URL.".to_string()))?
+        .pop_if_empty()
+        .extend(["svg", key.as_str()]);
+    let badge_query: Vec<(String, String)> =
+        url::form_urlencoded::parse(query.unwrap_or_default().as_bytes())
+            .filter(|(name, _)| name != "format")
+            .map(|(name, value)| (name.into_owned(), value.into_owned()))
+            .collect();
+    if !badge_query.is_empty() {
+        badge_url.query_pairs_mut().extend_pairs(badge_query);
+    }
+    let alt = params.label.as_deref().unwrap_or(DEFAULT_ALT);
+    let snippet = render_snippet(
+        params.format,
+        badge_url.as_str(),
+        alt,
+        params.link.as_deref(),
+    );
+    Ok((
+        [(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("text/plain; charset=utf-8"),
+        )],
+        snippet,
+    )
+        .into_response())
+}
+
+/// 服务的公开地址：`PUBLIC_URL`，否则按 `Host` 与 `X-Forwarded-Proto` 拼出
+fn base_url(config: &AppConfig, headers: &HeaderMap) -> Result<Url, AppError> {
+    if let Some(url) = &config.public_url {
+        return Ok(url.clone());
+    }
+    let invalid = || {
+        AppError::InvalidInput(
+            "Cannot tell the public address of this service; set `PUBLIC_URL`.".to_string(),
+        )
+    };
+    let host = headers
+        .get(header::HOST)
+        .and_then(|value| value.to_str().ok())
+        .filter(|host| !host.is_empty())
+        .ok_or_else(invalid)?;
+    let scheme = match headers
+        .get("x-forwarded-proto")
+        .and_then(|value| value.to_str().ok())
+    {
+        Some(proto) if proto.eq_ignore_ascii_case("https") => "https",
+        _ => "http",
+    };
+    let url = Url::parse(&format!("{}://{}/", scheme, host)).map_err(|_| invalid())?;
+    // `Host` 只能包含主机与端口
+    if url.path() != "/" || url.query().is_some() || !url.username().is_empty() {
+        return Err(invalid());
+    }
+    Ok(url)
+}
+
+/// 按标记语言生成嵌入代码
+fn render_snippet(format: SnippetFormat, badge_url: &str, alt: &str, link: Option<&str>) -> String {
+    match format {
+        SnippetFormat::Markdown => {
+            let image = format!(
+                "![{}]({})",
+                escape_markdown(alt),
+                encode_brackets(badge_url)
+            );
+            match link {
+                Some(link) => format!("[{}]({})", image, encode_brackets(link)),
+                None => image,
+            }
+        }
+        SnippetFormat::Html => {
+            let image = format!(
+                r#"<img src="{}" alt="{}">"#,
+                escape_xml(badge_url),
+                escape_xml(alt)
+            );
+            match link {
+                Some(link) => format!(r#"<a href="{}">{}</a>"#, escape_xml(link), image),
+                None => image,
+            }
+        }
+        SnippetFormat::Rst => {
+            let mut snippet = format!(".. image:: {}\n   :alt: {}\n", badge_url, alt);
+            if let Some(link) = link {
+                snippet.push_str(&format!("   :target: {}\n", link));
+            }
+            snippet
+        }
+        SnippetFormat::Asciidoc => {
+            let mut attributes = format!("\"{}\"", alt.replace('"', "&quot;"));
+            if let Some(link) = link {
+                attributes.push_str(&format!(",link=\"{}\"", encode_brackets(link)));
+            }
+            format!("image:{}[{}]", encode_brackets(badge_url), attributes)
+        }
+    }
+}
+
+/// 转义 Markdown 链接文本中的方括号
+fn escape_markdown(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('[', "\\[")
+        .replace(']', "\\]")
+}
+
+/// 对会截断 Markdown 与 AsciiDoc 地址的字符做百分号编码
+fn encode_brackets(url: &str) -> String {
+    url.replace('(', "%28")
+        .replace(')', "%29")
+        .replace('[', "%5B")
+        .replace(']', "%5D")
+        .replace('"', "%22")
+        .replace(' ', "%20")
+}
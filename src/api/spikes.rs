@@ -0,0 +1,81 @@
+//! 突增限流的管理接口
+
+use crate::api::auth::require_admin;
+use crate::api::handlers::validate_key;
+use crate::api::types::{ApiError, ThrottledKey, ThrottledKeys};
+use crate::config::AppConfig;
+use crate::error::AppError;
+use crate::spikes::SpikeGuard;
+use axum::{
+    extract::{Extension, Path},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use chrono::Utc;
+use std::sync::Arc;
+use tracing::info;
+
+/// 列出被限流的 key
+#[utoipa::path(
+    get,
+    summary = "List Throttled Keys",
+    description = "Returns the keys currently throttled by spike detection (`SPIKE_FACTOR`) on this instance, largest spike first. Keys are shown as stored, i.e. as digests when `HASH_KEYS` is on. Requires `Authorization: Bearer <ADMIN_TOKEN>`.",
+    path = "/admin/spikes",
+    tag = "Admin",
+    responses(
+        (status = 200, description = "Throttled keys.", body = ThrottledKeys),
+        (status = 401, description = "Missing or invalid admin token", body = ApiError)
+    )
+)]
+pub async fn list_spikes_route(
+    headers: HeaderMap,
+    Extension(config): Extension<Arc<AppConfig>>,
+    Extension(spikes): Extension<Arc<SpikeGuard>>,
+) -> Result<Json<ThrottledKeys>, AppError> {
+    require_admin(&headers, &config)?;
+    let keys = spikes
+        .list()
+        .into_iter()
+        .map(|(key, throttle)| ThrottledKey {
+            key,
+            cap_per_minute: throttle.cap_per_minute,
+            spike_hits: throttle.spike_hits,
+            baseline: throttle.baseline,
+            until: throttle.until,
+        })
+        .collect();
+    Ok(Json(ThrottledKeys { keys }))
+}
+
+/// 解除 key 的突增限流
+#[utoipa::path(
+    delete,
+    summary = "Lift a Spike Throttle",
+    description = "Lifts the key's spike throttle on this instance and exempts it from spike detection for one `SPIKE_THROTTLE_MINUTES` period, e.g. after a legitimate launch. Also exempts keys that are not throttled yet. Requires `Authorization: Bearer <ADMIN_TOKEN>`.",
+    path = "/admin/spikes/{key}",
+    tag = "Admin",
+    params(
+        ("key" = String, Path, description = "The key to release.")
+    ),
+    responses(
+        (status = 204, description = "Throttle lifted and key exempted."),
+        (status = 400, description = "Invalid key", body = ApiError),
+        (status = 401, description = "Missing or invalid admin token", body = ApiError)
+    )
+)]
+pub async fn lift_spike_route(
+    Path(key): Path<String>,
+    headers: HeaderMap,
+    Extension(config): Extension<Arc<AppConfig>>,
+    Extension(spikes): Extension<Arc<SpikeGuard>>,
+) -> Result<StatusCode, AppError> {
+    require_admin(&headers, &config)?;
+    let key = validate_key(&key, &config)?;
+    let exempt_until = Utc::now() + config.spike_throttle;
+    let lifted = spikes.lift(&config.stored_key(&key), exempt_until);
+    info!(
+        "Exempted {} from spike detection until {} (was throttled: {})",
+        key, exempt_until, lifted
+    );
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,172 @@
+//! 徽章拼图接口
+
+use crate::api::extract::Query;
+use crate::api::handlers::{validate_key, SURROGATE_CONTROL};
+use crate::api::types::{ApiError, SpriteParams};
+use crate::badge;
+use crate::config::AppConfig;
+use crate::error::AppError;
+use crate::format::sanitize_text;
+use crate::noise::CountNoise;
+use crate::svg;
+use axum::{
+    extract::Extension,
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+use sqlx::postgres::PgPool;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// 单次请求最多渲染的徽章数量
+pub const MAX_SPRITE_KEYS: usize = 50;
+
+/// 徽章之间的间距（像素）
+const SPRITE_GAP: f64 = 4.0;
+
+/// 徽章拼图接口
+#[utoipa::path(
+    get,
+    path = "/sprite",
+    tag = "Badge",
+    summary = "Get Several Badges as One SVG",
+    description = "Renders one badge per key (labelled with the key) into a single SVG, stacked vertically or laid out in a grid with `columns`. Totals carry the same `COUNT_NOISE` as `/hits`. Display-only: counters are NOT incremented.",
+    params(
+        SpriteParams
+    ),
+    responses(
+        (status = 200, description = "The combined SVG.", content_type = "image/svg+xml", body = String),
+        (status = 400, description = "Missing, invalid or too many keys", body = ApiError),
+        (status = 500, description = "Database error", body = ApiError)
+    )
+)]
+pub async fn sprite_route(
+    Query(params): Query<SpriteParams>,
+    Extension(pool): Extension<PgPool>,
+    Extension(config): Extension<Arc<AppConfig>>,
+    Extension(noise): Extension<Arc<CountNoise>>,
+) -> Result<Response, AppError> {
+    let keys: Vec<String> = params
+        .keys
+        .split(',')
+        .map(|key| key.trim().to_string())
+        .filter(|key| !key.is_empty())
+        .collect();
+    if keys.is_empty() {
+        return Err(AppError::InvalidInput(
+            "`keys` must list at least one key.".to_string(),
+        ));
+    }
+    if keys.len() > MAX_SPRITE_KEYS {
+        return Err(AppError::InvalidInput(format!(
+            "At most {} keys can be rendered at once.",
+            MAX_SPRITE_KEYS
+        )));
+    }
+    let keys = keys
+        .iter()
+        .map(|key| validate_key(key, &config))
+        .collect::<Result<Vec<_>, _>>()?;
+    let columns = params.columns.unwrap_or(1);
+    if columns == 0 {
+        return Err(AppError::InvalidInput(
+            "`columns` must be at least 1.".to_string(),
+        ));
+    }
+
+    let stored_keys: Vec<String> = keys.iter().map(|key| config.stored_key(key)).collect();
+    let totals: HashMap<String, i64> = sqlx::query!(
+        r#"
+        SELECT key AS "key!", SUM(count)::BIGINT AS "total!"
+        FROM (
+            SELECT key, count::BIGINT AS count FROM counters WHERE key = ANY($1)
+            UNION ALL
+            SELECT key, count FROM key_seeds WHERE key = ANY($1)
+        ) totals
+        GROUP BY key
+        "#,
+        &stored_keys
+    )
+    .fetch_all(&pool)
+    .await?
+    .into_iter()
+    .map(|record| (record.key, record.total))
+    .collect();
+
+    let badges: Vec<(String, f64, f64)> = keys
+        .iter()
+        .enumerate()
+        .map(|(index, key)| {
+            let total = totals
+                .get(&stored_keys[index])
+                .copied()
+                .unwrap_or(0)
+                .saturating_add(config.base_count(key));
+            // 低于 `PUBLIC_MIN_TOTAL` 的 key 与不存在的 key 一样显示 0
+            let message = if total >= config.public_min_total {
+                noise.apply(&stored_keys[index], total, config.noise_bound(key))
+            } else {
+                0
+            }
+            .to_string();
+            let label = svg::mask_markup(&sanitize_text(key));
+            let badge = badge::render(
+                params.style,
+                &shields::BadgeParams {
+                    style: params.style.into(),
+                    label: Some(label.as_str()),
+                    message: Some(message.as_str()),
+                    label_color: None,
+                    message_color: None,
+                    link: None,
+                    extra_link: None,
+                    logo: None,
+                    logo_color: None,
+                },
+            );
+            let badge = svg::unmask_markup(&badge);
+            let (width, height) = svg::dimensions(&badge).unwrap_or((0.0, 0.0));
+            (
+                svg::prefix_ids(&badge, &format!("b{}-", index)),
+                width,
+                height,
+            )
+        })
+        .collect();
+
+    let cell_width = badges.iter().map(|b| b.1).fold(0.0, f64::max);
+    let cell_height = badges.iter().map(|b| b.2).fold(0.0, f64::max);
+    let rows = badges.len().div_ceil(columns);
+    let used_columns = columns.min(badges.len());
+    let total_width = used_columns as f64 * (cell_width + SPRITE_GAP) - SPRITE_GAP;
+    let total_height = rows as f64 * (cell_height + SPRITE_GAP) - SPRITE_GAP;
+
+    let mut sheet = format!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}">"#,
+        total_width, total_height
+    );
+    for (index, (badge, _, _)) in badges.iter().enumerate() {
+        let x = (index % columns) as f64 * (cell_width + SPRITE_GAP);
+        let y = (index / columns) as f64 * (cell_height + SPRITE_GAP);
+        sheet.push_str(&format!(r#"<g transform="translate({},{})">"#, x, y));
+        sheet.push_str(badge);
+        sheet.push_str("</g>");
+    }
+    sheet.push_str("</svg>");
+    let sheet = svg::with_view_box(&sheet, params.scalable);
+    let sheet = svg::with_namespaces(&sheet, config.svg_xml_declaration, config.svg_xlink);
+
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("image/svg+xml;charset=utf-8"),
+    );
+    headers.insert(
+        header::CACHE_CONTROL,
+        HeaderValue::from_static("no-cache, no-store, must-revalidate"),
+    );
+    headers.insert(header::PRAGMA, HeaderValue::from_static("no-cache"));
+    headers.insert(header::EXPIRES, HeaderValue::from_static("0"));
+    headers.insert(SURROGATE_CONTROL, HeaderValue::from_static("no-store"));
+    Ok((StatusCode::OK, headers, sheet).into_response())
+}
@@ -0,0 +1,359 @@
+//! 统计接口
+
+use crate::api::annotations::fetch_annotations;
+use crate::api::extract::Query;
+use crate::api::handlers::{ensure_exact_access, ensure_public, get_total_count, validate_key};
+use crate::api::negotiate::Negotiated;
+use crate::api::types::{ApiError, Forecast, SeriesPoint, StatsMetric, StatsParams, StatsSummary};
+use crate::config::{AppConfig, Granularity};
+use crate::error::AppError;
+use crate::hll;
+use crate::noise::CountNoise;
+use axum::{
+    extract::{Extension, Path},
+    http::HeaderMap,
+    response::Response,
+};
+use chrono::{DateTime, Duration, Utc};
+use sqlx::postgres::PgPool;
+use std::sync::Arc;
+
+/// 默认统计区间（天）
+const DEFAULT_RANGE_DAYS: i64 = 30;
+
+/// 默认预测天数
+const DEFAULT_FORECAST_DAYS: u32 = 30;
+
+/// 最大预测天数
+const MAX_FORECAST_DAYS: u32 = 3650;
+
+/// 拟合趋势所需的最少完整天数
+const MIN_FORECAST_SAMPLES: i64 = 3;
+
+/// 区间两端所在自然日的边界
+struct DayBounds {
+    /// Start of the day containing `from`
+    from_day: DateTime<Utc>,
+    /// Start of the day after `from_day`
+    next_day: DateTime<Utc>,
+    /// Start of the day containing `to`
+    to_day: DateTime<Utc>,
+}
+
+/// 数据库操作：按时区与偏移计算区间两端的日界
+///
+/// Days in a time zone with daylight saving time last 23 or 25 hours, so the next day is
+/// found by truncating a point 36 hours into the current one instead of adding a day.
+async fn day_bounds(
+    pool: &PgPool,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    zone: &str,
+    offset_minutes: i32,
+) -> Result<DayBounds, AppError> {
+    let record = sqlx::query!(
+        r#"
+        SELECT
+            DATE_TRUNC('day', $1::TIMESTAMPTZ - make_interval(mins => $3), $4)
+                + make_interval(mins => $3) AS "from_day!",
+            DATE_TRUNC(
+                'day',
+                DATE_TRUNC('day', $1::TIMESTAMPTZ - make_interval(mins => $3), $4)
+                    + INTERVAL '36 hours',
+                $4
+            ) + make_interval(mins => $3) AS "next_day!",
+            DATE_TRUNC('day', $2::TIMESTAMPTZ - make_interval(mins => $3), $4)
+                + make_interval(mins => $3) AS "to_day!"
+        "#,
+        from,
+        to,
+        offset_minutes,
+        zone
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(DayBounds {
+        from_day: record.from_day,
+        next_day: record.next_day,
+        to_day: record.to_day,
+    })
+}
+
+/// 两个日界之间相隔的天数，容忍夏令时造成的 23/25 小时
+fn days_between(start: DateTime<Utc>, end: DateTime<Utc>) -> i64 {
+    ((end - start).num_minutes() as f64 / 1440.0).round() as i64
+}
+
+/// 数据库操作：时区名是否为 Postgres 所知
+pub async fn timezone_exists(pool: &PgPool, zone: &str) -> Result<bool, AppError> {
+    let exists = sqlx::query_scalar!(
+        r#"SELECT EXISTS(SELECT 1 FROM pg_timezone_names WHERE name = $1) AS "exists!""#,
+        zone
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(exists)
+}
+
+/// 对区间内的完整自然日做最小二乘线性回归，预测 `horizon_days` 天后的总数
+///
+/// Days without hits count as zero; the partial days at either end of the range are left
+/// out. Projected daily counts are clamped at zero so a falling trend never reduces the
+/// total.
+fn forecast(
+    series: &[SeriesPoint],
+    total: i64,
+    from: DateTime<Utc>,
+    bounds: &DayBounds,
+    horizon_days: u32,
+) -> Option<Forecast> {
+    let first = if bounds.from_day < from {
+        bounds.next_day
+    } else {
+        bounds.from_day
+    };
+    let samples = days_between(first, bounds.to_day);
+    if samples < MIN_FORECAST_SAMPLES {
+        return None;
+    }
+    let mut counts = vec![0.0; samples as usize];
+    for point in series {
+        let index = days_between(first, point.bucket);
+        if (0..samples).contains(&index) {
+            counts[index as usize] = point.count as f64;
+        }
+    }
+
+    let n = samples as f64;
+    let mean_x = (n - 1.0) / 2.0;
+    let mean_y = counts.iter().sum::<f64>() / n;
+    let (mut sxy, mut sxx, mut syy) = (0.0, 0.0, 0.0);
+    for (x, y) in counts.iter().enumerate() {
+        let dx = x as f64 - mean_x;
+        let dy = y - mean_y;
+        sxy += dx * dy;
+        sxx += dx * dx;
+        syy += dy * dy;
+    }
+    let slope = sxy / sxx;
+    let intercept = mean_y - slope * mean_x;
+    // 计数完全不变时直线拟合是精确的
+    let confidence = if syy == 0.0 {
+        1.0
+    } else {
+        (sxy * sxy / (sxx * syy)).clamp(0.0, 1.0)
+    };
+    let projected: f64 = (0..horizon_days)
+        .map(|day| (intercept + slope * (n + f64::from(day))).max(0.0))
+        .sum();
+    Some(Forecast {
+        horizon_days,
+        // `as` 对超出范围的浮点数取饱和值
+        predicted_total: total.saturating_add(projected.round() as i64),
+        confidence,
+    })
+}
+
+/// 数据库操作：最近 `hours` 小时与之前等长区间的计数
+///
+/// Like [`get_current_period_count`], a bucket of width `granularity` counts towards the
+/// window it ends in, so an `hour` or `day` row that starts before a boundary is not lost.
+pub async fn get_period_totals(
+    pool: &PgPool,
+    key: &str,
+    granularity: Granularity,
+    hours: i32,
+) -> Result<(i64, i64), AppError> {
+    let record = sqlx::query!(
+        r#"
+        SELECT
+            COALESCE(SUM(count) FILTER (
+                WHERE minute_window + make_interval(secs => $3::INT)
+                    > NOW() - make_interval(hours => $2)
+            ), 0)::BIGINT AS "current!",
+            COALESCE(SUM(count) FILTER (
+                WHERE minute_window + make_interval(secs => $3::INT)
+                    <= NOW() - make_interval(hours => $2)
+            ), 0)::BIGINT AS "previous!"
+        FROM counters
+        WHERE key = $1
+            AND minute_window + make_interval(secs => $3::INT)
+                > NOW() - make_interval(hours => $2 * 2)
+        "#,
+        key,
+        hours,
+        granularity.seconds()
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok((record.current, record.previous))
+}
+
+/// 数据库操作：当前自然日（按时区与偏移对齐，见 [`AppConfig::day_boundary`]）内的计数
+pub async fn get_today_count(
+    pool: &PgPool,
+    stored_key: &str,
+    granularity: Granularity,
+    zone: &str,
+    offset_minutes: i32,
+) -> Result<i64, AppError> {
+    get_current_period_count(pool, stored_key, granularity, "day", zone, offset_minutes).await
+}
+
+/// 数据库操作：当前自然日、周（周一开始）或月内的计数，`unit` 为 `DATE_TRUNC` 的字段名
+///
+/// Rows are matched when their bucket of width `granularity` overlaps the period, so an
+/// `hour` or `day` row that starts before a period boundary (one written before the key's
+/// `WINDOW_OFFSET` or time zone changed) still counts towards the period it ends in.
+pub async fn get_current_period_count(
+    pool: &PgPool,
+    stored_key: &str,
+    granularity: Granularity,
+    unit: &str,
+    zone: &str,
+    offset_minutes: i32,
+) -> Result<i64, AppError> {
+    let record = sqlx::query!(
+        r#"
+        SELECT COALESCE(SUM(count), 0)::BIGINT AS "count!"
+        FROM counters
+        WHERE key = $1
+            AND minute_window + make_interval(secs => $5::INT)
+                > DATE_TRUNC($4, NOW() - make_interval(mins => $2), $3)
+                    + make_interval(mins => $2)
+        "#,
+        stored_key,
+        offset_minutes,
+        zone,
+        unit,
+        granularity.seconds()
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(record.count)
+}
+
+/// 统计摘要接口
+#[utoipa::path(
+    get,
+    summary = "Get Stats for a Key",
+    description = "Returns the all-time total and the daily hit series (days start at midnight in `tz` or the key's `KEY_TIMEZONES` zone, otherwise `WINDOW_OFFSET` minutes past UTC midnight) for the given range without incrementing the counter. Pass `annotations=true` to include timeline annotations inside the range. `forecast` is a rough least-squares projection of the total `forecast_days` ahead, fitted to the complete days of the range; it is omitted when the range covers fewer than 3 complete days. With `metric=uniques` (requires `TRACK_UNIQUES`) `uniques` estimates the distinct visitors of the days inside the range. With `COUNT_NOISE` or `KEY_COUNT_NOISE` the total carries the same noise as `/hits`, and the exact daily series requires the admin token (`Authorization: Bearer <ADMIN_TOKEN>`). Returns MessagePack instead of JSON when `Accept` lists `application/msgpack`.",
+    path = "/stats/{key}",
+    tag = "Main",
+    params(
+        ("key" = String, Path, description = "The key to summarize."),
+        StatsParams
+    ),
+    responses(
+        (status = 200, description = "Stats summary for the key.",
+         content(
+             (StatsSummary = "application/json"),
+             (StatsSummary = "application/msgpack")
+         )
+        ),
+        (status = 400, description = "Invalid key, range or time zone", body = ApiError),
+        (status = 403, description = "The key has a noise bound and no admin token was given", body = ApiError),
+        (status = 404, description = "Key is below `PUBLIC_MIN_TOTAL`", body = ApiError),
+        (status = 500, description = "Database error", body = ApiError)
+    )
+)]
+pub async fn stats_route(
+    Path(key): Path<String>,
+    Query(params): Query<StatsParams>,
+    Extension(pool): Extension<PgPool>,
+    Extension(config): Extension<Arc<AppConfig>>,
+    Extension(noise): Extension<Arc<CountNoise>>,
+    headers: HeaderMap,
+    negotiated: Negotiated,
+) -> Result<Response, AppError> {
+    let key = validate_key(&key, &config)?;
+    let to = params.to.unwrap_or_else(Utc::now);
+    let from = params
+        .from
+        .unwrap_or_else(|| to - Duration::days(DEFAULT_RANGE_DAYS));
+    if from >= to {
+        return Err(AppError::InvalidInput(
+            "`from` must be earlier than `to`.".to_string(),
+        ));
+    }
+    let forecast_days = params.forecast_days.unwrap_or(DEFAULT_FORECAST_DAYS);
+    if !(1..=MAX_FORECAST_DAYS).contains(&forecast_days) {
+        return Err(AppError::InvalidInput(format!(
+            "`forecast_days` must be between 1 and {}.",
+            MAX_FORECAST_DAYS
+        )));
+    }
+
+    let (zone, offset_minutes) = match params.tz.as_deref() {
+        Some(zone) => {
+            if !timezone_exists(&pool, zone).await? {
+                return Err(AppError::InvalidInput(format!(
+                    "Unknown time zone `{}`.",
+                    zone
+                )));
+            }
+            (zone, 0)
+        }
+        None => config.day_boundary(&key),
+    };
+
+    let stored_key = config.stored_key(&key);
+    let total = get_total_count(&pool, &stored_key)
+        .await?
+        .saturating_add(config.base_count(&key));
+    ensure_public(total, &config)?;
+    // 每日计数之和会还原出精确总数
+    ensure_exact_access(&key, &headers, &config, "Stats")?;
+    let total = noise.apply(&stored_key, total, config.noise_bound(&key));
+    let series = sqlx::query_as!(
+        SeriesPoint,
+        r#"
+        SELECT
+            DATE_TRUNC('day', minute_window - make_interval(mins => $4), $5)
+                + make_interval(mins => $4) AS "bucket!",
+            SUM(count)::BIGINT AS "count!"
+        FROM counters
+        WHERE key = $1 AND minute_window >= $2 AND minute_window < $3
+        GROUP BY 1
+        ORDER BY 1
+        "#,
+        stored_key,
+        from,
+        to,
+        offset_minutes,
+        zone
+    )
+    .fetch_all(&pool)
+    .await?;
+    let annotations = if params.annotations {
+        Some(fetch_annotations(&pool, &key, &stored_key, from, to).await?)
+    } else {
+        None
+    };
+
+    let uniques = match params.metric {
+        Some(StatsMetric::Uniques) => {
+            if !config.track_uniques {
+                return Err(AppError::InvalidInput(
+                    "`metric=uniques` requires TRACK_UNIQUES to be enabled.".to_string(),
+                ));
+            }
+            Some(hll::estimate_range(&pool, &stored_key, from, to).await?)
+        }
+        None => None,
+    };
+
+    let bounds = day_bounds(&pool, from, to, zone, offset_minutes).await?;
+    let forecast = forecast(&series, total, from, &bounds, forecast_days);
+
+    Ok(negotiated.respond(StatsSummary {
+        key,
+        total,
+        from,
+        to,
+        series,
+        annotations,
+        forecast,
+        uniques,
+    }))
+}
@@ -1,5 +1,6 @@
 //! API 相关类型定义
 
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use utoipa::{IntoParams, ToSchema};
 
@@ -18,10 +19,65 @@ pub struct ShieldsIoBadge {
     pub label: String,      // The left side of the badge
     pub message: String,    // The right side of the badge (the count)
     pub color: String,      // e.g., "blue", "green", hex codes like "ff69b4"
-                            // Optional: Add fields like `labelColor`, `isError`, `namedLogo`, `logoSvg`, `logoColor`, `logoWidth`, `logoPosition`, `style`, `cacheSeconds` if needed
+    /// Marks the badge as an error, so shields.io shows it as one and caches it briefly
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub is_error: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label_color: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub named_logo: Option<String>, // A simple-icons slug, e.g. "github"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub logo_color: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub style: Option<BadgeStyle>,
 }
 
-#[derive(Debug, Serialize, Deserialize, ToSchema)]
+/// Shields.io Badge 的覆盖参数
+#[derive(Debug, Deserialize, ToSchema, IntoParams)]
+pub struct ShieldsBadgeParams {
+    /// Overrides the label (default `hits`, translated for `lang` or `Accept-Language`)
+    pub label: Option<String>,
+
+    /// Language of the default label, e.g. `de` or `zh-TW`; overrides `Accept-Language`
+    pub lang: Option<String>,
+
+    /// Overrides the message. When set, the badge is static: the count is neither shown nor incremented.
+    pub message: Option<String>,
+
+    /// Overrides the message color (default `blue`)
+    pub color: Option<String>,
+
+    /// Sets shields' `labelColor`
+    pub label_color: Option<String>,
+
+    /// Sets shields' `namedLogo` (a simple-icons slug, e.g. `github`)
+    pub logo: Option<String>,
+
+    /// Sets shields' `logoColor`
+    pub logo_color: Option<String>,
+
+    /// Sets shields' `style` (`square` is not supported by shields.io)
+    pub style: Option<BadgeStyle>,
+
+    /// How the count is written (ignored with `rollover`)
+    #[serde(default)]
+    pub format: CountFormat,
+
+    /// Thousands separator for `format=plain`, e.g. `comma` for `12,345`
+    #[serde(default)]
+    pub separator: Separator,
+
+    /// Show the count modulo this value, zero-padded, like an odometer
+    pub rollover: Option<i64>,
+
+    /// Last known count; the message becomes `{count} (+{count - delta_from})`, the delta written in the same `format` and `separator`
+    pub delta_from: Option<i64>,
+
+    /// Duration such as `1h` or `7d`; the badge turns grey and reads `(stale)` when the key's previous hit is older
+    pub stale_after: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, ToSchema)]
 #[serde(rename_all = "kebab-case")]
 pub enum BadgeStyle {
     Flat,
@@ -29,10 +85,126 @@ pub enum BadgeStyle {
     Plastic,
     Social,
     ForTheBadge,
+    /// Compact tile with the label on top and the count below (`/svg` and `/sprite` only)
+    Square,
+}
+
+impl BadgeStyle {
+    /// 所有支持的样式
+    pub const ALL: [BadgeStyle; 6] = [
+        BadgeStyle::Flat,
+        BadgeStyle::FlatSquare,
+        BadgeStyle::Plastic,
+        BadgeStyle::Social,
+        BadgeStyle::ForTheBadge,
+        BadgeStyle::Square,
+    ];
 }
 
-pub fn default_label() -> String {
-    "Hits".to_string()
+impl From<BadgeStyle> for shields::BadgeStyle {
+    fn from(style: BadgeStyle) -> Self {
+        match style {
+            BadgeStyle::Flat => shields::BadgeStyle::Flat,
+            BadgeStyle::FlatSquare => shields::BadgeStyle::FlatSquare,
+            BadgeStyle::Plastic => shields::BadgeStyle::Plastic,
+            BadgeStyle::Social => shields::BadgeStyle::Social,
+            BadgeStyle::ForTheBadge => shields::BadgeStyle::ForTheBadge,
+            // square 由 `badge` 模块自行渲染，shields 只用于读取配色
+            BadgeStyle::Square => shields::BadgeStyle::Flat,
+        }
+    }
+}
+
+/// 徽章显示模式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum BadgeMode {
+    /// Show the all-time total
+    #[default]
+    Total,
+    /// Show the hits of the last `trend_hours`, colored and marked by the change against the period before
+    Trend,
+    /// Show the all-time total, but only count one hit per visitor and day
+    Unique,
+    /// Show the all-time total, but only count the first hit of a browser session (cookie)
+    Session,
+    /// Show today's hits and the all-time total, e.g. `12 / 3456`
+    TodayTotal,
+}
+
+/// 徽章统计的自然周期
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum BadgePeriod {
+    /// Since the start of today
+    Day,
+    /// Since the start of this week (Monday)
+    Week,
+    /// Since the start of this month
+    Month,
+}
+
+impl BadgePeriod {
+    /// PostgreSQL `DATE_TRUNC` 的字段名
+    pub fn unit(self) -> &'static str {
+        match self {
+            Self::Day => "day",
+            Self::Week => "week",
+            Self::Month => "month",
+        }
+    }
+}
+
+/// 计数的数字格式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum CountFormat {
+    /// All digits, e.g. `12345`
+    #[default]
+    Plain,
+    /// Metric prefixes like shields.io, e.g. `12k` or `3.4M`
+    Compact,
+}
+
+/// 徽章配色主题
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum BadgeTheme {
+    /// Always use `label_color` and `message_color`
+    #[default]
+    Light,
+    /// Switch to `dark_label_color` and `dark_message_color` when the viewer prefers a dark color scheme
+    Auto,
+}
+
+pub fn default_dark_label_color() -> String {
+    "#30363d".to_string()
+}
+
+/// 千位分隔符
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum Separator {
+    /// `12345`
+    #[default]
+    None,
+    /// `12,345`
+    Comma,
+    /// `12.345`
+    Dot,
+    /// `12 345`
+    Space,
+}
+
+impl Separator {
+    pub fn as_char(self) -> Option<char> {
+        match self {
+            Self::None => None,
+            Self::Comma => Some(','),
+            Self::Dot => Some('.'),
+            Self::Space => Some(' '),
+        }
+    }
 }
 
 pub fn default_badge_style() -> BadgeStyle {
@@ -52,28 +224,234 @@ pub struct HitBadgeParams {
     #[serde(default = "default_badge_style")]
     pub style: BadgeStyle,
 
-    /// The label text on the left side of the badge
-    #[serde(default = "default_label")]
-    pub label: String,
+    /// The label text on the left side of the badge (default `Hits`, translated for `lang` or `Accept-Language`)
+    pub label: Option<String>,
 
-    /// The color of the label text
+    /// Language of the default label, e.g. `de` or `zh-TW`; overrides `Accept-Language`
+    pub lang: Option<String>,
+
+    /// Label background: hex (`#555` or `555`), a CSS color, or a shields name such as `lightgrey`
     #[serde(default = "default_label_color")]
     pub label_color: String,
 
-    /// The message text on the right side of the badge
+    /// Message background: hex, a CSS color, or a shields name such as `brightgreen` or `critical`
     #[serde(default = "default_message_color")]
     pub message_color: String,
 
+    /// `auto` embeds a `prefers-color-scheme` rule that switches to the dark colors
+    #[serde(default)]
+    pub theme: BadgeTheme,
+
+    /// Label color with `theme=auto` in dark mode
+    #[serde(default = "default_dark_label_color")]
+    pub dark_label_color: String,
+
+    /// Message color with `theme=auto` in dark mode (default `message_color`; stale, trend and error colors are kept)
+    pub dark_message_color: Option<String>,
+
     /// The link to the badge (optional)
     pub link: Option<String>,
 
     pub extra_link: Option<String>,
 
-    /// The logo to display on the badge
+    /// A simple-icons slug such as `github`, or a base64 `data:image/svg+xml` or `data:image/png` URI of up to 16 KiB
     pub logo: Option<String>,
 
     /// The width of the logo in pixels
     pub logo_color: Option<String>,
+
+    /// Return compact SVG without an XML declaration or indentation, for inlining into HTML
+    #[serde(default)]
+    pub raw: bool,
+
+    /// What the badge shows
+    #[serde(default)]
+    pub mode: BadgeMode,
+
+    /// Length of the compared periods for `mode=trend`, in hours (default 24)
+    pub trend_hours: Option<i32>,
+
+    /// Show only the hits of the current day, week or month instead of the all-time total (not with `mode=trend`)
+    pub period: Option<BadgePeriod>,
+
+    /// How the count is written (ignored with `rollover`)
+    #[serde(default)]
+    pub format: CountFormat,
+
+    /// Thousands separator for `format=plain`, e.g. `comma` for `12,345`
+    #[serde(default)]
+    pub separator: Separator,
+
+    /// Show the count modulo this value, zero-padded, like an odometer (not with `mode=trend`)
+    pub rollover: Option<i64>,
+
+    /// Last known count; the message becomes `{count} (+{count - delta_from})`, the delta written in the same `format` and `separator` (not with `mode=trend`)
+    pub delta_from: Option<i64>,
+
+    /// Text around the formatted count, e.g. `{count} views` or `#{count}`; must contain `{count}`
+    pub message_template: Option<String>,
+
+    /// Cache-busting token, e.g. a timestamp. Ignored by the counter; echoed into the SVG when `BADGE_NONCE` is on.
+    pub t: Option<String>,
+
+    /// Omit the fixed `width`/`height` so the badge scales to fill its container
+    #[serde(default)]
+    pub scalable: bool,
+
+    /// Size multiplier between 0.5 and 10, e.g. `2` for a 40px tall badge (ignored with `scalable`)
+    pub scale: Option<f64>,
+
+    /// Duration such as `1h` or `7d`; the badge turns grey and reads `(stale)` when the key's previous hit is older
+    pub stale_after: Option<String>,
+
+    /// Count up to the total (`mode=total`, `unique` or `session`) or fade the message in
+    #[serde(default)]
+    pub animate: bool,
+}
+
+/// 徽章拼图接口的查询参数
+#[derive(Debug, Deserialize, ToSchema, IntoParams)]
+pub struct SpriteParams {
+    /// Comma-separated list of keys, rendered in order
+    pub keys: String,
+
+    /// The style of every badge in the sheet
+    #[serde(default = "default_badge_style")]
+    pub style: BadgeStyle,
+
+    /// Number of badges per row (default 1, i.e. stacked vertically)
+    pub columns: Option<usize>,
+
+    /// Omit the fixed `width`/`height` so the badge scales to fill its container
+    #[serde(default)]
+    pub scalable: bool,
+}
+
+/// 嵌入代码的标记语言
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum SnippetFormat {
+    /// `[![Hits](...)](...)`
+    #[default]
+    Markdown,
+    /// `<a href="..."><img src="..." alt="Hits"></a>`
+    Html,
+    /// reStructuredText `.. image::` directive
+    Rst,
+    /// AsciiDoc `image:` macro
+    Asciidoc,
+}
+
+/// 嵌入代码接口的查询参数
+///
+/// Every other query parameter is copied into the badge URL.
+#[derive(Debug, Deserialize, ToSchema, IntoParams)]
+pub struct SnippetParams {
+    /// Markup of the returned snippet
+    #[serde(default)]
+    pub format: SnippetFormat,
+
+    /// Alt text and badge label (default `Hits`)
+    pub label: Option<String>,
+
+    /// Page the badge links to
+    pub link: Option<String>,
+}
+
+/// 嵌入组件接口的查询参数
+#[derive(Debug, Deserialize, ToSchema, IntoParams)]
+pub struct EmbedParams {
+    /// Text before the count (default `Hits`, translated like the badge label)
+    pub label: Option<String>,
+
+    /// Language of the default label, e.g. `zh` or `de` (default from `Accept-Language`)
+    pub lang: Option<String>,
+
+    /// Thousands separator for the count
+    #[serde(default)]
+    pub separator: Separator,
+
+    /// Text color, a `#` hex value or a CSS color name
+    #[serde(default = "default_embed_color")]
+    pub color: String,
+
+    /// Background color, a `#` hex value or a CSS color name
+    #[serde(default = "default_embed_background")]
+    pub background: String,
+
+    /// Font size in pixels, between 8 and 72
+    #[serde(default = "default_embed_font_size")]
+    pub font_size: u32,
+
+    /// Keep the count up to date over the WebSocket
+    #[serde(default)]
+    pub live: bool,
+}
+
+pub fn default_embed_color() -> String {
+    "#24292f".to_string()
+}
+pub fn default_embed_background() -> String {
+    "transparent".to_string()
+}
+pub fn default_embed_font_size() -> u32 {
+    14
+}
+
+/// 计数接口的响应方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum HitsRespond {
+    /// Return 204 No Content without reading the total
+    None,
+}
+
+/// 计数接口的计数方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum HitsMode {
+    /// Count one hit per visitor (salted hash of the client IP) and day
+    Unique,
+    /// Count the first hit per browser session, tracked with a short-lived cookie
+    Session,
+}
+
+/// 计数自增接口的查询参数
+#[derive(Debug, Deserialize, ToSchema, IntoParams)]
+pub struct HitsParams {
+    /// Set to `none` to skip reading the total and get an empty 204 response.
+    pub respond: Option<HitsRespond>,
+    /// Set to `unique` to only count the first hit of each visitor per day, or to `session` to
+    /// only count the first hit of each browser session.
+    pub mode: Option<HitsMode>,
+    /// Add this many hits at once (1 to `AMOUNT_MAX`); requires `Authorization: Bearer <AMOUNT_TOKEN>`.
+    pub amount: Option<i32>,
+}
+
+/// 跳转计数接口的查询参数
+#[derive(Debug, Deserialize, ToSchema, IntoParams)]
+pub struct RedirectParams {
+    /// The http(s) URL to redirect to. Its host must be listed in `REDIRECT_ALLOWED_HOSTS`.
+    pub url: String,
+}
+
+/// 自检中单个子系统的结果
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SelfTestCheck {
+    /// Subsystem name, e.g. `render:flat`, `font_metrics`, `database`
+    pub name: String,
+    pub ok: bool,
+    /// Failure reason, only present when `ok` is false
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+/// 自检报告
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SelfTestReport {
+    /// True when every check passed
+    pub ok: bool,
+    pub checks: Vec<SelfTestCheck>,
 }
 
 #[derive(Serialize, ToSchema)]
@@ -81,4 +459,341 @@ pub struct AppInfo {
     pub project_name: String,
     pub version: String,
     pub docs_path: String,
+    /// Sum of all counters, from a periodically reconciled in-memory copy
+    pub total_hits: i64,
+}
+
+/// 创建时间轴标注的请求体
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct AnnotationRequest {
+    /// The point in time the note refers to (RFC 3339)
+    #[schema(example = "2025-10-14T09:00:00Z")]
+    pub timestamp: DateTime<Utc>,
+
+    /// Free-form note, e.g. "deployed v2"
+    #[schema(example = "deployed v2")]
+    pub note: String,
+}
+
+/// 时间轴标注
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Annotation {
+    pub key: String,
+    pub timestamp: DateTime<Utc>,
+    pub note: String,
+}
+
+/// 统计接口的查询参数
+#[derive(Debug, Deserialize, ToSchema, IntoParams)]
+pub struct StatsParams {
+    /// Start of the range (inclusive, RFC 3339). Defaults to 30 days before `to`.
+    pub from: Option<DateTime<Utc>>,
+
+    /// End of the range (exclusive, RFC 3339). Defaults to now.
+    pub to: Option<DateTime<Utc>>,
+
+    /// Include annotations that fall inside the range
+    #[serde(default)]
+    pub annotations: bool,
+
+    /// How many days ahead the forecast projects the total (default 30, at most 3650)
+    pub forecast_days: Option<u32>,
+
+    /// Extra metric to include; `uniques` estimates distinct visitors inside the range
+    pub metric: Option<StatsMetric>,
+
+    /// IANA time zone whose midnight starts each day of the series, e.g. `Asia/Tokyo`.
+    /// Defaults to the key's `KEY_TIMEZONES` entry, then to UTC shifted by `WINDOW_OFFSET`.
+    pub tz: Option<String>,
+}
+
+/// 统计接口的附加指标
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum StatsMetric {
+    /// Approximate distinct visitors (HyperLogLog), requires `TRACK_UNIQUES`
+    Uniques,
+}
+
+/// 重命名请求体
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct RenameRequest {
+    /// The existing key
+    pub from: String,
+    /// The new key
+    pub to: String,
+}
+
+/// 目标键已存在时的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum RenameForce {
+    /// Add the source's history to the existing target
+    Merge,
+}
+
+/// 重命名接口的查询参数
+#[derive(Debug, Deserialize, ToSchema, IntoParams)]
+pub struct RenameParams {
+    /// Set to `merge` to fold the history into an existing target key instead of failing.
+    pub force: Option<RenameForce>,
+}
+
+/// 重命名结果
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RenameResult {
+    pub from: String,
+    pub to: String,
+    /// Rows moved across `counters`, `geo_counts`, `bot_counts`, `visitor_sketches`, `key_seeds`,
+    /// `annotations`, `hit_events` and `key_rate_limits`
+    pub rows_affected: u64,
+    /// Total of the target key after the rename
+    pub total: i64,
+}
+
+/// 校验接口的查询参数
+#[derive(Debug, Deserialize, ToSchema, IntoParams)]
+pub struct VerifyParams {
+    /// Overwrite the in-memory values with the recomputed ones.
+    #[serde(default)]
+    pub fix: bool,
+}
+
+/// 重新计算的总数与内存中缓存值的对比
+#[derive(Debug, Serialize, ToSchema)]
+pub struct VerifyResult {
+    pub key: String,
+    /// Sum of the key's stored rows and seed plus its `KEY_BASE_COUNTS` offset, like `/count`
+    pub computed_total: i64,
+    /// The key's total in the `/metrics/keys` snapshot, if the key is exported
+    pub snapshot_total: Option<i64>,
+    /// Sum of all stored rows
+    pub computed_grand_total: i64,
+    /// The in-memory grand total reported by `/`
+    pub cached_grand_total: i64,
+    /// Whether every cached value equals its recomputed value
+    pub matched: bool,
+    /// Whether mismatched cached values were overwritten (`fix=true`)
+    pub fixed: bool,
+}
+
+/// 设置 key 每分钟上限的请求体
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct KeyRateLimitRequest {
+    /// Maximum counted hits per minute (at least 1)
+    #[schema(example = 60)]
+    pub per_minute: i32,
+}
+
+/// key 的每分钟上限
+#[derive(Debug, Serialize, ToSchema)]
+pub struct KeyRateLimit {
+    pub key: String,
+    /// Maximum counted hits per minute, or `null` when the key is unlimited
+    pub per_minute: Option<i32>,
+}
+
+/// 设置 key 起始计数的请求体
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct KeySeedRequest {
+    /// Hits carried over from another service (at least 1)
+    #[schema(example = 123456)]
+    pub count: i64,
+}
+
+/// key 的起始计数
+#[derive(Debug, Serialize, ToSchema)]
+pub struct KeySeed {
+    pub key: String,
+    /// Starting count included in the key's total, or `null` when the key has none
+    pub count: Option<i64>,
+}
+
+/// 屏蔽名单条目的类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum DenyKind {
+    /// A key; hits on it are refused
+    Key,
+    /// A client IP or CIDR range; its hits are not counted
+    Ip,
+}
+
+impl DenyKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DenyKind::Key => "key",
+            DenyKind::Ip => "ip",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "key" => Some(DenyKind::Key),
+            "ip" => Some(DenyKind::Ip),
+            _ => None,
+        }
+    }
+}
+
+/// 屏蔽名单条目
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct DenylistEntry {
+    pub kind: DenyKind,
+    /// The key, or an IP address or CIDR range such as `203.0.113.0/24`. Listed keys are
+    /// shown as stored, i.e. as digests when `HASH_KEYS` is on.
+    #[schema(example = "203.0.113.0/24")]
+    pub value: String,
+}
+
+/// 屏蔽名单
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DenylistEntries {
+    pub entries: Vec<DenylistEntry>,
+}
+
+/// 因流量突增被限流的 key
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ThrottledKey {
+    /// The stored key (a digest when `HASH_KEYS` is on)
+    pub key: String,
+    /// Hits counted per minute while throttled
+    pub cap_per_minute: i64,
+    /// Hits in the minute that triggered the throttle
+    pub spike_hits: i64,
+    /// Average hits per minute in the hour before the spike
+    pub baseline: f64,
+    /// When the throttle expires unless the key keeps spiking
+    pub until: DateTime<Utc>,
+}
+
+/// 被限流的 key 列表
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ThrottledKeys {
+    pub keys: Vec<ThrottledKey>,
+}
+
+/// Webhook 计数结果
+#[derive(Debug, Serialize, ToSchema)]
+pub struct WebhookResult {
+    pub key: String,
+    /// Normalized event name, e.g. `push` or `merge_request`
+    pub event: String,
+    /// The payload's action, if any (e.g. `created` for stars)
+    pub action: Option<String>,
+    /// Amount added to the key (0 for unmapped events)
+    pub counted: i32,
+    pub total: i64,
+}
+
+/// 地理分布接口的查询参数
+#[derive(Debug, Deserialize, ToSchema, IntoParams)]
+pub struct GeoParams {
+    /// Start of the range (inclusive, RFC 3339). Defaults to all time.
+    pub from: Option<DateTime<Utc>>,
+
+    /// End of the range (exclusive, RFC 3339). Defaults to now.
+    pub to: Option<DateTime<Utc>>,
+}
+
+/// 单个国家的计数
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CountryCount {
+    /// ISO 3166-1 alpha-2 country code
+    pub country: String,
+    pub count: i64,
+}
+
+/// 按国家统计的访问分布
+#[derive(Debug, Serialize, ToSchema)]
+pub struct GeoBreakdown {
+    pub key: String,
+    /// Countries ordered by count, highest first. Only hits sent with `geo=true` are included.
+    pub countries: Vec<CountryCount>,
+}
+
+/// 活动统计接口的查询参数
+#[derive(Debug, Deserialize, ToSchema, IntoParams)]
+pub struct CampaignParams {
+    /// Start of the range (inclusive, RFC 3339). Defaults to all time.
+    pub from: Option<DateTime<Utc>>,
+
+    /// End of the range (exclusive, RFC 3339). Defaults to now.
+    pub to: Option<DateTime<Utc>>,
+}
+
+/// 单个 UTM 组合的计数
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CampaignCount {
+    pub source: Option<String>,
+    pub medium: Option<String>,
+    pub campaign: Option<String>,
+    /// Estimated hits, scaled up by `HIT_EVENTS_SAMPLE_RATE`
+    pub count: i64,
+}
+
+/// key 的活动统计
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CampaignBreakdown {
+    pub key: String,
+    /// `utm_source` / `utm_medium` / `utm_campaign` combinations ordered by count, highest first
+    pub campaigns: Vec<CampaignCount>,
+}
+
+/// 爬虫计数接口的查询参数
+#[derive(Debug, Deserialize, ToSchema, IntoParams)]
+pub struct BotParams {
+    /// Start of the range (inclusive, RFC 3339). Defaults to all time.
+    pub from: Option<DateTime<Utc>>,
+
+    /// End of the range (exclusive, RFC 3339). Defaults to now.
+    pub to: Option<DateTime<Utc>>,
+}
+
+/// 来自已知爬虫与代理网段的访问数
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BotHits {
+    pub key: String,
+    /// Hits from `BOT_IP_LISTS` ranges in the range; they are not part of the key's total
+    pub count: i64,
+}
+
+/// 时间序列中的单个桶
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SeriesPoint {
+    /// Start of the daily bucket, shifted by `WINDOW_OFFSET`
+    pub bucket: DateTime<Utc>,
+    pub count: i64,
+}
+
+/// 统计摘要
+#[derive(Debug, Serialize, ToSchema)]
+pub struct StatsSummary {
+    pub key: String,
+    /// All-time total for the key
+    pub total: i64,
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    /// Daily hit counts inside the range, sorted by bucket
+    pub series: Vec<SeriesPoint>,
+    /// Annotations inside the range, sorted by timestamp. Only present when requested.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub annotations: Option<Vec<Annotation>>,
+    /// Projected total. Omitted when the range has too few complete days to fit a trend.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub forecast: Option<Forecast>,
+    /// Estimated distinct visitors inside the range (about 3% error). Only present with `metric=uniques`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uniques: Option<i64>,
+}
+
+/// 基于日计数线性回归的总数预测（仅为估计）
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Forecast {
+    /// Days past `to` the projection covers
+    pub horizon_days: u32,
+    /// Estimated all-time total after `horizon_days`: the current total plus the fitted daily counts
+    pub predicted_total: i64,
+    /// R² of the least-squares fit over the daily counts, from 0 (no trend) to 1 (perfect fit)
+    pub confidence: f64,
 }
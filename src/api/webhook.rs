@@ -0,0 +1,248 @@
+//! GitHub / GitLab webhook 计数接口
+//!
+//! GitHub deliveries are verified with the `X-Hub-Signature-256` HMAC of the raw body,
+//! GitLab deliveries by comparing `X-Gitlab-Token` with the secret. The event name is
+//! taken from `X-GitHub-Event` or `X-Gitlab-Event` (`Tag Push Hook` becomes `tag_push`)
+//! and looked up in `WEBHOOK_EVENTS`, first as `event.action`, then as `event`.
+
+use crate::api::auth::constant_time_eq;
+use crate::api::handlers::{get_total_count, validate_key};
+use crate::api::hit::HitContext;
+use crate::api::types::{ApiError, WebhookResult};
+use crate::api::ws::HitEvent;
+use crate::error::AppError;
+use axum::{body::Bytes, extract::Path, http::HeaderMap, Json};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+/// 验证投递来源，返回标准化的事件名
+fn verify_delivery(headers: &HeaderMap, body: &[u8], secret: &str) -> Result<String, AppError> {
+    let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+    if let Some(event) = header("x-github-event") {
+        let signature = header("x-hub-signature-256")
+            .and_then(|value| value.strip_prefix("sha256="))
+            .and_then(|value| hex::decode(value).ok())
+            .unwrap_or_default();
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+        mac.update(body);
+        return match mac.verify_slice(&signature) {
+            Ok(()) => Ok(event.to_ascii_lowercase()),
+            Err(_) => Err(AppError::Unauthorized(
+                "Invalid X-Hub-Signature-256.".to_string(),
+            )),
+        };
+    }
+    if let Some(event) = header("x-gitlab-event") {
+        let token = header("x-gitlab-token").unwrap_or_default();
+        if !constant_time_eq(token.as_bytes(), secret.as_bytes()) {
+            return Err(AppError::Unauthorized(
+                "Invalid X-Gitlab-Token.".to_string(),
+            ));
+        }
+        let event = event.trim().to_ascii_lowercase();
+        let event = event.strip_suffix(" hook").unwrap_or(&event);
+        return Ok(event.replace(' ', "_"));
+    }
+    Err(AppError::InvalidInput(
+        "Missing X-GitHub-Event or X-Gitlab-Event header.".to_string(),
+    ))
+}
+
+/// 从 payload 中读取事件的 action（GitHub 的 `action`，GitLab 的 `object_attributes.action`）
+fn payload_action(body: &[u8]) -> Option<String> {
+    let payload: serde_json::Value = serde_json::from_slice(body).ok()?;
+    payload
+        .get("action")
+        .or_else(|| payload.get("object_attributes")?.get("action"))
+        .and_then(|action| action.as_str())
+        .map(str::to_ascii_lowercase)
+}
+
+/// Webhook 计数接口
+#[utoipa::path(
+    post,
+    summary = "Count a GitHub/GitLab Webhook Event",
+    description = "Accepts GitHub or GitLab webhook deliveries and increments the key by the amount configured for the event in `WEBHOOK_EVENTS` (default: `push=1,star.created=1`). `event.action` entries take precedence over plain `event` entries; unmapped events are acknowledged without counting. GitHub deliveries must carry a valid `X-Hub-Signature-256`, GitLab deliveries an `X-Gitlab-Token` equal to `WEBHOOK_SECRET`.",
+    path = "/webhook/{key}",
+    tag = "Main",
+    params(
+        ("key" = String, Path, description = "The key to increment.")
+    ),
+    request_body(content = String, description = "The raw webhook payload", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Delivery accepted.", body = WebhookResult),
+        (status = 400, description = "Invalid key or missing event header", body = ApiError),
+        (status = 401, description = "Webhooks disabled or invalid signature", body = ApiError),
+        (status = 500, description = "Database error", body = ApiError)
+    )
+)]
+pub async fn webhook_route(
+    Path(key): Path<String>,
+    hit: HitContext,
+    body: Bytes,
+) -> Result<Json<WebhookResult>, AppError> {
+    let config = &hit.config;
+    let key = validate_key(&key, config)?;
+    if hit.is_denied(&key) {
+        return Err(AppError::Forbidden("This key is blocked.".to_string()));
+    }
+    let Some(secret) = config.webhook_secret.as_deref() else {
+        return Err(AppError::Unauthorized(
+            "Webhooks are disabled: WEBHOOK_SECRET is not configured.".to_string(),
+        ));
+    };
+    let event = verify_delivery(&hit.headers, &body, secret)?;
+    let action = payload_action(&body);
+    let amount = action
+        .as_ref()
+        .and_then(|action| config.webhook_events.get(&format!("{}.{}", event, action)))
+        .or_else(|| config.webhook_events.get(&event))
+        .copied()
+        .unwrap_or(0);
+
+    let stored_key = config.stored_key(&key);
+    let total = if amount > 0 {
+        let total = hit
+            .writes
+            .add(
+                &hit.pool,
+                &stored_key,
+                amount,
+                config.granularity(&key),
+                config.day_boundary(&key),
+            )
+            .await?
+            .saturating_add(config.base_count(&key));
+        hit.grand_total.add(i64::from(amount));
+        hit.broadcaster
+            .send(HitEvent {
+                key: key.clone(),
+                total,
+            })
+            .ok();
+        total
+    } else {
+        get_total_count(&hit.pool, &stored_key)
+            .await?
+            .saturating_add(config.base_count(&key))
+    };
+    Ok(Json(WebhookResult {
+        key,
+        event,
+        action,
+        counted: amount,
+        total,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &str = "It's a Secret to Everybody";
+    const BODY: &[u8] = br#"{"action":"created"}"#;
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for &(name, value) in pairs {
+            headers.insert(name, value.parse().unwrap());
+        }
+        headers
+    }
+
+    fn github_signature(body: &[u8], secret: &str) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+        mac.update(body);
+        format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+    }
+
+    #[test]
+    fn github_signature_is_verified() {
+        let signature = github_signature(BODY, SECRET);
+        let delivery = headers(&[
+            ("x-github-event", "Star"),
+            ("x-hub-signature-256", &signature),
+        ]);
+        assert_eq!(verify_delivery(&delivery, BODY, SECRET).unwrap(), "star");
+
+        let other_secret = github_signature(BODY, "another secret");
+        let delivery = headers(&[
+            ("x-github-event", "star"),
+            ("x-hub-signature-256", &other_secret),
+        ]);
+        assert!(matches!(
+            verify_delivery(&delivery, BODY, SECRET),
+            Err(AppError::Unauthorized(_))
+        ));
+        let delivery = headers(&[
+            ("x-github-event", "star"),
+            ("x-hub-signature-256", &signature),
+        ]);
+        assert!(matches!(
+            verify_delivery(&delivery, br#"{"action":"deleted"}"#, SECRET),
+            Err(AppError::Unauthorized(_))
+        ));
+    }
+
+    #[test]
+    fn github_signature_needs_the_sha256_prefix() {
+        let signature = github_signature(BODY, SECRET);
+        let digest = signature.strip_prefix("sha256=").unwrap();
+        for value in [
+            digest.to_string(),
+            format!("sha1={}", digest),
+            format!("SHA256={}", digest),
+        ] {
+            let delivery = headers(&[("x-github-event", "star"), ("x-hub-signature-256", &value)]);
+            assert!(matches!(
+                verify_delivery(&delivery, BODY, SECRET),
+                Err(AppError::Unauthorized(_))
+            ));
+        }
+        let unsigned = headers(&[("x-github-event", "star")]);
+        assert!(matches!(
+            verify_delivery(&unsigned, BODY, SECRET),
+            Err(AppError::Unauthorized(_))
+        ));
+    }
+
+    #[test]
+    fn gitlab_token_is_compared() {
+        let delivery = headers(&[("x-gitlab-event", "Push Hook"), ("x-gitlab-token", SECRET)]);
+        assert_eq!(verify_delivery(&delivery, BODY, SECRET).unwrap(), "push");
+        for token in ["wrong", "", "It's a Secret to Everybody!"] {
+            let delivery = headers(&[("x-gitlab-event", "Push Hook"), ("x-gitlab-token", token)]);
+            assert!(matches!(
+                verify_delivery(&delivery, BODY, SECRET),
+                Err(AppError::Unauthorized(_))
+            ));
+        }
+        let untokened = headers(&[("x-gitlab-event", "Push Hook")]);
+        assert!(matches!(
+            verify_delivery(&untokened, BODY, SECRET),
+            Err(AppError::Unauthorized(_))
+        ));
+    }
+
+    #[test]
+    fn gitlab_event_names_are_normalized() {
+        for (event, expected) in [
+            ("Tag Push Hook", "tag_push"),
+            ("Merge Request Hook", "merge_request"),
+            ("Push Hook", "push"),
+            ("  Note Hook ", "note"),
+        ] {
+            let delivery = headers(&[("x-gitlab-event", event), ("x-gitlab-token", SECRET)]);
+            assert_eq!(verify_delivery(&delivery, BODY, SECRET).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn deliveries_need_an_event_header() {
+        assert!(matches!(
+            verify_delivery(&HeaderMap::new(), BODY, SECRET),
+            Err(AppError::InvalidInput(_))
+        ));
+    }
+}
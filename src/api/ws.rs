@@ -1,49 +1,212 @@
 //! WebSocket handler 相关实现
+//!
+//! By default every counted hit is sent as a text frame containing the key. Clients can
+//! negotiate the compact binary format by requesting the [`BINARY_PROTOCOL`] subprotocol
+//! via `Sec-WebSocket-Protocol`. Each binary frame then carries one event:
+//!
+//! ```text
+//! varint(key length in bytes) | key (UTF-8) | varint(total)
+//! ```
+//!
+//! Varints are unsigned LEB128: 7 bits per byte, least significant group first, with the
+//! high bit set on every byte except the last.
+//!
+//! With `WS_BATCH_MS` set, each connection buffers events and sends them together every
+//! interval, or earlier once `WS_BATCH_MAX` events are waiting. A batched text frame is a
+//! JSON array of keys; a batched binary frame is the events' encodings back to back.
+//!
+//! Open connections are counted globally and per client IP ([`ConnectionLimiter`]);
+//! handshakes beyond `WS_MAX_CONNECTIONS` or `WS_MAX_CONNECTIONS_PER_IP` get 429.
 
+use crate::api::types::ApiError;
+use crate::client_ip::resolve_client_ip;
+use crate::config::AppConfig;
 use axum::{
     extract::{
         ws::{Message, WebSocket, WebSocketUpgrade},
-        State,
+        ConnectInfo, Extension, State,
     },
-    response::IntoResponse,
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
 };
 use futures_util::stream::SplitSink;
 use futures_util::SinkExt;
 use futures_util::StreamExt;
-use std::sync::Arc;
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use tokio::sync::broadcast;
+use tokio::time::{interval, MissedTickBehavior};
 use tracing::{info, warn};
 
-pub type Broadcaster = broadcast::Sender<String>;
+/// 二进制事件格式的子协议名
+pub const BINARY_PROTOCOL: &str = "hits.binary.v1";
+
+/// 一次计数事件
+#[derive(Debug, Clone)]
+pub struct HitEvent {
+    pub key: String,
+    /// The key's total after the hit
+    pub total: i64,
+}
+
+pub type Broadcaster = broadcast::Sender<HitEvent>;
+
+/// 当前打开的连接数，全局与按 IP 统计
+#[derive(Default)]
+pub struct ConnectionLimiter {
+    counts: Mutex<ConnectionCounts>,
+}
+
+#[derive(Default)]
+struct ConnectionCounts {
+    total: usize,
+    per_ip: HashMap<IpAddr, usize>,
+}
+
+impl ConnectionLimiter {
+    /// 未超出限制时占用一个连接名额，名额在返回的 guard 被释放时归还
+    pub fn acquire(self: &Arc<Self>, ip: IpAddr, config: &AppConfig) -> Option<ConnectionGuard> {
+        let mut counts = self.counts.lock().unwrap();
+        let per_ip = counts.per_ip.get(&ip).copied().unwrap_or(0);
+        if (config.ws_max_connections > 0 && counts.total >= config.ws_max_connections)
+            || (config.ws_max_connections_per_ip > 0 && per_ip >= config.ws_max_connections_per_ip)
+        {
+            return None;
+        }
+        counts.total += 1;
+        counts.per_ip.insert(ip, per_ip + 1);
+        Some(ConnectionGuard {
+            limiter: self.clone(),
+            ip,
+        })
+    }
+}
+
+/// 一个已占用的连接名额
+pub struct ConnectionGuard {
+    limiter: Arc<ConnectionLimiter>,
+    ip: IpAddr,
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        let mut counts = self.limiter.counts.lock().unwrap();
+        counts.total -= 1;
+        if let Some(count) = counts.per_ip.get_mut(&self.ip) {
+            *count -= 1;
+            if *count == 0 {
+                counts.per_ip.remove(&self.ip);
+            }
+        }
+    }
+}
 
 /// WebSocket 连接入口
 pub async fn ws_handler(
     ws: WebSocketUpgrade,
+    headers: HeaderMap,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
     State(broadcaster): State<Arc<Broadcaster>>,
-) -> impl IntoResponse {
+    Extension(config): Extension<Arc<AppConfig>>,
+    Extension(limiter): Extension<Arc<ConnectionLimiter>>,
+) -> Response {
     info!("WebSocket connection request received");
-    ws.on_upgrade(move |socket| handle_socket(socket, broadcaster))
+    if !origin_allowed(&headers, &config) {
+        warn!("Rejected WebSocket connection from disallowed origin");
+        let api_error = ApiError {
+            message: "Origin is not allowed to open WebSocket connections.".to_string(),
+        };
+        return (StatusCode::FORBIDDEN, Json(api_error)).into_response();
+    }
+    let ip = resolve_client_ip(peer.ip(), &headers, &config);
+    let Some(guard) = limiter.acquire(ip, &config) else {
+        warn!(
+            "Rejected WebSocket connection from {}: connection limit reached",
+            ip
+        );
+        let api_error = ApiError {
+            message: "Too many WebSocket connections.".to_string(),
+        };
+        return (StatusCode::TOO_MANY_REQUESTS, Json(api_error)).into_response();
+    };
+    ws.protocols([BINARY_PROTOCOL])
+        .on_upgrade(move |socket| handle_socket(socket, broadcaster, config, guard))
 }
 
-pub async fn handle_socket(socket: WebSocket, broadcaster: Arc<Broadcaster>) {
-    info!("WebSocket connection established");
+/// 检查握手请求的 `Origin` 是否在 `ALLOWED_ORIGINS` 中
+///
+/// Requests without an `Origin` header come from non-browser clients, which can send any
+/// origin they like, so they are let through.
+fn origin_allowed(headers: &HeaderMap, config: &AppConfig) -> bool {
+    let Some(allowed) = config.allowed_origins.as_ref() else {
+        return true;
+    };
+    match headers.get(header::ORIGIN) {
+        None => true,
+        Some(origin) => origin.to_str().is_ok_and(|origin| {
+            let origin = origin.trim_end_matches('/').to_ascii_lowercase();
+            allowed.contains(&origin)
+        }),
+    }
+}
+
+pub async fn handle_socket(
+    socket: WebSocket,
+    broadcaster: Arc<Broadcaster>,
+    config: Arc<AppConfig>,
+    guard: ConnectionGuard,
+) {
+    let binary = socket
+        .protocol()
+        .is_some_and(|protocol| protocol.as_bytes() == BINARY_PROTOCOL.as_bytes());
+    info!("WebSocket connection established (binary: {})", binary);
     let (mut ws_sender, mut ws_receiver): (SplitSink<WebSocket, Message>, _) = socket.split();
     let mut rx = broadcaster.subscribe();
 
+    let batching = !config.ws_batch_interval.is_zero();
+    let batch_max = config.ws_batch_max;
+    let mut flush = interval(config.ws_batch_interval.max(Duration::from_millis(1)));
+    flush.set_missed_tick_behavior(MissedTickBehavior::Delay);
     let send_task = tokio::spawn(async move {
+        let mut batch = Vec::new();
         loop {
-            match rx.recv().await {
-                Ok(key) => {
-                    if ws_sender.send(Message::Text(key.into())).await.is_err() {
-                        warn!("WebSocket send failed, client disconnected?");
+            let flush_now = tokio::select! {
+                result = rx.recv() => match result {
+                    Ok(event) if batching => {
+                        batch.push(event);
+                        batch.len() >= batch_max
+                    }
+                    Ok(event) => {
+                        let message = if binary {
+                            Message::Binary(encode_binary(&event).into())
+                        } else {
+                            Message::Text(event.key.into())
+                        };
+                        if ws_sender.send(message).await.is_err() {
+                            warn!("WebSocket send failed, client disconnected?");
+                            break;
+                        }
+                        false
+                    }
+                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                        warn!("WebSocket receiver lagged behind by {} messages.", n);
+                        false
+                    }
+                    Err(broadcast::error::RecvError::Closed) => {
+                        // The broadcaster has been dropped, no more messages to receive
                         break;
                     }
-                }
-                Err(broadcast::error::RecvError::Lagged(n)) => {
-                    warn!("WebSocket receiver lagged behind by {} messages.", n);
-                }
-                Err(broadcast::error::RecvError::Closed) => {
-                    // The broadcaster has been dropped, no more messages to receive
+                },
+                _ = flush.tick(), if batching => true,
+            };
+            if flush_now && !batch.is_empty() {
+                let message = encode_batch(&batch, binary);
+                batch.clear();
+                if ws_sender.send(message).await.is_err() {
+                    warn!("WebSocket send failed, client disconnected?");
                     break;
                 }
             }
@@ -77,5 +240,41 @@ pub async fn handle_socket(socket: WebSocket, broadcaster: Arc<Broadcaster>) {
         _ = send_task => { /* Send task finished */ }
         _ = recv_task => { /* Receive task finished */ }
     }
+    // 归还连接名额
+    drop(guard);
     info!("WebSocket connection closed.");
 }
+
+/// 按二进制格式编码事件
+fn encode_binary(event: &HitEvent) -> Vec<u8> {
+    let key = event.key.as_bytes();
+    let mut buf = Vec::with_capacity(key.len() + 12);
+    write_varint(&mut buf, key.len() as u64);
+    buf.extend_from_slice(key);
+    write_varint(&mut buf, event.total.max(0) as u64);
+    buf
+}
+
+/// 将一批事件编码为一个帧
+fn encode_batch(events: &[HitEvent], binary: bool) -> Message {
+    if binary {
+        Message::Binary(
+            events
+                .iter()
+                .flat_map(encode_binary)
+                .collect::<Vec<_>>()
+                .into(),
+        )
+    } else {
+        let keys: Vec<&str> = events.iter().map(|event| event.key.as_str()).collect();
+        Message::Text(serde_json::to_string(&keys).unwrap_or_default().into())
+    }
+}
+
+fn write_varint(buf: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buf.push((value as u8 & 0x7f) | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
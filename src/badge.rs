@@ -0,0 +1,137 @@
+//! 徽章渲染
+//!
+//! Every style except `square` is rendered by the shields crate. `square` is a compact
+//! tile for sidebars and profile cards: the label in a small band on top and the count
+//! in large bold type below. It uses the same fonts and width tables as the other
+//! styles, and takes its colors from a flat render of the same parameters, so named
+//! colors, CSS colors and the light/dark text choice behave exactly as elsewhere.
+//! Logos are not drawn on square tiles.
+
+use crate::api::types::BadgeStyle;
+use shields::{get_text_width, render_badge_svg, Font};
+
+/// 方形徽章的最小宽度，也是高度（像素）
+const SQUARE_SIZE: f64 = 48.0;
+
+/// 标签栏的高度（像素）
+const LABEL_BAND: f64 = 18.0;
+
+/// 文本左右的留白（像素）
+const SQUARE_PADDING: f64 = 8.0;
+
+/// 计数相对 10px 字体的放大倍数
+const COUNT_SCALE: f64 = 2.0;
+
+/// 按样式渲染徽章
+pub fn render(style: BadgeStyle, params: &shields::BadgeParams) -> String {
+    match style {
+        BadgeStyle::Square => render_square(params),
+        _ => render_badge_svg(params),
+    }
+}
+
+/// 方形徽章的配色：标签底色、计数底色、标签文字色、计数文字色
+struct SquareColors {
+    label: String,
+    message: String,
+    label_text: String,
+    message_text: String,
+}
+
+fn render_square(params: &shields::BadgeParams) -> String {
+    let label = params.label.unwrap_or_default();
+    let message = params.message.unwrap_or_default();
+    let colors = square_colors(params);
+    let label_width = get_text_width(label, Font::VerdanaNormal10);
+    let count_width = get_text_width(message, Font::VerdanaBold10) * COUNT_SCALE;
+    let width = (label_width.max(count_width) + 2.0 * SQUARE_PADDING)
+        .ceil()
+        .max(SQUARE_SIZE);
+    let band = if label.is_empty() { 0.0 } else { LABEL_BAND };
+    // 文本以 10 倍坐标绘制后缩小，与 shields 的样式一致
+    let center = width * 5.0;
+    let count_baseline = (band + (SQUARE_SIZE - band) / 2.0 + 7.0) * 10.0;
+    let title = if label.is_empty() {
+        message.to_string()
+    } else {
+        format!("{}: {}", label, message)
+    };
+    let mut body = format!(
+        r##"<g clip-path="url(#r)"><rect width="{width}" height="{band}" fill="{label_color}"/><rect y="{band}" width="{width}" height="{rest}" fill="{message_color}"/></g><g text-anchor="middle" font-family="Verdana,Geneva,DejaVu Sans,sans-serif" text-rendering="geometricPrecision">"##,
+        width = width,
+        band = band,
+        rest = SQUARE_SIZE - band,
+        label_color = colors.label,
+        message_color = colors.message,
+    );
+    if !label.is_empty() {
+        body.push_str(&format!(
+            r#"<text x="{}" y="130" transform="scale(.1)" font-size="100" fill="{}" textLength="{}">{}</text>"#,
+            center,
+            colors.label_text,
+            (label_width * 10.0).round(),
+            label
+        ));
+    }
+    body.push_str(&format!(
+        r#"<text x="{}" y="{}" transform="scale(.1)" font-size="{}" font-weight="bold" fill="{}" textLength="{}">{}</text></g>"#,
+        center,
+        count_baseline,
+        100.0 * COUNT_SCALE,
+        colors.message_text,
+        (count_width * 10.0).round(),
+        message
+    ));
+    if let Some(link) = params.link {
+        body = format!(r#"<a target="_blank" href="{}">{}</a>"#, link, body);
+    }
+    format!(
+        r##"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}" role="img" aria-label="{title}"><title>{title}</title><clipPath id="r"><rect width="{width}" height="{height}" rx="4" fill="#fff"/></clipPath>{body}</svg>"##,
+        width = width,
+        height = SQUARE_SIZE,
+        title = title,
+        body = body,
+    )
+}
+
+/// 从同参数的 flat 徽章中读取 shields 解析后的颜色
+///
+/// The label is replaced by a placeholder, since an empty label changes the flat layout.
+fn square_colors(params: &shields::BadgeParams) -> SquareColors {
+    let flat = render_badge_svg(&shields::BadgeParams {
+        style: shields::BadgeStyle::Flat,
+        label: Some("label"),
+        message: Some("message"),
+        logo: None,
+        link: None,
+        extra_link: None,
+        ..*params
+    });
+    let group = flat
+        .split_once("clip-path=\"url(#r)\">")
+        .map_or("", |(_, group)| group);
+    let mut fills = group
+        .split("<rect")
+        .skip(1)
+        .filter_map(|tag| attribute(tag, "fill"));
+    let mut text_fills = flat
+        .split("<text")
+        .skip(1)
+        .filter(|tag| !tag.starts_with(" aria-hidden"))
+        .filter_map(|tag| attribute(tag, "fill"));
+    SquareColors {
+        label: fills.next().unwrap_or_else(|| "#555".to_string()),
+        message: fills.next().unwrap_or_else(|| "#007ec6".to_string()),
+        label_text: text_fills.next().unwrap_or_else(|| "#fff".to_string()),
+        message_text: text_fills.next().unwrap_or_else(|| "#fff".to_string()),
+    }
+}
+
+/// 开始标签中的属性值
+fn attribute(tag: &str, name: &str) -> Option<String> {
+    let tag = &tag[..tag.find('>')?];
+    let needle = format!(" {}=\"", name);
+    let start = tag.find(&needle)? + needle.len();
+    let end = start + tag[start..].find('"')?;
+    Some(tag[start..end].to_string())
+}
@@ -0,0 +1,140 @@
+//! 已知爬虫与图片代理的网段
+//!
+//! `BOT_IP_LISTS` names published IP range lists, such as GitHub's `/meta` API or
+//! Google's `googlebot.json`. Each is downloaded at startup and again every
+//! `BOT_IP_REFRESH_SECONDS`. Plain text lists hold one CIDR per line (`#` starts a
+//! comment); for JSON documents every string that parses as an address or CIDR is used,
+//! wherever it appears. A list that fails to download or parse keeps its previous ranges.
+//!
+//! Hits from these ranges are answered but not counted. With `BOT_IP_ACTION=tally` they
+//! are added to a per-key daily tally in `bot_counts`, served by `/bots/{key}`.
+
+use crate::config::AppConfig;
+use crate::error::AppError;
+use ipnet::IpNet;
+use serde_json::Value;
+use sqlx::postgres::PgPool;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// 下载单个名单的最长时间
+const FETCH_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// 内存中的网段，按名单 URL 分组
+#[derive(Default)]
+pub struct BotNetworks {
+    lists: RwLock<HashMap<String, Vec<IpNet>>>,
+}
+
+impl BotNetworks {
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        self.lists
+            .read()
+            .unwrap()
+            .values()
+            .flatten()
+            .any(|net| net.contains(&ip))
+    }
+
+    async fn refresh(&self, client: &reqwest::Client, url: &str) -> Result<usize, String> {
+        let body = client
+            .get(url)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| e.to_string())?
+            .text()
+            .await
+            .map_err(|e| e.to_string())?;
+        let nets = parse_list(&body);
+        if nets.is_empty() {
+            return Err("no IP ranges found".to_string());
+        }
+        let count = nets.len();
+        self.lists.write().unwrap().insert(url.to_string(), nets);
+        Ok(count)
+    }
+}
+
+/// 解析名单：JSON 文档中的所有网段字符串，否则按行解析
+fn parse_list(body: &str) -> Vec<IpNet> {
+    let mut nets = Vec::new();
+    match serde_json::from_str::<Value>(body) {
+        Ok(value) => collect_nets(&value, &mut nets),
+        Err(_) => nets.extend(
+            body.lines()
+                .filter_map(|line| line.split('#').next())
+                .filter_map(parse_net),
+        ),
+    }
+    nets.sort();
+    nets.dedup();
+    nets
+}
+
+fn collect_nets(value: &Value, nets: &mut Vec<IpNet>) {
+    match value {
+        Value::String(text) => nets.extend(parse_net(text)),
+        Value::Array(items) => items.iter().for_each(|item| collect_nets(item, nets)),
+        Value::Object(fields) => fields.values().for_each(|field| collect_nets(field, nets)),
+        _ => {}
+    }
+}
+
+/// 单个网段，不带前缀长度的地址视为单个主机
+fn parse_net(text: &str) -> Option<IpNet> {
+    let text = text.trim();
+    text.parse::<IpNet>()
+        .ok()
+        .or_else(|| text.parse::<IpAddr>().ok().map(IpNet::from))
+}
+
+/// 数据库操作：给 key 的当日爬虫计数加一
+pub async fn record(
+    pool: &PgPool,
+    stored_key: &str,
+    window_offset_minutes: i32,
+) -> Result<(), AppError> {
+    sqlx::query!(
+        r#"
+        INSERT INTO bot_counts (key, day_window, count)
+        VALUES (
+            $1,
+            DATE_TRUNC('day', NOW() - make_interval(mins => $2), 'UTC')
+                + make_interval(mins => $2),
+            1
+        )
+        ON CONFLICT (key, day_window)
+        DO UPDATE SET count = bot_counts.count + 1
+        "#,
+        stored_key,
+        window_offset_minutes
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// 定期下载名单的后台任务
+pub async fn run(config: Arc<AppConfig>, networks: Arc<BotNetworks>) {
+    let client = match reqwest::Client::builder().timeout(FETCH_TIMEOUT).build() {
+        Ok(client) => client,
+        Err(e) => {
+            warn!("Failed to create HTTP client for bot IP lists: {}", e);
+            return;
+        }
+    };
+    let mut interval = tokio::time::interval(config.bot_ip_refresh);
+    loop {
+        interval.tick().await;
+        for url in &config.bot_ip_lists {
+            match networks.refresh(&client, url).await {
+                Ok(count) => info!("Loaded {} bot IP ranges from {}", count, url),
+                Err(e) => warn!("Failed to load bot IP ranges from {}: {}", url, e),
+            }
+        }
+    }
+}
@@ -0,0 +1,89 @@
+//! 验证码保护的计数
+//!
+//! Keys listed in `PROTECTED_KEYS` only count a hit when it carries a Cloudflare Turnstile
+//! or hCaptcha response token, passed in the `X-Hits-Captcha` header or the `captcha`
+//! query parameter, that the provider's siteverify endpoint accepts. Requests without a
+//! valid token get the current total and an `X-Hits-Captcha: required` header. Providers
+//! accept each token once, so a replayed token is rejected by them.
+//!
+//! Verification failures (timeouts, provider errors) are logged and treated as invalid
+//! tokens, so an outage stops protected keys from counting rather than opening them up.
+
+use crate::config::{AppConfig, CaptchaProvider};
+use serde::Deserialize;
+use std::net::IpAddr;
+use std::time::Duration;
+use tracing::warn;
+
+/// 请求携带 token、响应提示需要验证的头部
+pub const CAPTCHA_HEADER: &str = "x-hits-captcha";
+
+/// 等待验证接口的最长时间
+const VERIFY_TIMEOUT: Duration = Duration::from_secs(5);
+
+impl CaptchaProvider {
+    fn verify_url(self) -> &'static str {
+        match self {
+            Self::Turnstile => "https://challenges.cloudflare.com/turnstile/v0/siteverify",
+            Self::Hcaptcha => "https://api.hcaptcha.com/siteverify",
+        }
+    }
+}
+
+/// siteverify 响应中用到的字段
+#[derive(Deserialize)]
+struct VerifyResponse {
+    success: bool,
+}
+
+/// 向验证码服务校验 token
+pub struct CaptchaVerifier {
+    client: reqwest::Client,
+    provider: Option<(CaptchaProvider, String)>,
+}
+
+impl CaptchaVerifier {
+    pub fn new(config: &AppConfig) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(VERIFY_TIMEOUT)
+            .build()
+            .expect("TLS backend is available");
+        Self {
+            client,
+            provider: config.captcha_provider.zip(config.captcha_secret.clone()),
+        }
+    }
+
+    /// token 被服务接受时返回 true，未配置服务或请求失败时返回 false
+    pub async fn verify(&self, token: &str, client_ip: Option<IpAddr>) -> bool {
+        let Some((provider, secret)) = &self.provider else {
+            return false;
+        };
+        let mut form = vec![("secret", secret.clone()), ("response", token.to_string())];
+        if let Some(ip) = client_ip {
+            form.push(("remoteip", ip.to_string()));
+        }
+        let response = self
+            .client
+            .post(provider.verify_url())
+            .form(&form)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status());
+        let body = match response {
+            Ok(response) => response.bytes().await,
+            Err(e) => Err(e),
+        };
+        match body.map(|body| serde_json::from_slice::<VerifyResponse>(&body)) {
+            Ok(Ok(result)) => result.success,
+            Ok(Err(e)) => {
+                warn!("Unexpected {:?} siteverify response: {}", provider, e);
+                false
+            }
+            Err(e) => {
+                warn!("Failed to verify {:?} token: {}", provider, e);
+                false
+            }
+        }
+    }
+}
@@ -0,0 +1,116 @@
+//! 双重提交计数校验
+//!
+//! For keys listed in `CHALLENGE_KEYS`, a request without a valid token only receives a
+//! freshly issued, signed token in the `X-Hits-Challenge` response header. The hit is
+//! counted when a follow-up request sends that token back in the same header. Tokens are
+//! bound to the key, expire after `CHALLENGE_TTL_SECONDS` and can be redeemed once.
+//!
+//! Issued tokens are kept in process memory, so the follow-up request must reach the same
+//! instance. At most [`MAX_OUTSTANDING`] unredeemed tokens are kept; beyond that the oldest
+//! are dropped, so a flood of requests cannot grow memory without bound. Plain `<img>`
+//! embeds cannot echo headers and will never increment such keys.
+
+use crate::config::AppConfig;
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use sha2::Sha256;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// 请求与响应中携带 token 的头部
+pub const CHALLENGE_HEADER: &str = "x-hits-challenge";
+
+/// 同时保留的未兑换 token 上限
+pub const MAX_OUTSTANDING: usize = 100_000;
+
+/// 已签发 token 的存储
+pub struct ChallengeStore {
+    secret: Vec<u8>,
+    ttl: Duration,
+    issued: Mutex<Issued>,
+}
+
+/// 未兑换的 token 及其签发顺序
+///
+/// All tokens share one TTL, so issue order is also expiry order and expired or evicted
+/// tokens are always at the front of `order`. Redeemed nonces stay in `order` until they
+/// reach the front.
+#[derive(Default)]
+struct Issued {
+    expires: HashMap<String, Instant>,
+    order: VecDeque<String>,
+}
+
+impl ChallengeStore {
+    pub fn new(config: &AppConfig) -> Self {
+        let secret = match &config.challenge_secret {
+            Some(secret) => secret.as_bytes().to_vec(),
+            None => {
+                let mut secret = vec![0u8; 32];
+                rand::rng().fill_bytes(&mut secret);
+                secret
+            }
+        };
+        Self {
+            secret,
+            ttl: config.challenge_ttl,
+            issued: Mutex::new(Issued::default()),
+        }
+    }
+
+    /// 为 key 签发新的 token
+    pub fn issue(&self, key: &str) -> String {
+        let mut nonce = [0u8; 16];
+        rand::rng().fill_bytes(&mut nonce);
+        let nonce = hex::encode(nonce);
+        let signature = self.sign(key, &nonce);
+        let now = Instant::now();
+        let mut issued = self.issued.lock().unwrap();
+        while let Some(oldest) = issued.order.front() {
+            let expired = issued
+                .expires
+                .get(oldest)
+                .is_none_or(|expires_at| *expires_at <= now);
+            if !expired && issued.order.len() < MAX_OUTSTANDING {
+                break;
+            }
+            if let Some(oldest) = issued.order.pop_front() {
+                issued.expires.remove(&oldest);
+            }
+        }
+        issued.expires.insert(nonce.clone(), now + self.ttl);
+        issued.order.push_back(nonce.clone());
+        format!("{}.{}", nonce, signature)
+    }
+
+    /// 校验并消耗 token，成功时返回 true
+    pub fn redeem(&self, key: &str, token: &str) -> bool {
+        let Some((nonce, signature)) = token.split_once('.') else {
+            return false;
+        };
+        let Ok(signature) = hex::decode(signature) else {
+            return false;
+        };
+        if self.mac(key, nonce).verify_slice(&signature).is_err() {
+            return false;
+        }
+        let mut issued = self.issued.lock().unwrap();
+        matches!(issued.expires.remove(nonce), Some(expires_at) if expires_at > Instant::now())
+    }
+
+    fn mac(&self, key: &str, nonce: &str) -> HmacSha256 {
+        let mut mac =
+            HmacSha256::new_from_slice(&self.secret).expect("HMAC accepts any key length");
+        mac.update(key.as_bytes());
+        mac.update(b":");
+        mac.update(nonce.as_bytes());
+        mac
+    }
+
+    fn sign(&self, key: &str, nonce: &str) -> String {
+        hex::encode(self.mac(key, nonce).finalize().into_bytes())
+    }
+}
@@ -0,0 +1,211 @@
+//! 客户端 IP 解析
+//!
+//! The peer address is only replaced by a forwarded address when the peer is a trusted
+//! proxy. The proxies' header (`X-Forwarded-For`, or RFC 7239 `Forwarded` with
+//! `FORWARDED_HEADER=forwarded`) is walked from the right (the entries appended by our
+//! own proxies) and the first untrusted address wins, so spoofed entries a client
+//! prepends on the left are never reached while a trusted hop sits in front of them.
+
+use crate::config::{AppConfig, ForwardedHeader};
+use axum::http::HeaderMap;
+use ipnet::IpNet;
+use std::net::{IpAddr, SocketAddr};
+
+/// 最多解析的转发跳数，超出部分直接忽略
+pub const MAX_FORWARDED_HOPS: usize = 16;
+
+/// 解析真实客户端 IP
+pub fn resolve_client_ip(peer: IpAddr, headers: &HeaderMap, config: &AppConfig) -> IpAddr {
+    if !is_trusted(peer, &config.trusted_proxies) {
+        return peer;
+    }
+    let mut leftmost = None;
+    let hops = match config.forwarded_header {
+        ForwardedHeader::XForwardedFor => forwarded_for_hops(headers),
+        ForwardedHeader::Forwarded => forwarded_hops(headers),
+    };
+    for ip in hops {
+        if !is_trusted(ip, &config.trusted_proxies) {
+            return ip;
+        }
+        leftmost = Some(ip);
+    }
+    leftmost.unwrap_or(peer)
+}
+
+/// 从右向左返回 `X-Forwarded-For` 中合法的 IP，最多 [`MAX_FORWARDED_HOPS`] 个
+///
+/// Entries that are not valid IPs (optionally with a port) are skipped. Only the last
+/// `MAX_FORWARDED_HOPS` comma-separated entries are ever looked at, so oversized
+/// chains cost no more than short ones.
+fn forwarded_for_hops(headers: &HeaderMap) -> Vec<IpAddr> {
+    headers
+        .get_all("x-forwarded-for")
+        .iter()
+        .rev()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.rsplit(','))
+        .take(MAX_FORWARDED_HOPS)
+        .filter_map(parse_hop)
+        .collect()
+}
+
+/// 从右向左返回 `Forwarded` 中各元素 `for=` 参数的合法 IP，最多 [`MAX_FORWARDED_HOPS`] 个
+///
+/// Elements without a `for=` IP, such as `for=unknown` or obfuscated `for=_hidden`
+/// identifiers, are skipped like malformed `X-Forwarded-For` entries.
+fn forwarded_hops(headers: &HeaderMap) -> Vec<IpAddr> {
+    headers
+        .get_all("forwarded")
+        .iter()
+        .rev()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.rsplit(','))
+        .take(MAX_FORWARDED_HOPS)
+        .filter_map(|element| {
+            element.split(';').find_map(|pair| {
+                let (name, value) = pair.split_once('=')?;
+                if !name.trim().eq_ignore_ascii_case("for") {
+                    return None;
+                }
+                let value = value.trim().trim_matches('"');
+                // `[2001:db8::1]` 不带端口时 SocketAddr 无法解析，先去掉方括号
+                let value = value
+                    .strip_prefix('[')
+                    .and_then(|rest| rest.strip_suffix(']'))
+                    .unwrap_or(value);
+                parse_hop(value)
+            })
+        })
+        .collect()
+}
+
+/// 解析单个跳点：`1.2.3.4`、`1.2.3.4:80`、`2001:db8::1` 或 `[2001:db8::1]:80`
+fn parse_hop(entry: &str) -> Option<IpAddr> {
+    let entry = entry.trim();
+    if entry.is_empty() || entry.len() > 64 {
+        return None;
+    }
+    entry
+        .parse::<IpAddr>()
+        .or_else(|_| entry.parse::<SocketAddr>().map(|addr| addr.ip()))
+        .ok()
+}
+
+fn is_trusted(ip: IpAddr, trusted: &[IpNet]) -> bool {
+    trusted.iter().any(|net| net.contains(&ip))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    const PEER: &str = "10.0.0.1";
+
+    fn config(trusted: &[&str], header: ForwardedHeader) -> AppConfig {
+        AppConfig {
+            trusted_proxies: trusted.iter().map(|net| net.parse().unwrap()).collect(),
+            forwarded_header: header,
+            ..Default::default()
+        }
+    }
+
+    fn resolve(name: &'static str, values: &[&str], config: &AppConfig) -> IpAddr {
+        let mut headers = HeaderMap::new();
+        for value in values {
+            headers.append(name, HeaderValue::from_str(value).unwrap());
+        }
+        resolve_client_ip(PEER.parse().unwrap(), &headers, config)
+    }
+
+    fn ip(value: &str) -> IpAddr {
+        value.parse().unwrap()
+    }
+
+    #[test]
+    fn untrusted_peer_ignores_header() {
+        let config = config(&[], ForwardedHeader::XForwardedFor);
+        assert_eq!(
+            resolve("x-forwarded-for", &["203.0.113.7"], &config),
+            ip(PEER)
+        );
+    }
+
+    #[test]
+    fn walks_trusted_hops_from_the_right() {
+        let config = config(&["10.0.0.0/8"], ForwardedHeader::XForwardedFor);
+        let client = resolve(
+            "x-forwarded-for",
+            &["198.51.100.9, 203.0.113.7, 10.0.0.2, 10.0.0.3"],
+            &config,
+        );
+        assert_eq!(client, ip("203.0.113.7"));
+    }
+
+    #[test]
+    fn spoofed_left_entries_are_not_reached() {
+        let config = config(&["10.0.0.0/8"], ForwardedHeader::XForwardedFor);
+        // 客户端自己写入的 1.1.1.1 位于真实地址左侧
+        let client = resolve("x-forwarded-for", &["1.1.1.1", "203.0.113.7"], &config);
+        assert_eq!(client, ip("203.0.113.7"));
+    }
+
+    #[test]
+    fn all_trusted_falls_back_to_leftmost() {
+        let config = config(&["10.0.0.0/8"], ForwardedHeader::XForwardedFor);
+        let client = resolve("x-forwarded-for", &["10.0.0.5, 10.0.0.2"], &config);
+        assert_eq!(client, ip("10.0.0.5"));
+    }
+
+    #[test]
+    fn garbage_entries_are_skipped() {
+        let config = config(&["10.0.0.0/8"], ForwardedHeader::XForwardedFor);
+        let client = resolve(
+            "x-forwarded-for",
+            &["203.0.113.7, not-an-ip, , 999.1.1.1, unknown, 10.0.0.2"],
+            &config,
+        );
+        assert_eq!(client, ip("203.0.113.7"));
+        let client = resolve("x-forwarded-for", &["garbage"], &config);
+        assert_eq!(client, ip(PEER));
+    }
+
+    #[test]
+    fn oversized_chains_only_read_the_last_hops() {
+        let config = config(&["10.0.0.0/8"], ForwardedHeader::XForwardedFor);
+        let mut chain = vec!["203.0.113.7".to_string()];
+        chain.extend((0..MAX_FORWARDED_HOPS).map(|i| format!("10.0.1.{}", i)));
+        let client = resolve("x-forwarded-for", &[&chain.join(",")], &config);
+        assert_eq!(client, ip("10.0.1.0"));
+        assert_eq!(parse_hop(&"1".repeat(65)), None);
+    }
+
+    #[test]
+    fn ports_and_brackets() {
+        assert_eq!(parse_hop("203.0.113.7:8080"), Some(ip("203.0.113.7")));
+        assert_eq!(parse_hop(" 2001:db8::1 "), Some(ip("2001:db8::1")));
+        assert_eq!(parse_hop("[2001:db8::1]:443"), Some(ip("2001:db8::1")));
+        assert_eq!(parse_hop("[2001:db8::1"), None);
+        assert_eq!(parse_hop("203.0.113.7:port"), None);
+    }
+
+    #[test]
+    fn forwarded_header() {
+        let config = config(&["10.0.0.0/8"], ForwardedHeader::Forwarded);
+        let client = resolve(
+            "forwarded",
+            &[
+                r#"for=198.51.100.9, for="[2001:db8::1]:443";proto=https, for=_hidden, for=10.0.0.2"#,
+            ],
+            &config,
+        );
+        assert_eq!(client, ip("2001:db8::1"));
+        let client = resolve(
+            "forwarded",
+            &["for=unknown;by=10.0.0.2, proto=http"],
+            &config,
+        );
+        assert_eq!(client, ip(PEER));
+    }
+}
@@ -0,0 +1,56 @@
+//! 广播事件合并
+//!
+//! With `BROADCAST_COALESCE_MS` set, handlers publish hit events to an ingress channel and
+//! this task forwards them to WebSocket subscribers once per window. Repeated events for
+//! the same key within a window collapse into one event carrying the latest total, so
+//! clients end up with the value `/hits` reports even when it went down (count noise, a
+//! rename, a seed change or a reset).
+
+use crate::api::ws::{Broadcaster, HitEvent};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio::time::MissedTickBehavior;
+use tracing::warn;
+
+/// 合并任务
+pub async fn run(
+    mut ingress: broadcast::Receiver<HitEvent>,
+    subscribers: Arc<Broadcaster>,
+    window: Duration,
+) {
+    let mut pending: Vec<HitEvent> = Vec::new();
+    let mut positions: HashMap<String, usize> = HashMap::new();
+    let mut ticker = tokio::time::interval(window);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        tokio::select! {
+            received = ingress.recv() => match received {
+                Ok(event) => match positions.get(&event.key) {
+                    Some(&index) => {
+                        let existing = &mut pending[index];
+                        existing.total = event.total;
+                    }
+                    None => {
+                        positions.insert(event.key.clone(), pending.len());
+                        pending.push(event);
+                    }
+                },
+                Err(broadcast::error::RecvError::Lagged(n)) => {
+                    warn!("Broadcast coalescer lagged behind by {} events.", n);
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+            _ = ticker.tick() => {
+                positions.clear();
+                for event in pending.drain(..) {
+                    subscribers.send(event).ok();
+                }
+            }
+        }
+    }
+    for event in pending {
+        subscribers.send(event).ok();
+    }
+}
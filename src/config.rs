@@ -0,0 +1,1035 @@
+//! 应用运行时配置
+
+use anyhow::{bail, Context, Result};
+use axum::http::{HeaderMap, HeaderName};
+use hmac::{Hmac, Mac};
+use ipnet::IpNet;
+use regex::{Regex, RegexSet, RegexSetBuilder};
+use sha2::Sha256;
+use std::{
+    collections::{HashMap, HashSet},
+    env,
+    net::IpAddr,
+    path::PathBuf,
+    time::Duration,
+};
+
+/// JSON 字段命名风格
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JsonCasing {
+    /// `schemaVersion`
+    Camel,
+    /// `schema_version`
+    Snake,
+}
+
+/// 受保护 key 使用的验证码服务
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptchaProvider {
+    /// Cloudflare Turnstile
+    Turnstile,
+    Hcaptcha,
+}
+
+/// 计数行的时间粒度
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Granularity {
+    Second,
+    #[default]
+    Minute,
+    Hour,
+    Day,
+}
+
+impl Granularity {
+    /// `DATE_TRUNC` 使用的单位名
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Second => "second",
+            Self::Minute => "minute",
+            Self::Hour => "hour",
+            Self::Day => "day",
+        }
+    }
+
+    /// 一个计数行覆盖的秒数
+    pub fn seconds(self) -> i32 {
+        match self {
+            Self::Second => 1,
+            Self::Minute => 60,
+            Self::Hour => 3600,
+            Self::Day => 86400,
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "second" => Some(Self::Second),
+            "minute" => Some(Self::Minute),
+            "hour" => Some(Self::Hour),
+            "day" => Some(Self::Day),
+            _ => None,
+        }
+    }
+}
+
+/// 受信代理用来传递客户端地址的请求头
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ForwardedHeader {
+    /// `X-Forwarded-For: 192.0.2.1, 198.51.100.2`
+    #[default]
+    XForwardedFor,
+    /// RFC 7239 `Forwarded: for=192.0.2.1, for="[2001:db8::1]:80"`
+    Forwarded,
+}
+
+/// 来自已知爬虫或代理网段的访问如何处理
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BotIpAction {
+    /// Answer the request but do not count it
+    #[default]
+    Exclude,
+    /// Do not count it, but add it to a separate per-key tally
+    Tally,
+}
+
+/// 请求头匹配规则：`Name` 要求头部存在，`Name=value` 要求头部包含该值
+#[derive(Debug, Clone)]
+pub struct HeaderRule {
+    pub name: HeaderName,
+    pub value: Option<String>,
+}
+
+impl HeaderRule {
+    fn parse(entry: &str) -> Result<Self> {
+        let (name, value) = match entry.split_once('=') {
+            Some((name, value)) => (name.trim(), Some(value.trim().to_ascii_lowercase())),
+            None => (entry.trim(), None),
+        };
+        let name = HeaderName::from_bytes(name.as_bytes())
+            .with_context(|| format!("Invalid header name in rule: {}", entry))?;
+        Ok(Self { name, value })
+    }
+
+    /// 请求是否满足该规则
+    ///
+    /// Values are compared case-insensitively against each `,`/`;`-separated token, so
+    /// `Sec-Purpose=prefetch` also matches `prefetch;prerender`.
+    pub fn matches(&self, headers: &HeaderMap) -> bool {
+        headers
+            .get_all(&self.name)
+            .iter()
+            .any(|header| match &self.value {
+                None => true,
+                Some(expected) => header.to_str().is_ok_and(|header| {
+                    header
+                        .split([',', ';'])
+                        .any(|token| token.trim().eq_ignore_ascii_case(expected))
+                }),
+            })
+    }
+}
+
+/// 从环境变量加载的运行时配置
+#[derive(Debug, Clone, Default)]
+pub struct AppConfig {
+    /// Bearer token required by the admin endpoints. Admin endpoints reject every request when unset.
+    pub admin_token: Option<String>,
+    /// Bearer token that allows `/hits/{key}?amount=N` (`AMOUNT_TOKEN`). `amount` is rejected when unset.
+    pub amount_token: Option<String>,
+    /// Largest `amount` a single request may add (`AMOUNT_MAX`, default 1000).
+    pub amount_max: i32,
+    /// Global override for JSON field casing (`JSON_CASING=camel|snake`).
+    /// When unset, the badge endpoint uses camelCase (shields.io) and everything else snake_case.
+    pub json_casing: Option<JsonCasing>,
+    /// Hosts `/go/{key}` may redirect to (`REDIRECT_ALLOWED_HOSTS`, comma-separated).
+    /// `*.example.com` matches any subdomain. Redirects are refused when empty.
+    pub redirect_allowed_hosts: Vec<String>,
+    /// Public base URL of this service used by `/snippet/{key}` (`PUBLIC_URL`), e.g. `https://hits.example.com`.
+    /// The request's `Host` header is used when unset.
+    pub public_url: Option<url::Url>,
+    /// Reverse proxies whose forwarded client addresses are trusted (`TRUSTED_PROXIES`, comma-separated IPs or CIDRs).
+    pub trusted_proxies: Vec<IpNet>,
+    /// Header the trusted proxies put the client address in (`FORWARDED_HEADER=x-forwarded-for|forwarded`).
+    /// Only this one is read, so clients cannot slip an address past the proxy in the other.
+    pub forwarded_header: ForwardedHeader,
+    /// Origins allowed to open WebSocket connections (`ALLOWED_ORIGINS`, comma-separated).
+    /// `None` (unset or `*`) allows any origin.
+    pub allowed_origins: Option<Vec<String>>,
+    /// SVG badge messages above this count are shown as `{cap}{suffix}` with the cap in
+    /// compact form, e.g. `1M+` (`DISPLAY_CAP`).
+    /// Stored totals and JSON responses are unaffected.
+    pub display_cap: Option<i64>,
+    /// Suffix appended to a capped count (`DISPLAY_CAP_SUFFIX`, default `+`).
+    pub display_cap_suffix: String,
+    /// Offset in minutes applied when grouping hits into daily buckets (`WINDOW_OFFSET`),
+    /// e.g. `480` makes days start at 08:00 UTC. Must be within ±1439.
+    pub window_offset_minutes: i32,
+    /// Keys that only count a hit when the request echoes a previously issued challenge token (`CHALLENGE_KEYS`).
+    pub challenge_keys: HashSet<String>,
+    /// Keys that only count a hit carrying a valid captcha token (`PROTECTED_KEYS`).
+    pub protected_keys: HashSet<String>,
+    /// Service that verifies captcha tokens (`CAPTCHA_PROVIDER=turnstile|hcaptcha`).
+    pub captcha_provider: Option<CaptchaProvider>,
+    /// Secret key for the captcha provider's siteverify API (`CAPTCHA_SECRET`).
+    pub captcha_secret: Option<String>,
+    /// Secret used to sign challenge tokens (`CHALLENGE_SECRET`). A random one is generated when unset.
+    pub challenge_secret: Option<String>,
+    /// How long an issued challenge token stays valid (`CHALLENGE_TTL_SECONDS`, default 60).
+    pub challenge_ttl: Duration,
+    /// Keys whose total reaches this value are exported on `/metrics/keys` (`METRICS_KEYS_MIN_TOTAL`).
+    pub metrics_keys_min_total: Option<i64>,
+    /// Keys always exported on `/metrics/keys` (`METRICS_KEYS_ALLOWLIST`, comma-separated).
+    pub metrics_keys_allowlist: Vec<String>,
+    /// How often the `/metrics/keys` snapshot is refreshed (`METRICS_KEYS_INTERVAL_SECONDS`, default 60).
+    pub metrics_keys_interval: Duration,
+    /// Lowercase keys before use (`KEY_LOWERCASE`), so `Key` and `key` share a counter.
+    pub key_lowercase: bool,
+    /// Strip trailing slashes from keys and request paths (`KEY_STRIP_TRAILING_SLASH`).
+    pub key_strip_trailing_slash: bool,
+    /// Window for collapsing repeated WebSocket events of the same key (`BROADCAST_COALESCE_MS`, 0 = off).
+    pub broadcast_coalesce: Duration,
+    /// Hits are only counted when every rule matches (`COUNT_REQUIRE_HEADERS`).
+    pub count_require_headers: Vec<HeaderRule>,
+    /// Hits are not counted when any rule matches (`COUNT_REJECT_HEADERS`).
+    pub count_reject_headers: Vec<HeaderRule>,
+    /// MaxMind country database used for `?geo=true` hits (`GEOIP_DATABASE`).
+    pub geoip_database: Option<PathBuf>,
+    /// Secret shared with GitHub/GitLab webhooks (`WEBHOOK_SECRET`). `/webhook/{key}` rejects every request when unset.
+    pub webhook_secret: Option<String>,
+    /// Increment per webhook event (`WEBHOOK_EVENTS`, comma-separated `event[.action]=amount`).
+    pub webhook_events: HashMap<String, i32>,
+    /// Secret used to store keys as HMAC-SHA256 digests (`HASH_KEYS=true` with `KEY_HASH_SECRET`).
+    /// Keys are stored in plaintext when unset.
+    pub key_hash_secret: Option<String>,
+    /// Maximum counted hits per key and day (`KEY_DAILY_QUOTAS`, comma-separated `key=limit`).
+    /// Days follow `WINDOW_OFFSET`; keys without an entry are unlimited.
+    pub key_daily_quotas: HashMap<String, i64>,
+    /// Offsets added to returned totals (`KEY_BASE_COUNTS`, comma-separated `key=count`).
+    /// Kept out of the database, so they never show up in the hit series.
+    pub key_base_counts: HashMap<String, i64>,
+    /// Embed a per-response nonce comment in SVG badges so every response body differs (`BADGE_NONCE`).
+    pub badge_nonce: bool,
+    /// Read-only endpoints treat keys below this total as nonexistent (`PUBLIC_MIN_TOTAL`, default 0).
+    pub public_min_total: i64,
+    /// How often the in-memory grand total is re-read from the database (`GRAND_TOTAL_RECONCILE_SECONDS`, default 60).
+    pub grand_total_reconcile_interval: Duration,
+    /// Keys matching any of these regexes are rejected with 403 (`BLOCKED_KEY_PATTERNS`, comma-separated).
+    pub blocked_key_patterns: RegexSet,
+    /// Prefix SVG responses with an `<?xml ...?>` declaration (`SVG_XML_DECLARATION`).
+    pub svg_xml_declaration: bool,
+    /// Declare `xmlns:xlink` and use `xlink:href` in SVG responses (`SVG_XLINK`).
+    pub svg_xlink: bool,
+    /// Maximum concurrent WebSocket connections (`WS_MAX_CONNECTIONS`, 0 = unlimited).
+    pub ws_max_connections: usize,
+    /// Maximum concurrent WebSocket connections per client IP (`WS_MAX_CONNECTIONS_PER_IP`, 0 = unlimited).
+    pub ws_max_connections_per_ip: usize,
+    /// Interval for batching WebSocket events into one frame per connection (`WS_BATCH_MS`, 0 = off).
+    pub ws_batch_interval: Duration,
+    /// Flush a WebSocket batch early once it holds this many events (`WS_BATCH_MAX`, default 100).
+    pub ws_batch_max: usize,
+    /// Counter upserts slower than this are logged and counted (`SLOW_QUERY_MS`, unset = off).
+    pub slow_query_threshold: Option<Duration>,
+    /// Merge concurrent writes to the same key into one upsert (`COALESCE_WRITES`).
+    pub coalesce_writes: bool,
+    /// Salt for the visitor hashes used by `?mode=unique` (`VISITOR_SALT`). A random one is generated when unset.
+    pub visitor_salt: Option<String>,
+    /// Repeated hits from the same client within this window count once (`HITS_DEDUP_SECONDS`, 0 = off).
+    pub dedup_window: Duration,
+    /// Per-key dedup windows overriding `HITS_DEDUP_SECONDS` (`KEY_DEDUP_SECONDS`, comma-separated `key=seconds`).
+    pub key_dedup_windows: HashMap<String, Duration>,
+    /// Hits whose `User-Agent` matches any of these case-insensitive regexes are not counted
+    /// (`BOT_USER_AGENTS`, default [`DEFAULT_BOT_USER_AGENTS`]; empty when `COUNT_BOTS=true`).
+    pub bot_user_agents: RegexSet,
+    /// URLs of published bot or proxy IP range lists (`BOT_IP_LISTS`, comma-separated), as
+    /// plain text with one CIDR per line or JSON documents containing CIDR strings.
+    pub bot_ip_lists: Vec<String>,
+    /// What to do with hits from those ranges (`BOT_IP_ACTION=exclude|tally`, default `exclude`).
+    pub bot_ip_action: BotIpAction,
+    /// How often the lists are downloaded again (`BOT_IP_REFRESH_SECONDS`, default 86400).
+    pub bot_ip_refresh: Duration,
+    /// Requests per second each client IP may sustain (`RATE_LIMIT_PER_SECOND`, 0 = unlimited).
+    pub rate_limit_per_second: f64,
+    /// Requests a client IP may send at once before being limited (`RATE_LIMIT_BURST`,
+    /// defaults to one second's worth, at least 1).
+    pub rate_limit_burst: f64,
+    /// Do not count or log requests that send `DNT: 1` or `Sec-GPC: 1` (`RESPECT_DNT`).
+    pub respect_dnt: bool,
+    /// Only count requests that look like a browser loading an image (`BROWSER_ONLY`).
+    pub browser_only: bool,
+    /// Keys that only count browser image loads even when `BROWSER_ONLY` is off (`BROWSER_ONLY_KEYS`, comma-separated).
+    pub browser_only_keys: HashSet<String>,
+    /// Lifetime of the cookie set by `?mode=session` (`SESSION_COOKIE_SECONDS`, default 1800).
+    pub session_cookie_ttl: Duration,
+    /// Keys that only write every `N`th hit, with an increment of `N` (`KEY_SAMPLE_RATES`,
+    /// comma-separated `key=N`).
+    pub key_sample_rates: HashMap<String, u32>,
+    /// Bound of the noise added to public totals (`COUNT_NOISE`, default 0 = exact).
+    pub count_noise: u32,
+    /// Per-key noise bounds overriding `COUNT_NOISE` (`KEY_COUNT_NOISE`, comma-separated `key=bound`).
+    pub key_count_noise: HashMap<String, u32>,
+    /// Feed counted hits into per-key HyperLogLog sketches for `/stats/{key}?metric=uniques` (`TRACK_UNIQUES`).
+    pub track_uniques: bool,
+    /// Keys that only count hits whose `Referer` starts with one of their prefixes
+    /// (`KEY_REFERRER_ALLOWLIST`, comma-separated `key=host/path`, repeat a key for more prefixes).
+    pub key_referrer_allowlist: HashMap<String, Vec<String>>,
+    /// IANA time zones whose midnight starts a key's days in `/stats` and daily quotas
+    /// (`KEY_TIMEZONES`, comma-separated `key=Area/City`). Overrides `WINDOW_OFFSET` for the key.
+    pub key_timezones: HashMap<String, String>,
+    /// Width of the time bucket each hit is added to (`BUCKET_GRANULARITY=second|minute|hour|day`, default minute).
+    pub granularity: Granularity,
+    /// Per-key bucket widths overriding `BUCKET_GRANULARITY` (`KEY_BUCKET_GRANULARITY`, comma-separated `key=hour`).
+    pub key_granularities: HashMap<String, Granularity>,
+    /// Throttle keys whose last minute exceeds this multiple of their hourly average (`SPIKE_FACTOR`, unset = off).
+    pub spike_factor: Option<f64>,
+    /// Minimum hits in a minute before a key can be flagged as spiking (`SPIKE_MIN_HITS`, default 600).
+    pub spike_min_hits: i64,
+    /// How long a spiking key stays throttled (`SPIKE_THROTTLE_MINUTES`, default 60).
+    pub spike_throttle: Duration,
+    /// Record one in this many counted hits in `hit_events` (`HIT_EVENTS_SAMPLE_RATE`, unset = off).
+    pub hit_events_sample_rate: Option<u32>,
+    /// How long rows in `hit_events` are kept (`HIT_EVENTS_RETENTION_DAYS`, default 30).
+    pub hit_events_retention: Duration,
+}
+
+/// `KEY_SAMPLE_RATES` 允许的最大抽样率
+pub const MAX_SAMPLE_RATE: u32 = 1_000_000;
+
+/// 默认视为爬虫的 `User-Agent` 模式
+pub const DEFAULT_BOT_USER_AGENTS: &[&str] = &[
+    r"bot\b",
+    "crawler",
+    "spider",
+    "slurp",
+    "facebookexternalhit",
+    "headlesschrome",
+    "lighthouse",
+];
+
+impl AppConfig {
+    /// 按 `KEY_STRIP_TRAILING_SLASH` 与 `KEY_LOWERCASE` 规范化请求中的 key
+    pub fn normalize_key(&self, key: &str) -> String {
+        let key = if self.key_strip_trailing_slash {
+            key.trim_end_matches('/')
+        } else {
+            key
+        };
+        if self.key_lowercase {
+            key.to_lowercase()
+        } else {
+            key.to_string()
+        }
+    }
+
+    /// 数据库中实际存储的 key：开启 `HASH_KEYS` 时为 HMAC 摘要，否则原样返回
+    pub fn stored_key(&self, key: &str) -> String {
+        match &self.key_hash_secret {
+            Some(secret) => {
+                let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+                    .expect("HMAC accepts any key length");
+                mac.update(key.as_bytes());
+                hex::encode(mac.finalize().into_bytes())
+            }
+            None => key.to_string(),
+        }
+    }
+
+    /// `KEY_BASE_COUNTS` 为 key 配置的起始偏移，未配置时为 0
+    pub fn base_count(&self, key: &str) -> i64 {
+        self.key_base_counts.get(key).copied().unwrap_or(0)
+    }
+
+    /// key 的去重窗口，`KEY_DEDUP_SECONDS` 优先于 `HITS_DEDUP_SECONDS`
+    pub fn dedup_window(&self, key: &str) -> Duration {
+        self.key_dedup_windows
+            .get(key)
+            .copied()
+            .unwrap_or(self.dedup_window)
+    }
+
+    /// 公开总数的噪声上限，`KEY_COUNT_NOISE` 优先于 `COUNT_NOISE`
+    pub fn noise_bound(&self, key: &str) -> u32 {
+        self.key_count_noise
+            .get(key)
+            .copied()
+            .unwrap_or(self.count_noise)
+    }
+
+    /// key 的自然日边界：`KEY_TIMEZONES` 中的时区，否则为 UTC 加 `WINDOW_OFFSET`
+    pub fn day_boundary(&self, key: &str) -> (&str, i32) {
+        match self.key_timezones.get(key) {
+            Some(zone) => (zone, 0),
+            None => ("UTC", self.window_offset_minutes),
+        }
+    }
+
+    /// key 计数行的时间粒度，`KEY_BUCKET_GRANULARITY` 优先于 `BUCKET_GRANULARITY`
+    pub fn granularity(&self, key: &str) -> Granularity {
+        self.key_granularities
+            .get(key)
+            .copied()
+            .unwrap_or(self.granularity)
+    }
+
+    /// key 是否只计数浏览器加载图片的请求
+    pub fn browser_only(&self, key: &str) -> bool {
+        self.browser_only || self.browser_only_keys.contains(key)
+    }
+
+    /// `KEY_SAMPLE_RATES` 为 key 配置的抽样率，未配置时为 1（每次都写入）
+    pub fn sample_rate(&self, key: &str) -> u32 {
+        self.key_sample_rates.get(key).copied().unwrap_or(1)
+    }
+
+    /// 是否启用 `/metrics/keys` 快照
+    pub fn metrics_keys_enabled(&self) -> bool {
+        self.metrics_keys_min_total.is_some() || !self.metrics_keys_allowlist.is_empty()
+    }
+
+    /// 从环境变量读取配置
+    pub fn from_env() -> Result<Self> {
+        let admin_token = env::var("ADMIN_TOKEN").ok().filter(|t| !t.is_empty());
+        let amount_token = env::var("AMOUNT_TOKEN").ok().filter(|t| !t.is_empty());
+        let amount_max = env_parse("AMOUNT_MAX", 1000)?.max(1);
+        let json_casing = match env::var("JSON_CASING").ok().as_deref() {
+            None | Some("") => None,
+            Some("camel") => Some(JsonCasing::Camel),
+            Some("snake") => Some(JsonCasing::Snake),
+            Some(other) => bail!(
+                "Invalid JSON_CASING value: {} (expected camel or snake)",
+                other
+            ),
+        };
+        let redirect_allowed_hosts = env_list("REDIRECT_ALLOWED_HOSTS")
+            .into_iter()
+            .map(|host| host.to_ascii_lowercase())
+            .collect();
+        let public_url = match env::var("PUBLIC_URL").ok().filter(|v| !v.is_empty()) {
+            Some(value) => {
+                let url = url::Url::parse(&value)
+                    .with_context(|| format!("Invalid PUBLIC_URL value: {}", value))?;
+                if !matches!(url.scheme(), "http" | "https") || url.cannot_be_a_base() {
+                    bail!("PUBLIC_URL must be an http or https URL: {}", value);
+                }
+                Some(url)
+            }
+            None => None,
+        };
+        let forwarded_header = match env::var("FORWARDED_HEADER")
+            .ok()
+            .map(|value| value.to_ascii_lowercase())
+            .as_deref()
+        {
+            None | Some("") | Some("x-forwarded-for") => ForwardedHeader::XForwardedFor,
+            Some("forwarded") => ForwardedHeader::Forwarded,
+            Some(other) => bail!(
+                "Invalid FORWARDED_HEADER value: {} (expected x-forwarded-for or forwarded)",
+                other
+            ),
+        };
+        let trusted_proxies = env_list("TRUSTED_PROXIES")
+            .iter()
+            .map(|entry| parse_ip_net(entry))
+            .collect::<Result<_>>()?;
+        let allowed_origins = env_list("ALLOWED_ORIGINS");
+        let allowed_origins =
+            if allowed_origins.is_empty() || allowed_origins.iter().any(|o| o == "*") {
+                None
+            } else {
+                Some(
+                    allowed_origins
+                        .into_iter()
+                        .map(|origin| origin.trim_end_matches('/').to_ascii_lowercase())
+                        .collect(),
+                )
+            };
+        let display_cap = match env::var("DISPLAY_CAP").ok().filter(|v| !v.is_empty()) {
+            Some(value) => {
+                let cap: i64 = value
+                    .parse()
+                    .with_context(|| format!("Invalid DISPLAY_CAP value: {}", value))?;
+                if cap < 0 {
+                    bail!("DISPLAY_CAP must not be negative: {}", cap);
+                }
+                Some(cap)
+            }
+            None => None,
+        };
+        let display_cap_suffix = env::var("DISPLAY_CAP_SUFFIX").unwrap_or_else(|_| "+".to_string());
+        let window_offset_minutes = match env::var("WINDOW_OFFSET").ok().filter(|v| !v.is_empty()) {
+            Some(value) => {
+                let offset: i32 = value
+                    .parse()
+                    .with_context(|| format!("Invalid WINDOW_OFFSET value: {}", value))?;
+                if !(-1439..=1439).contains(&offset) {
+                    bail!(
+                        "WINDOW_OFFSET must be between -1439 and 1439 minutes: {}",
+                        offset
+                    );
+                }
+                offset
+            }
+            None => 0,
+        };
+        let challenge_keys = env_list("CHALLENGE_KEYS").into_iter().collect();
+        let protected_keys: HashSet<String> = env_list("PROTECTED_KEYS").into_iter().collect();
+        let captcha_provider = match env::var("CAPTCHA_PROVIDER")
+            .ok()
+            .map(|value| value.to_ascii_lowercase())
+            .as_deref()
+        {
+            None | Some("") => None,
+            Some("turnstile") => Some(CaptchaProvider::Turnstile),
+            Some("hcaptcha") => Some(CaptchaProvider::Hcaptcha),
+            Some(other) => bail!(
+                "Invalid CAPTCHA_PROVIDER value: {} (expected turnstile or hcaptcha)",
+                other
+            ),
+        };
+        let captcha_secret = env::var("CAPTCHA_SECRET").ok().filter(|s| !s.is_empty());
+        if !protected_keys.is_empty() && (captcha_provider.is_none() || captcha_secret.is_none()) {
+            bail!("PROTECTED_KEYS requires CAPTCHA_PROVIDER and CAPTCHA_SECRET to be set");
+        }
+        let challenge_secret = env::var("CHALLENGE_SECRET").ok().filter(|s| !s.is_empty());
+        let challenge_ttl = Duration::from_secs(env_parse("CHALLENGE_TTL_SECONDS", 60)?);
+        let metrics_keys_min_total = env::var("METRICS_KEYS_MIN_TOTAL")
+            .ok()
+            .filter(|v| !v.is_empty())
+            .map(|value| {
+                value
+                    .parse::<i64>()
+                    .with_context(|| format!("Invalid METRICS_KEYS_MIN_TOTAL value: {}", value))
+            })
+            .transpose()?;
+        let metrics_keys_allowlist = env_list("METRICS_KEYS_ALLOWLIST");
+        let metrics_keys_interval =
+            Duration::from_secs(env_parse("METRICS_KEYS_INTERVAL_SECONDS", 60)?.max(1));
+        let key_lowercase = env_parse("KEY_LOWERCASE", false)?;
+        let key_strip_trailing_slash = env_parse("KEY_STRIP_TRAILING_SLASH", false)?;
+        let broadcast_coalesce = Duration::from_millis(env_parse("BROADCAST_COALESCE_MS", 0)?);
+        let count_require_headers = env_list("COUNT_REQUIRE_HEADERS")
+            .iter()
+            .map(|entry| HeaderRule::parse(entry))
+            .collect::<Result<_>>()?;
+        let count_reject_headers = env_list("COUNT_REJECT_HEADERS")
+            .iter()
+            .map(|entry| HeaderRule::parse(entry))
+            .collect::<Result<_>>()?;
+        let geoip_database = env::var("GEOIP_DATABASE")
+            .ok()
+            .filter(|v| !v.is_empty())
+            .map(PathBuf::from);
+        let webhook_secret = env::var("WEBHOOK_SECRET").ok().filter(|s| !s.is_empty());
+        let webhook_events = match env_list("WEBHOOK_EVENTS") {
+            entries if entries.is_empty() => {
+                HashMap::from([("push".to_string(), 1), ("star.created".to_string(), 1)])
+            }
+            entries => entries
+                .iter()
+                .map(|entry| parse_webhook_event(entry))
+                .collect::<Result<_>>()?,
+        };
+        let key_hash_secret = if env_parse("HASH_KEYS", false)? {
+            match env::var("KEY_HASH_SECRET").ok().filter(|s| !s.is_empty()) {
+                Some(secret) => Some(secret),
+                None => bail!("HASH_KEYS=true requires KEY_HASH_SECRET to be set"),
+            }
+        } else {
+            None
+        };
+        let key_daily_quotas = env_list("KEY_DAILY_QUOTAS")
+            .iter()
+            .map(|entry| parse_key_amount("KEY_DAILY_QUOTAS", entry))
+            .collect::<Result<_>>()?;
+        let key_base_counts = env_list("KEY_BASE_COUNTS")
+            .iter()
+            .map(|entry| parse_key_amount("KEY_BASE_COUNTS", entry))
+            .collect::<Result<_>>()?;
+        let badge_nonce = env_parse("BADGE_NONCE", false)?;
+        let public_min_total = env_parse("PUBLIC_MIN_TOTAL", 0)?;
+        let grand_total_reconcile_interval =
+            Duration::from_secs(env_parse("GRAND_TOTAL_RECONCILE_SECONDS", 60)?.max(1));
+        let blocked_key_patterns = env_list("BLOCKED_KEY_PATTERNS");
+        for pattern in &blocked_key_patterns {
+            Regex::new(pattern)
+                .with_context(|| format!("Invalid regex in BLOCKED_KEY_PATTERNS: {}", pattern))?;
+        }
+        let blocked_key_patterns = RegexSet::new(&blocked_key_patterns)
+            .context("Failed to compile BLOCKED_KEY_PATTERNS")?;
+        let svg_xml_declaration = env_parse("SVG_XML_DECLARATION", false)?;
+        let svg_xlink = env_parse("SVG_XLINK", false)?;
+        let ws_max_connections = env_parse("WS_MAX_CONNECTIONS", 0)?;
+        let ws_max_connections_per_ip = env_parse("WS_MAX_CONNECTIONS_PER_IP", 0)?;
+        let ws_batch_interval = Duration::from_millis(env_parse("WS_BATCH_MS", 0)?);
+        let ws_batch_max = env_parse("WS_BATCH_MAX", 100)?.max(1);
+        let slow_query_threshold = env::var("SLOW_QUERY_MS")
+            .ok()
+            .filter(|v| !v.is_empty())
+            .map(|value| {
+                value
+                    .parse::<u64>()
+                    .map(Duration::from_millis)
+                    .with_context(|| format!("Invalid SLOW_QUERY_MS value: {}", value))
+            })
+            .transpose()?;
+        let coalesce_writes = env_parse("COALESCE_WRITES", false)?;
+        let visitor_salt = env::var("VISITOR_SALT").ok().filter(|s| !s.is_empty());
+        let bot_user_agents = if env_parse("COUNT_BOTS", false)? {
+            RegexSet::empty()
+        } else {
+            let patterns = match env_list("BOT_USER_AGENTS") {
+                patterns if patterns.is_empty() => DEFAULT_BOT_USER_AGENTS
+                    .iter()
+                    .map(|pattern| pattern.to_string())
+                    .collect(),
+                patterns => patterns,
+            };
+            RegexSetBuilder::new(&patterns)
+                .case_insensitive(true)
+                .build()
+                .context("Failed to compile BOT_USER_AGENTS")?
+        };
+        let bot_ip_lists = env_list("BOT_IP_LISTS");
+        let bot_ip_action = match env::var("BOT_IP_ACTION")
+            .ok()
+            .map(|value| value.to_ascii_lowercase())
+            .as_deref()
+        {
+            None | Some("") | Some("exclude") => BotIpAction::Exclude,
+            Some("tally") => BotIpAction::Tally,
+            Some(other) => bail!(
+                "Invalid BOT_IP_ACTION value: {} (expected exclude or tally)",
+                other
+            ),
+        };
+        let bot_ip_refresh =
+            Duration::from_secs(env_parse("BOT_IP_REFRESH_SECONDS", 86400)?.max(60));
+        let rate_limit_per_second: f64 = env_parse("RATE_LIMIT_PER_SECOND", 0.0)?;
+        if !(rate_limit_per_second.is_finite() && rate_limit_per_second >= 0.0) {
+            bail!(
+                "RATE_LIMIT_PER_SECOND must be a non-negative number: {}",
+                rate_limit_per_second
+            );
+        }
+        let rate_limit_burst = env_parse("RATE_LIMIT_BURST", rate_limit_per_second)?.max(1.0);
+        let respect_dnt = env_parse("RESPECT_DNT", false)?;
+        let browser_only = env_parse("BROWSER_ONLY", false)?;
+        let browser_only_keys = env_list("BROWSER_ONLY_KEYS").into_iter().collect();
+        let session_cookie_ttl =
+            Duration::from_secs(env_parse("SESSION_COOKIE_SECONDS", 1800)?.max(1));
+        let count_noise = env_parse("COUNT_NOISE", 0)?;
+        let key_count_noise = env_list("KEY_COUNT_NOISE")
+            .iter()
+            .map(|entry| {
+                let (key, bound) = parse_key_amount("KEY_COUNT_NOISE", entry)?;
+                let bound = u32::try_from(bound).with_context(|| {
+                    format!(
+                        "Invalid KEY_COUNT_NOISE bound (must be at least 0): {}",
+                        entry
+                    )
+                })?;
+                Ok((key, bound))
+            })
+            .collect::<Result<_>>()?;
+        let key_sample_rates = env_list("KEY_SAMPLE_RATES")
+            .iter()
+            .map(|entry| parse_sample_rate(entry, amount_max))
+            .collect::<Result<_>>()?;
+        let track_uniques = env_parse("TRACK_UNIQUES", false)?;
+        let spike_factor = env::var("SPIKE_FACTOR")
+            .ok()
+            .filter(|v| !v.is_empty())
+            .map(|value| {
+                value
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|factor| factor.is_finite() && *factor > 1.0)
+                    .with_context(|| {
+                        format!("Invalid SPIKE_FACTOR value (must be above 1): {}", value)
+                    })
+            })
+            .transpose()?;
+        let spike_min_hits = env_parse("SPIKE_MIN_HITS", 600)?.max(1);
+        let spike_throttle =
+            Duration::from_secs(env_parse("SPIKE_THROTTLE_MINUTES", 60)?.max(1) * 60);
+        let hit_events_sample_rate = env::var("HIT_EVENTS_SAMPLE_RATE")
+            .ok()
+            .filter(|v| !v.is_empty())
+            .map(|value| {
+                value
+                    .parse::<u32>()
+                    .ok()
+                    .filter(|rate| *rate >= 1)
+                    .with_context(|| {
+                        format!(
+                            "Invalid HIT_EVENTS_SAMPLE_RATE value (must be at least 1): {}",
+                            value
+                        )
+                    })
+            })
+            .transpose()?;
+        let hit_events_retention =
+            Duration::from_secs(env_parse("HIT_EVENTS_RETENTION_DAYS", 30)?.max(1) * 86400);
+        let mut key_timezones = HashMap::new();
+        for entry in env_list("KEY_TIMEZONES") {
+            match entry.split_once('=') {
+                Some((key, zone)) if !key.trim().is_empty() && !zone.trim().is_empty() => {
+                    key_timezones.insert(key.trim().to_string(), zone.trim().to_string());
+                }
+                _ => bail!(
+                    "Invalid KEY_TIMEZONES entry (expected key=Area/City): {}",
+                    entry
+                ),
+            }
+        }
+        let granularity = match env::var("BUCKET_GRANULARITY")
+            .ok()
+            .filter(|v| !v.is_empty())
+        {
+            Some(value) => Granularity::parse(&value).with_context(|| {
+                format!(
+                    "Invalid BUCKET_GRANULARITY value: {} (expected second, minute, hour or day)",
+                    value
+                )
+            })?,
+            None => Granularity::default(),
+        };
+        let key_granularities = env_list("KEY_BUCKET_GRANULARITY")
+            .iter()
+            .map(|entry| {
+                entry
+                    .split_once('=')
+                    .and_then(|(key, value)| {
+                        Some((key.trim().to_string(), Granularity::parse(value)?))
+                    })
+                    .filter(|(key, _)| !key.is_empty())
+                    .with_context(|| {
+                        format!(
+                            "Invalid KEY_BUCKET_GRANULARITY entry (expected key=second|minute|hour|day): {}",
+                            entry
+                        )
+                    })
+            })
+            .collect::<Result<_>>()?;
+        let mut key_referrer_allowlist: HashMap<String, Vec<String>> = HashMap::new();
+        for entry in env_list("KEY_REFERRER_ALLOWLIST") {
+            let Some((key, prefix)) = entry.split_once('=') else {
+                bail!(
+                    "Invalid KEY_REFERRER_ALLOWLIST entry (expected key=host/path): {}",
+                    entry
+                );
+            };
+            key_referrer_allowlist
+                .entry(key.trim().to_string())
+                .or_default()
+                .push(strip_scheme(prefix.trim()).to_ascii_lowercase());
+        }
+        let dedup_window = Duration::from_secs(env_parse("HITS_DEDUP_SECONDS", 0)?);
+        let key_dedup_windows = env_list("KEY_DEDUP_SECONDS")
+            .iter()
+            .map(|entry| {
+                parse_key_amount("KEY_DEDUP_SECONDS", entry)
+                    .map(|(key, seconds)| (key, Duration::from_secs(seconds as u64)))
+            })
+            .collect::<Result<_>>()?;
+        let mut config = Self {
+            admin_token,
+            amount_token,
+            amount_max,
+            json_casing,
+            redirect_allowed_hosts,
+            public_url,
+            trusted_proxies,
+            forwarded_header,
+            allowed_origins,
+            display_cap,
+            display_cap_suffix,
+            window_offset_minutes,
+            challenge_keys,
+            protected_keys,
+            captcha_provider,
+            captcha_secret,
+            challenge_secret,
+            challenge_ttl,
+            metrics_keys_min_total,
+            metrics_keys_allowlist,
+            metrics_keys_interval,
+            key_lowercase,
+            key_strip_trailing_slash,
+            broadcast_coalesce,
+            count_require_headers,
+            count_reject_headers,
+            geoip_database,
+            webhook_secret,
+            webhook_events,
+            key_hash_secret,
+            key_daily_quotas,
+            key_base_counts,
+            badge_nonce,
+            public_min_total,
+            grand_total_reconcile_interval,
+            blocked_key_patterns,
+            svg_xml_declaration,
+            svg_xlink,
+            ws_max_connections,
+            ws_max_connections_per_ip,
+            ws_batch_interval,
+            ws_batch_max,
+            slow_query_threshold,
+            coalesce_writes,
+            visitor_salt,
+            dedup_window,
+            key_dedup_windows,
+            bot_user_agents,
+            bot_ip_lists,
+            bot_ip_action,
+            bot_ip_refresh,
+            rate_limit_per_second,
+            rate_limit_burst,
+            respect_dnt,
+            browser_only,
+            browser_only_keys,
+            session_cookie_ttl,
+            key_sample_rates,
+            count_noise,
+            key_count_noise,
+            track_uniques,
+            key_referrer_allowlist,
+            key_timezones,
+            granularity,
+            key_granularities,
+            spike_factor,
+            spike_min_hits,
+            spike_throttle,
+            hit_events_sample_rate,
+            hit_events_retention,
+        };
+        config.normalize_configured_keys()?;
+        Ok(config)
+    }
+
+    /// 把按 key 配置的列表与映射规范化，使其与 [`AppConfig::normalize_key`] 后的请求 key 一致
+    ///
+    /// Entries that only differ in case or trailing slashes would silently override each other,
+    /// so they are rejected, except in `KEY_REFERRER_ALLOWLIST` where their prefixes are merged.
+    fn normalize_configured_keys(&mut self) -> Result<()> {
+        if !self.key_lowercase && !self.key_strip_trailing_slash {
+            return Ok(());
+        }
+        self.challenge_keys = self.normalize_set(&self.challenge_keys);
+        self.protected_keys = self.normalize_set(&self.protected_keys);
+        self.browser_only_keys = self.normalize_set(&self.browser_only_keys);
+        self.metrics_keys_allowlist = self
+            .metrics_keys_allowlist
+            .iter()
+            .map(|key| self.normalize_key(key))
+            .collect();
+        self.key_daily_quotas = self.normalize_map("KEY_DAILY_QUOTAS", &self.key_daily_quotas)?;
+        self.key_base_counts = self.normalize_map("KEY_BASE_COUNTS", &self.key_base_counts)?;
+        self.key_dedup_windows =
+            self.normalize_map("KEY_DEDUP_SECONDS", &self.key_dedup_windows)?;
+        self.key_sample_rates = self.normalize_map("KEY_SAMPLE_RATES", &self.key_sample_rates)?;
+        self.key_count_noise = self.normalize_map("KEY_COUNT_NOISE", &self.key_count_noise)?;
+        self.key_timezones = self.normalize_map("KEY_TIMEZONES", &self.key_timezones)?;
+        self.key_granularities =
+            self.normalize_map("KEY_BUCKET_GRANULARITY", &self.key_granularities)?;
+        let mut allowlist: HashMap<String, Vec<String>> = HashMap::new();
+        for (key, prefixes) in &self.key_referrer_allowlist {
+            allowlist
+                .entry(self.normalize_key(key))
+                .or_default()
+                .extend(prefixes.iter().cloned());
+        }
+        self.key_referrer_allowlist = allowlist;
+        Ok(())
+    }
+
+    /// 规范化 key 集合
+    fn normalize_set(&self, keys: &HashSet<String>) -> HashSet<String> {
+        keys.iter().map(|key| self.normalize_key(key)).collect()
+    }
+
+    /// 规范化映射的 key，两个条目规范化后相同时报错
+    fn normalize_map<V: Clone>(
+        &self,
+        name: &str,
+        map: &HashMap<String, V>,
+    ) -> Result<HashMap<String, V>> {
+        let mut normalized = HashMap::with_capacity(map.len());
+        for (key, value) in map {
+            let normal = self.normalize_key(key);
+            if normalized.insert(normal.clone(), value.clone()).is_some() {
+                bail!(
+                    "{} has several entries for the key {} (keys are normalized by KEY_LOWERCASE and KEY_STRIP_TRAILING_SLASH)",
+                    name,
+                    normal
+                );
+            }
+        }
+        Ok(normalized)
+    }
+}
+
+/// 去掉 URL 的 `scheme://` 前缀
+pub fn strip_scheme(url: &str) -> &str {
+    url.split_once("://").map_or(url, |(_, rest)| rest)
+}
+
+/// 读取并解析环境变量，未设置时使用默认值
+fn env_parse<T>(name: &str, default: T) -> Result<T>
+where
+    T: std::str::FromStr,
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    match env::var(name).ok().filter(|v| !v.is_empty()) {
+        Some(value) => value
+            .parse()
+            .with_context(|| format!("Invalid {} value: {}", name, value)),
+        None => Ok(default),
+    }
+}
+
+/// 读取逗号分隔的环境变量列表，忽略空项
+fn env_list(name: &str) -> Vec<String> {
+    env::var(name)
+        .map(|value| {
+            value
+                .split(',')
+                .map(|item| item.trim().to_string())
+                .filter(|item| !item.is_empty())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// 解析 `event[.action]=amount`，省略数量时为 1
+fn parse_webhook_event(entry: &str) -> Result<(String, i32)> {
+    let (event, amount) = match entry.split_once('=') {
+        Some((event, amount)) => (
+            event.trim(),
+            amount
+                .trim()
+                .parse::<i32>()
+                .with_context(|| format!("Invalid amount in WEBHOOK_EVENTS: {}", entry))?,
+        ),
+        None => (entry.trim(), 1),
+    };
+    if amount < 0 {
+        bail!("WEBHOOK_EVENTS amounts must not be negative: {}", entry);
+    }
+    Ok((event.to_ascii_lowercase(), amount))
+}
+
+/// 解析 `key=amount`
+fn parse_key_amount(name: &str, entry: &str) -> Result<(String, i64)> {
+    let Some((key, amount)) = entry.rsplit_once('=') else {
+        bail!("Invalid {} entry (expected key=amount): {}", name, entry);
+    };
+    let amount: i64 = amount
+        .trim()
+        .parse()
+        .with_context(|| format!("Invalid amount in {}: {}", name, entry))?;
+    if amount < 0 {
+        bail!("{} amounts must not be negative: {}", name, entry);
+    }
+    Ok((key.trim().to_string(), amount))
+}
+
+/// 解析 `KEY_SAMPLE_RATES` 的 `key=N`
+///
+/// A written hit adds `N` times its amount, so the rate is capped at [`MAX_SAMPLE_RATE`]
+/// and `N * AMOUNT_MAX` must still fit the `INTEGER` increment.
+fn parse_sample_rate(entry: &str, amount_max: i32) -> Result<(String, u32)> {
+    let (key, rate) = parse_key_amount("KEY_SAMPLE_RATES", entry)?;
+    let rate = match u32::try_from(rate) {
+        Ok(rate) if (1..=MAX_SAMPLE_RATE).contains(&rate) => rate,
+        _ => bail!(
+            "KEY_SAMPLE_RATES rates must be between 1 and {}: {}",
+            MAX_SAMPLE_RATE,
+            entry
+        ),
+    };
+    if (rate as i32).checked_mul(amount_max).is_none() {
+        bail!(
+            "KEY_SAMPLE_RATES rate times AMOUNT_MAX ({}) exceeds {}: {}",
+            amount_max,
+            i32::MAX,
+            entry
+        );
+    }
+    Ok((key, rate))
+}
+
+/// 解析 IP 或 CIDR，单个 IP 视为主机网段
+fn parse_ip_net(entry: &str) -> Result<IpNet> {
+    if let Ok(ip) = entry.parse::<IpAddr>() {
+        return Ok(IpNet::from(ip));
+    }
+    entry
+        .parse::<IpNet>()
+        .with_context(|| format!("Invalid IP or CIDR in TRUSTED_PROXIES: {}", entry))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn configured_keys_follow_key_normalization() {
+        let mut config = AppConfig {
+            key_lowercase: true,
+            key_strip_trailing_slash: true,
+            key_sample_rates: HashMap::from([("Viral/".to_string(), 10)]),
+            key_count_noise: HashMap::from([("Secret".to_string(), 5)]),
+            key_granularities: HashMap::from([("Busy//".to_string(), Granularity::Hour)]),
+            key_base_counts: HashMap::from([("Moved".to_string(), 100)]),
+            protected_keys: HashSet::from(["Guarded/".to_string()]),
+            key_referrer_allowlist: HashMap::from([
+                ("Docs".to_string(), vec!["a.example/".to_string()]),
+                ("docs/".to_string(), vec!["b.example/".to_string()]),
+            ]),
+            ..AppConfig::default()
+        };
+        config.normalize_configured_keys().unwrap();
+        let key = config.normalize_key("VIRAL/");
+        assert_eq!(key, "viral");
+        assert_eq!(config.sample_rate(&key), 10);
+        assert_eq!(config.noise_bound("secret"), 5);
+        assert_eq!(config.granularity("busy"), Granularity::Hour);
+        assert_eq!(config.base_count("moved"), 100);
+        assert!(config.protected_keys.contains("guarded"));
+        let mut prefixes = config.key_referrer_allowlist["docs"].clone();
+        prefixes.sort();
+        assert_eq!(prefixes, ["a.example/", "b.example/"]);
+    }
+
+    #[test]
+    fn configured_keys_that_collide_are_rejected() {
+        let mut config = AppConfig {
+            key_lowercase: true,
+            key_base_counts: HashMap::from([("Key".to_string(), 1), ("key".to_string(), 2)]),
+            ..AppConfig::default()
+        };
+        assert!(config.normalize_configured_keys().is_err());
+        config.key_lowercase = false;
+        assert!(config.normalize_configured_keys().is_ok());
+    }
+
+    #[test]
+    fn sample_rates_are_bounded() {
+        assert_eq!(
+            parse_sample_rate("viral=10", 1000).unwrap(),
+            ("viral".to_string(), 10)
+        );
+        assert_eq!(
+            parse_sample_rate("viral=1000000", 1000).unwrap().1,
+            MAX_SAMPLE_RATE
+        );
+        assert!(parse_sample_rate("viral=0", 1000).is_err());
+        assert!(parse_sample_rate("viral=1000001", 1).is_err());
+        assert!(parse_sample_rate("viral=2147483647", 1).is_err());
+        // 1_000_000 * 2_148 > i32::MAX
+        assert!(parse_sample_rate("viral=1000000", 2_147).is_ok());
+        assert!(parse_sample_rate("viral=1000000", 2_148).is_err());
+    }
+}
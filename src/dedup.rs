@@ -0,0 +1,48 @@
+//! 重复访问去重
+//!
+//! With `HITS_DEDUP_SECONDS` (or a per-key `KEY_DEDUP_SECONDS` entry), a client's first
+//! counted hit on a key opens a window of that length during which further hits from the
+//! same client IP are not counted. Windows are fixed, not extended by the ignored repeats.
+//! The cache lives in process memory, so every instance deduplicates on its own. Clients
+//! are held as [`IpHasher`](crate::privacy::IpHasher) hashes rather than addresses; a
+//! window that spans the daily salt rotation ends early.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// 清理过期条目的最短间隔
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// 每个 (key, 客户端) 的窗口结束时间
+#[derive(Default)]
+pub struct DedupCache {
+    seen: Mutex<Seen>,
+}
+
+#[derive(Default)]
+struct Seen {
+    until: HashMap<(String, u64), Instant>,
+    pruned_at: Option<Instant>,
+}
+
+impl DedupCache {
+    /// 窗口外的访问返回 true 并开启新窗口，窗口内的重复访问返回 false
+    pub fn admit(&self, key: &str, visitor: u64, window: Duration) -> bool {
+        let now = Instant::now();
+        let mut seen = self.seen.lock().unwrap();
+        if seen
+            .pruned_at
+            .is_none_or(|pruned_at| now.duration_since(pruned_at) >= PRUNE_INTERVAL)
+        {
+            seen.until.retain(|_, until| *until > now);
+            seen.pruned_at = Some(now);
+        }
+        let entry = seen.until.entry((key.to_string(), visitor)).or_insert(now);
+        if *entry > now {
+            return false;
+        }
+        *entry = now + window;
+        true
+    }
+}
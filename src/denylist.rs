@@ -0,0 +1,130 @@
+//! 管理员维护的屏蔽名单
+//!
+//! Entries are added and removed through `/admin/denylist` and stored in the `denylist`
+//! table, either as a stored key or as an IP network. Like the per-key rate limits, every
+//! instance keeps a copy in memory that its own admin requests update immediately and that
+//! is reloaded from the table every [`RELOAD_INTERVAL`]. Hits on blocked keys are refused,
+//! hits from blocked networks are answered but not counted.
+
+use crate::api::types::DenyKind;
+use crate::error::AppError;
+use ipnet::IpNet;
+use sqlx::postgres::PgPool;
+use std::collections::HashSet;
+use std::net::IpAddr;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tracing::{debug, warn};
+
+/// 从数据库重新加载名单的间隔
+pub const RELOAD_INTERVAL: Duration = Duration::from_secs(30);
+
+/// 内存中的屏蔽名单
+#[derive(Default)]
+pub struct Denylist {
+    entries: RwLock<Entries>,
+}
+
+#[derive(Default)]
+struct Entries {
+    keys: HashSet<String>,
+    nets: Vec<IpNet>,
+}
+
+impl Entries {
+    fn insert(&mut self, kind: DenyKind, value: &str) {
+        match kind {
+            DenyKind::Key => {
+                self.keys.insert(value.to_string());
+            }
+            DenyKind::Ip => {
+                if let Ok(net) = value.parse::<IpNet>() {
+                    if !self.nets.contains(&net) {
+                        self.nets.push(net);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Denylist {
+    pub fn blocks_key(&self, stored_key: &str) -> bool {
+        self.entries.read().unwrap().keys.contains(stored_key)
+    }
+
+    pub fn blocks_ip(&self, ip: IpAddr) -> bool {
+        let entries = self.entries.read().unwrap();
+        entries.nets.iter().any(|net| net.contains(&ip))
+    }
+
+    /// 数据库操作：列出所有条目
+    pub async fn list(pool: &PgPool) -> Result<Vec<(DenyKind, String)>, AppError> {
+        let rows = sqlx::query!("SELECT kind, value FROM denylist ORDER BY kind, created_at")
+            .fetch_all(pool)
+            .await?;
+        Ok(rows
+            .into_iter()
+            .filter_map(|row| Some((DenyKind::parse(&row.kind)?, row.value)))
+            .collect())
+    }
+
+    /// 数据库操作：添加条目，返回是否为新条目
+    pub async fn add(&self, pool: &PgPool, kind: DenyKind, value: &str) -> Result<bool, AppError> {
+        let result = sqlx::query!(
+            "INSERT INTO denylist (kind, value) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+            kind.as_str(),
+            value
+        )
+        .execute(pool)
+        .await?;
+        self.entries.write().unwrap().insert(kind, value);
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// 数据库操作：删除条目，返回之前是否存在
+    pub async fn remove(
+        &self,
+        pool: &PgPool,
+        kind: DenyKind,
+        value: &str,
+    ) -> Result<bool, AppError> {
+        let result = sqlx::query!(
+            "DELETE FROM denylist WHERE kind = $1 AND value = $2",
+            kind.as_str(),
+            value
+        )
+        .execute(pool)
+        .await?;
+        let mut entries = self.entries.write().unwrap();
+        match kind {
+            DenyKind::Key => {
+                entries.keys.remove(value);
+            }
+            DenyKind::Ip => entries.nets.retain(|net| net.to_string() != value),
+        }
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn reload(&self, pool: &PgPool) -> Result<usize, AppError> {
+        let rows = Self::list(pool).await?;
+        let mut entries = Entries::default();
+        for (kind, value) in &rows {
+            entries.insert(*kind, value);
+        }
+        *self.entries.write().unwrap() = entries;
+        Ok(rows.len())
+    }
+}
+
+/// 定期重新加载名单的后台任务，查询失败时保留当前值
+pub async fn run(pool: PgPool, denylist: Arc<Denylist>) {
+    let mut interval = tokio::time::interval(RELOAD_INTERVAL);
+    loop {
+        interval.tick().await;
+        match denylist.reload(&pool).await {
+            Ok(count) => debug!("Loaded {} denylist entries", count),
+            Err(e) => warn!("Failed to reload denylist: {}", e),
+        }
+    }
+}
@@ -12,16 +12,47 @@ use thiserror::Error;
 pub enum AppError {
     #[error("Database error: {0}")]
     DatabaseError(#[from] sqlx::Error),
+    #[error("Invalid input: {0}")]
+    InvalidInput(String),
+    #[error("Unauthorized: {0}")]
+    Unauthorized(String),
+    #[error("Conflict: {0}")]
+    Conflict(String),
+    #[error("Quota exceeded: {0}")]
+    QuotaExceeded(String),
+    #[error("Not found: {0}")]
+    NotFound(String),
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
+    #[error("Rate limited: retry after {0} seconds")]
+    RateLimited(u64),
 }
 
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
-        error!("Error processing request: {}", self);
+        // 被屏蔽的 key 在 validate_key 中以 debug 级别记录，限流在中间件中记录
+        if !matches!(self, AppError::Forbidden(_) | AppError::RateLimited(_)) {
+            error!("Error processing request: {}", self);
+        }
+        let retry_after = match self {
+            AppError::RateLimited(seconds) => Some(seconds),
+            _ => None,
+        };
         let (status, error_message) = match self {
             AppError::DatabaseError(_) => (
                 StatusCode::INTERNAL_SERVER_ERROR,
                 "An unexpected database error occurred.".to_string(),
             ),
+            AppError::InvalidInput(message) => (StatusCode::BAD_REQUEST, message),
+            AppError::Unauthorized(message) => (StatusCode::UNAUTHORIZED, message),
+            AppError::Conflict(message) => (StatusCode::CONFLICT, message),
+            AppError::QuotaExceeded(message) => (StatusCode::TOO_MANY_REQUESTS, message),
+            AppError::NotFound(message) => (StatusCode::NOT_FOUND, message),
+            AppError::Forbidden(message) => (StatusCode::FORBIDDEN, message),
+            AppError::RateLimited(seconds) => (
+                StatusCode::TOO_MANY_REQUESTS,
+                format!("Too many requests. Retry after {}s.", seconds),
+            ),
         };
         let api_error = ApiError {
             message: error_message,
@@ -38,6 +69,11 @@ impl IntoResponse for AppError {
         response
             .headers_mut()
             .insert(header::EXPIRES, HeaderValue::from_static("0"));
+        if let Some(seconds) = retry_after {
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, HeaderValue::from(seconds));
+        }
         response
     }
 }
\ No newline at end of file
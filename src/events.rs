@@ -0,0 +1,205 @@
+//! 可选的访问事件记录
+//!
+//! With `HIT_EVENTS_SAMPLE_RATE=N`, one in `N` counted hits is written to `hit_events`
+//! with its time, key, referrer host, a coarse [`UaClass`] and any [`Utm`] campaign
+//! parameters. Each row carries the number of hits it stands for, so sums over `weight`
+//! estimate the real counts. Client IPs, full referrer URLs and raw user agents are never
+//! stored, and rows older than
+//! `HIT_EVENTS_RETENTION_DAYS` are deleted hourly. Recording is off by default since it
+//! adds an insert to every sampled hit.
+
+use crate::error::AppError;
+use axum::http::{header, HeaderMap};
+use sqlx::postgres::PgPool;
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// 清理过期事件的间隔
+const PRUNE_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// UTM 参数值保留的最大字符数
+const MAX_UTM_CHARS: usize = 100;
+
+/// 图片代理的 `User-Agent` 片段（小写）
+const PROXY_AGENTS: &[&str] = &["camo", "googleimageproxy", "yahoomailproxy"];
+
+/// 爬虫的 `User-Agent` 片段（小写），只用于分类，与 `BOT_USER_AGENTS` 无关
+const BOT_AGENTS: &[&str] = &[
+    "bot",
+    "crawler",
+    "spider",
+    "slurp",
+    "headless",
+    "lighthouse",
+];
+
+/// 命令行工具与 HTTP 库的 `User-Agent` 前缀（小写）
+const TOOL_AGENTS: &[&str] = &[
+    "curl/",
+    "wget/",
+    "python-",
+    "python/",
+    "go-http-client",
+    "okhttp",
+    "java/",
+    "node-fetch",
+    "axios",
+    "httpie",
+    "libwww-perl",
+    "reqwest",
+];
+
+/// 粗粒度的客户端类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UaClass {
+    /// Image proxies such as GitHub's camo, which fetch on behalf of a viewer
+    Proxy,
+    Bot,
+    /// Command-line tools and HTTP libraries
+    Tool,
+    Mobile,
+    Desktop,
+    Unknown,
+}
+
+impl UaClass {
+    pub fn classify(headers: &HeaderMap) -> Self {
+        let Some(user_agent) = headers
+            .get(header::USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_ascii_lowercase)
+        else {
+            return Self::Unknown;
+        };
+        let contains_any = |parts: &[&str]| parts.iter().any(|part| user_agent.contains(part));
+        if contains_any(PROXY_AGENTS) {
+            Self::Proxy
+        } else if contains_any(BOT_AGENTS) {
+            Self::Bot
+        } else if TOOL_AGENTS
+            .iter()
+            .any(|prefix| user_agent.starts_with(prefix))
+        {
+            Self::Tool
+        } else if contains_any(&["mobile", "android", "iphone", "ipad"]) {
+            Self::Mobile
+        } else if user_agent.starts_with("mozilla/") {
+            Self::Desktop
+        } else {
+            Self::Unknown
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Proxy => "proxy",
+            Self::Bot => "bot",
+            Self::Tool => "tool",
+            Self::Mobile => "mobile",
+            Self::Desktop => "desktop",
+            Self::Unknown => "unknown",
+        }
+    }
+}
+
+/// `Referer` 的主机名（小写），没有或无法解析时为 `None`
+pub fn referrer_host(headers: &HeaderMap) -> Option<String> {
+    let referer = headers.get(header::REFERER)?.to_str().ok()?;
+    let url = url::Url::parse(referer).ok()?;
+    url.host_str().map(str::to_ascii_lowercase)
+}
+
+/// 活动参数 `utm_source`、`utm_medium` 与 `utm_campaign`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Utm {
+    pub source: Option<String>,
+    pub medium: Option<String>,
+    pub campaign: Option<String>,
+}
+
+impl Utm {
+    /// 优先读取请求自身的查询参数，都没有时读取 `Referer` 中的参数
+    pub fn from_request(query: Option<&str>, headers: &HeaderMap) -> Self {
+        let utm = query.map(Self::parse).unwrap_or_default();
+        if !utm.is_empty() {
+            return utm;
+        }
+        headers
+            .get(header::REFERER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|referer| url::Url::parse(referer).ok())
+            .and_then(|url| url.query().map(Self::parse))
+            .unwrap_or_default()
+    }
+
+    fn parse(query: &str) -> Self {
+        let mut utm = Self::default();
+        for (name, value) in url::form_urlencoded::parse(query.as_bytes()) {
+            let slot = match name.as_ref() {
+                "utm_source" => &mut utm.source,
+                "utm_medium" => &mut utm.medium,
+                "utm_campaign" => &mut utm.campaign,
+                _ => continue,
+            };
+            let value = value.trim();
+            if slot.is_none() && !value.is_empty() {
+                *slot = Some(value.chars().take(MAX_UTM_CHARS).collect());
+            }
+        }
+        utm
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.source.is_none() && self.medium.is_none() && self.campaign.is_none()
+    }
+}
+
+/// 数据库操作：写入一条事件
+pub async fn record(
+    pool: &PgPool,
+    stored_key: &str,
+    headers: &HeaderMap,
+    utm: &Utm,
+    weight: i32,
+) -> Result<(), AppError> {
+    sqlx::query!(
+        r#"
+        INSERT INTO hit_events (
+            key, referrer_host, ua_class, weight, utm_source, utm_medium, utm_campaign
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        "#,
+        stored_key,
+        referrer_host(headers),
+        UaClass::classify(headers).as_str(),
+        weight,
+        utm.source,
+        utm.medium,
+        utm.campaign
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// 定期删除超出保留期的事件
+pub async fn run(pool: PgPool, retention: Duration) {
+    let mut interval = tokio::time::interval(PRUNE_INTERVAL);
+    let retention_seconds = retention.as_secs_f64();
+    loop {
+        interval.tick().await;
+        let result = sqlx::query!(
+            "DELETE FROM hit_events WHERE created_at < NOW() - make_interval(secs => $1)",
+            retention_seconds
+        )
+        .execute(&pool)
+        .await;
+        match result {
+            Ok(result) if result.rows_affected() > 0 => {
+                info!("Pruned {} hit events", result.rows_affected());
+            }
+            Ok(_) => {}
+            Err(e) => warn!("Failed to prune hit events: {}", e),
+        }
+    }
+}
@@ -0,0 +1,456 @@
+//! 徽章文本格式化
+
+use crate::api::types::{CountFormat, Separator};
+use crate::config::AppConfig;
+
+/// 公制前缀，依次为 10^3、10^6 …
+const METRIC_PREFIXES: [&str; 6] = ["k", "M", "G", "T", "P", "E"];
+
+/// 将计数格式化为徽章右侧显示的文本
+///
+/// Counts above `DISPLAY_CAP` show the cap in compact form, e.g. `1M+`, whatever `format`
+/// and `separator` say (see [`capped_text`]). Only affects what the SVG badge shows; the
+/// stored total is never changed.
+pub fn format_count(
+    count: i64,
+    format: CountFormat,
+    separator: Separator,
+    config: &AppConfig,
+) -> String {
+    match config.display_cap {
+        Some(cap) if count > cap => capped_text(cap, config),
+        _ => format_number(count, format, separator),
+    }
+}
+
+/// 超过上限时显示的文本：紧凑写法的上限加后缀，如 `999+`、`1M+`
+pub fn capped_text(cap: i64, config: &AppConfig) -> String {
+    format!("{}{}", format_compact(cap), config.display_cap_suffix)
+}
+
+/// 按 `format` 书写计数，SVG 与 shields.io 接口共用；分隔符只用于 `plain`
+pub fn format_number(count: i64, format: CountFormat, separator: Separator) -> String {
+    match (format, separator.as_char()) {
+        (CountFormat::Compact, _) => format_compact(count),
+        (CountFormat::Plain, None) => count.to_string(),
+        (CountFormat::Plain, Some(separator)) => group_thousands(count, separator),
+    }
+}
+
+/// 每三位插入分隔符，如 `12,345`
+fn group_thousands(count: i64, separator: char) -> String {
+    let digits = count.unsigned_abs().to_string();
+    let mut out = String::with_capacity(digits.len() * 4 / 3 + 1);
+    if count < 0 {
+        out.push('-');
+    }
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            out.push(separator);
+        }
+        out.push(digit);
+    }
+    out
+}
+
+/// 与 shields.io 相同的缩写：`999`、`1.2k`、`12k`、`3.4M`
+///
+/// Below 10 of a unit one decimal is shown unless it is `0`; otherwise the value is
+/// rounded, moving up to the next prefix when it would reach 1000.
+pub fn format_compact(count: i64) -> String {
+    let sign = if count < 0 { "-" } else { "" };
+    let abs = count.unsigned_abs();
+    for (i, prefix) in METRIC_PREFIXES.iter().enumerate().rev() {
+        let limit = 1000u64.pow(i as u32 + 1);
+        if abs < limit {
+            continue;
+        }
+        let scaled = abs as f64 / limit as f64;
+        if scaled < 10.0 {
+            let one_decimal = format!("{:.1}", scaled);
+            if !one_decimal.ends_with('0') {
+                return format!("{}{}{}", sign, one_decimal, prefix);
+            }
+        }
+        let rounded = scaled.round();
+        return match METRIC_PREFIXES.get(i + 1) {
+            Some(next) if rounded >= 1000.0 => format!("{}1{}", sign, next),
+            _ => format!("{}{}{}", sign, rounded, prefix),
+        };
+    }
+    count.to_string()
+}
+
+/// 里程表式显示：`count % modulus`，补零到 `modulus - 1` 的位数
+///
+/// `modulus = 1000` shows `042` for a total of 12042. `modulus` must be positive.
+pub fn format_rollover(count: i64, modulus: i64) -> String {
+    let width = (modulus - 1).max(1).to_string().len();
+    format!("{:0width$}", count.rem_euclid(modulus), width = width)
+}
+
+/// 相对上次已知计数的增量，如 `(+12)`、`(+1.2k)`；计数比 `from` 小时为负
+///
+/// The magnitude is written like the count, with the same `format` and `separator`.
+pub fn format_delta(count: i64, from: i64, format: CountFormat, separator: Separator) -> String {
+    let delta = count.saturating_sub(from);
+    let sign = if delta >= 0 { "+" } else { "" };
+    format!("({}{})", sign, format_number(delta, format, separator))
+}
+
+/// `message_template` 中代表计数的占位符
+pub const COUNT_PLACEHOLDER: &str = "{count}";
+
+/// 把格式化后的计数代入 `template`，没有模板时原样返回
+pub fn apply_template(template: Option<&str>, message: String) -> String {
+    match template {
+        Some(template) => template.replace(COUNT_PLACEHOLDER, &message),
+        None => message,
+    }
+}
+
+/// 计数动画的中间帧数
+const COUNT_UP_FRAMES: i64 = 12;
+
+/// 计数动画的中间值，先快后慢地从 0 增长到 `total`（不含 `total` 本身）
+pub fn count_up_steps(total: i64) -> Vec<i64> {
+    let mut steps: Vec<i64> = (0..COUNT_UP_FRAMES)
+        .map(|i| {
+            let t = i as f64 / COUNT_UP_FRAMES as f64;
+            (total as f64 * (1.0 - (1.0 - t).powi(3))).round() as i64
+        })
+        .filter(|&step| step < total)
+        .collect();
+    steps.dedup();
+    steps
+}
+
+/// 趋势徽章的颜色
+pub const TREND_UP_COLOR: &str = "#4c1";
+pub const TREND_DOWN_COLOR: &str = "#e05d44";
+pub const TREND_FLAT_COLOR: &str = "#9f9f9f";
+
+/// 被屏蔽 key 的徽章颜色
+pub const ERROR_COLOR: &str = "#e05d44";
+
+/// 被屏蔽 key 的徽章文本
+pub const BLOCKED_MESSAGE: &str = "blocked";
+
+/// 服务端出错时的徽章文本
+pub const ERROR_MESSAGE: &str = "error";
+
+/// 超过 `stale_after` 未更新的徽章颜色
+pub const STALE_COLOR: &str = "#9f9f9f";
+
+/// 过期徽章的文本，如 `1234 (stale)`
+pub fn format_stale(message: &str) -> String {
+    format!("{} (stale)", message)
+}
+
+/// 根据本期与上期计数生成趋势文本与颜色
+///
+/// Without any hits in the previous period there is nothing to compare against, so the
+/// trend is shown as flat.
+pub fn format_trend(
+    current: i64,
+    previous: i64,
+    format: CountFormat,
+    separator: Separator,
+    config: &AppConfig,
+) -> (String, &'static str) {
+    let count = format_count(current, format, separator, config);
+    if previous == 0 || current == previous {
+        (format!("→ {}", count), TREND_FLAT_COLOR)
+    } else if current > previous {
+        (format!("↑ {}", count), TREND_UP_COLOR)
+    } else {
+        (format!("↓ {}", count), TREND_DOWN_COLOR)
+    }
+}
+
+/// 转义 XML 特殊字符，结果可同时放进文本与属性值
+///
+/// The renderer interpolates labels, messages and links verbatim, so they are escaped
+/// before rendering. Numeric character references are used because `for-the-badge`
+/// upper-cases its text, which would turn `&amp;` into the undefined `&AMP;`.
+pub fn escape_xml(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&#38;"),
+            '<' => out.push_str("&#60;"),
+            '>' => out.push_str("&#62;"),
+            '"' => out.push_str("&#34;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// 清理徽章文本中的控制字符
+///
+/// Line breaks and tabs become spaces; other control characters have no glyph or
+/// advance width and are dropped so they cannot break the layout. Tag characters
+/// (U+E0000..U+E007F) are invisible and dropped too, since badges use them as
+/// placeholders (see [`crate::svg::mask_markup`]).
+pub fn sanitize_text(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\r' | '\n' | '\t' => out.push(' '),
+            c if c.is_control() => {}
+            '\u{E0000}'..='\u{E007F}' => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::types::BadgeStyle;
+    use crate::{badge, svg};
+
+    const FORMATS: [CountFormat; 2] = [CountFormat::Plain, CountFormat::Compact];
+    const SEPARATORS: [Separator; 4] = [
+        Separator::None,
+        Separator::Comma,
+        Separator::Dot,
+        Separator::Space,
+    ];
+
+    #[test]
+    fn extreme_counts() {
+        let cases = [
+            (
+                CountFormat::Plain,
+                Separator::None,
+                i64::MAX,
+                "9223372036854775807",
+            ),
+            (
+                CountFormat::Plain,
+                Separator::Comma,
+                i64::MAX,
+                "9,223,372,036,854,775,807",
+            ),
+            (
+                CountFormat::Plain,
+                Separator::Dot,
+                i64::MAX,
+                "9.223.372.036.854.775.807",
+            ),
+            (
+                CountFormat::Plain,
+                Separator::Space,
+                i64::MAX,
+                "9 223 372 036 854 775 807",
+            ),
+            (CountFormat::Compact, Separator::None, i64::MAX, "9.2E"),
+            (
+                CountFormat::Plain,
+                Separator::None,
+                i64::MIN,
+                "-9223372036854775808",
+            ),
+            (
+                CountFormat::Plain,
+                Separator::Comma,
+                i64::MIN,
+                "-9,223,372,036,854,775,808",
+            ),
+            (
+                CountFormat::Plain,
+                Separator::Dot,
+                i64::MIN,
+                "-9.223.372.036.854.775.808",
+            ),
+            (
+                CountFormat::Plain,
+                Separator::Space,
+                i64::MIN,
+                "-9 223 372 036 854 775 808",
+            ),
+            (CountFormat::Compact, Separator::Comma, i64::MIN, "-9.2E"),
+        ];
+        for (format, separator, count, expected) in cases {
+            assert_eq!(format_number(count, format, separator), expected);
+        }
+    }
+
+    #[test]
+    fn control_characters_in_labels() {
+        assert_eq!(sanitize_text("Page\tViews"), "Page Views");
+        assert_eq!(sanitize_text("line\nbreak"), "line break");
+        assert_eq!(sanitize_text("crlf\r\nend"), "crlf end");
+        assert_eq!(sanitize_text("cr\rend"), "cr end");
+        assert_eq!(sanitize_text("bell\u{7}null\u{0}del\u{7f}"), "bellnulldel");
+        assert_eq!(sanitize_text("\u{1b}[31mred"), "[31mred");
+        assert_eq!(sanitize_text("访问量 ✓"), "访问量 ✓");
+    }
+
+    #[test]
+    fn capped_extreme_counts() {
+        let config = AppConfig {
+            display_cap: Some(i64::MAX - 1),
+            display_cap_suffix: "+".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(
+            format_count(i64::MAX, CountFormat::Plain, Separator::None, &config),
+            "9.2E+"
+        );
+        assert_eq!(
+            format_count(i64::MIN, CountFormat::Compact, Separator::None, &config),
+            "-9.2E"
+        );
+    }
+
+    #[test]
+    fn deltas_follow_the_count_format() {
+        let delta = |count, from, format, separator| format_delta(count, from, format, separator);
+        assert_eq!(
+            delta(123_458, 100_000, CountFormat::Plain, Separator::None),
+            "(+23458)"
+        );
+        assert_eq!(
+            delta(123_458, 100_000, CountFormat::Plain, Separator::Comma),
+            "(+23,458)"
+        );
+        assert_eq!(
+            delta(101_234, 100_000, CountFormat::Compact, Separator::None),
+            "(+1.2k)"
+        );
+        assert_eq!(
+            delta(100_000, 101_234, CountFormat::Compact, Separator::Comma),
+            "(-1.2k)"
+        );
+        assert_eq!(delta(5, 5, CountFormat::Plain, Separator::Space), "(+0)");
+        assert_eq!(
+            delta(0, 1_234_567, CountFormat::Plain, Separator::Dot),
+            "(-1.234.567)"
+        );
+    }
+
+    #[test]
+    fn display_cap() {
+        let config = |cap| AppConfig {
+            display_cap: Some(cap),
+            display_cap_suffix: "+".to_string(),
+            ..Default::default()
+        };
+        let capped =
+            |count, cap, format, separator| format_count(count, format, separator, &config(cap));
+        assert_eq!(
+            capped(1000, 999, CountFormat::Plain, Separator::None),
+            "999+"
+        );
+        assert_eq!(capped(999, 999, CountFormat::Plain, Separator::None), "999");
+        assert_eq!(
+            capped(1_000_001, 1_000_000, CountFormat::Plain, Separator::Comma),
+            "1M+"
+        );
+        assert_eq!(
+            capped(1_000_000, 1_000_000, CountFormat::Plain, Separator::Comma),
+            "1,000,000"
+        );
+        assert_eq!(
+            capped(20_000, 10_000, CountFormat::Compact, Separator::None),
+            "10k+"
+        );
+    }
+
+    #[test]
+    fn extreme_counts_render_with_finite_width() {
+        let config = AppConfig::default();
+        for count in [i64::MAX, i64::MIN] {
+            for format in FORMATS {
+                for separator in SEPARATORS {
+                    let message = format_count(count, format, separator, &config);
+                    for style in BadgeStyle::ALL {
+                        let badge = badge::render(
+                            style,
+                            &shields::BadgeParams {
+                                style: style.into(),
+                                label: Some("Hits"),
+                                message: Some(message.as_str()),
+                                label_color: None,
+                                message_color: None,
+                                link: None,
+                                extra_link: None,
+                                logo: None,
+                                logo_color: None,
+                            },
+                        );
+                        let (width, height) = svg::dimensions(&badge).expect("badge has a size");
+                        assert!(width.is_finite() && width > 0.0, "{message} in {style:?}");
+                        assert!(height.is_finite() && height > 0.0, "{message} in {style:?}");
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn escape_xml_uses_numeric_references() {
+        for (c, reference) in [
+            ('&', "&#38;"),
+            ('<', "&#60;"),
+            ('>', "&#62;"),
+            ('"', "&#34;"),
+            ('\'', "&#39;"),
+        ] {
+            assert_eq!(escape_xml(&c.to_string()), reference);
+        }
+        assert_eq!(
+            escape_xml(r#"<a href="x">R&D's</a>"#),
+            "&#60;a href=&#34;x&#34;&#62;R&#38;D&#39;s&#60;/a&#62;"
+        );
+        assert_eq!(
+            escape_xml("plain text, ünïcode 🎉"),
+            "plain text, ünïcode 🎉"
+        );
+        // 已转义的文本会再转义一次
+        assert_eq!(escape_xml("&#38;"), "&#38;#38;");
+    }
+
+    #[test]
+    fn references_survive_for_the_badge_upper_casing() {
+        let label = r#"R&D <"it's">"#;
+        let render = |label: &str| {
+            badge::render(
+                BadgeStyle::ForTheBadge,
+                &shields::BadgeParams {
+                    style: BadgeStyle::ForTheBadge.into(),
+                    label: Some(label),
+                    message: Some("42"),
+                    label_color: None,
+                    message_color: None,
+                    link: None,
+                    extra_link: None,
+                    logo: None,
+                    logo_color: None,
+                },
+            )
+        };
+        let expected = "R&#38;D &#60;&#34;IT&#39;S&#34;&#62;";
+        // 直接渲染转义后的文本，与 `/svg` 的占位方式都应得到相同的引用
+        for badge in [
+            render(&escape_xml(label)),
+            svg::unmask_markup(&render(&svg::mask_markup(label))),
+        ] {
+            assert!(badge.contains(expected), "{badge}");
+            assert!(
+                !badge.contains("&AMP;") && !badge.contains("&#X"),
+                "{badge}"
+            );
+            assert!(xml::reader::EventReader::new(badge.as_bytes())
+                .into_iter()
+                .all(|event| event.is_ok()));
+        }
+    }
+}
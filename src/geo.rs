@@ -0,0 +1,51 @@
+//! 国家级地理位置查询
+//!
+//! Countries are resolved from a MaxMind-format database (`GEOIP_DATABASE`, e.g. the free
+//! GeoLite2-Country). Only ISO country codes leave this module; addresses are never stored.
+//! A missing or unreadable database disables the lookup instead of failing startup.
+
+use crate::config::AppConfig;
+use maxminddb::{geoip2, Reader};
+use std::net::IpAddr;
+use tracing::{info, warn};
+
+/// GeoIP 数据库封装，未配置或加载失败时所有查询都返回 `None`
+#[derive(Default)]
+pub struct GeoIp {
+    reader: Option<Reader<Vec<u8>>>,
+}
+
+impl GeoIp {
+    pub fn open(config: &AppConfig) -> Self {
+        let Some(path) = &config.geoip_database else {
+            return Self::default();
+        };
+        match Reader::open_readfile(path) {
+            Ok(reader) => {
+                info!("Loaded GeoIP database from {}", path.display());
+                Self {
+                    reader: Some(reader),
+                }
+            }
+            Err(err) => {
+                warn!(
+                    "Failed to load GeoIP database {}: {}; geo counts disabled",
+                    path.display(),
+                    err
+                );
+                Self::default()
+            }
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.reader.is_some()
+    }
+
+    /// 查询 IP 所属国家的 ISO 3166-1 alpha-2 代码
+    pub fn country(&self, ip: IpAddr) -> Option<String> {
+        let result = self.reader.as_ref()?.lookup(ip).ok()?;
+        let country = result.decode::<geoip2::Country>().ok()??;
+        country.country.iso_code.map(str::to_string)
+    }
+}
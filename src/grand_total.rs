@@ -0,0 +1,68 @@
+//! 全局总数的内存副本
+//!
+//! `/` reports the sum of all counters without querying the database per request. The
+//! in-memory value is bumped on every counted hit and periodically replaced with
+//! `SELECT SUM(count)` (`GRAND_TOTAL_RECONCILE_SECONDS`), which corrects drift from
+//! writes this process did not see, such as other instances, renames or failed requests.
+
+use crate::config::AppConfig;
+use sqlx::postgres::PgPool;
+use std::sync::{
+    atomic::{AtomicI64, Ordering},
+    Arc,
+};
+use tracing::{debug, info, warn};
+
+/// 所有 key 的总计数
+#[derive(Default)]
+pub struct GrandTotal {
+    total: AtomicI64,
+}
+
+impl GrandTotal {
+    pub fn get(&self) -> i64 {
+        self.total.load(Ordering::Relaxed)
+    }
+
+    /// 替换为新值，返回之前的值
+    pub fn set(&self, total: i64) -> i64 {
+        self.total.swap(total, Ordering::Relaxed)
+    }
+
+    pub fn add(&self, amount: i64) {
+        self.total
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |total| {
+                Some(total.saturating_add(amount))
+            })
+            .ok();
+    }
+}
+
+/// 定期与数据库对账的后台任务，查询失败时保留当前值并在下次重试
+pub async fn run(pool: PgPool, config: Arc<AppConfig>, grand_total: Arc<GrandTotal>) {
+    let mut interval = tokio::time::interval(config.grand_total_reconcile_interval);
+    let mut loaded = false;
+    loop {
+        interval.tick().await;
+        let result = sqlx::query_scalar!(
+            r#"SELECT COALESCE(SUM(count), 0)::BIGINT AS "total!" FROM counters"#
+        )
+        .fetch_one(&pool)
+        .await;
+        match result {
+            Ok(total) => {
+                let previous = grand_total.set(total);
+                let drift = total - previous;
+                if !loaded {
+                    info!("Loaded grand total {}", total);
+                    loaded = true;
+                } else if drift != 0 {
+                    info!("Reconciled grand total to {} (drift {:+})", total, drift);
+                } else {
+                    debug!("Grand total {} is in sync", total);
+                }
+            }
+            Err(e) => warn!("Failed to reconcile grand total: {}", e),
+        }
+    }
+}
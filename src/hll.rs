@@ -0,0 +1,107 @@
+//! HyperLogLog 独立访客估算
+//!
+//! With `TRACK_UNIQUES`, every counted hit with a known client IP updates one of the
+//! [`REGISTERS`] registers of the key's sketch for the current day (days follow
+//! `WINDOW_OFFSET`). A register only ever grows, so the upsert is a no-op for most hits.
+//! Daily sketches are merged by taking the per-register maximum, which lets `/stats/{key}`
+//! estimate distinct visitors for any range of days. With 1024 registers the standard
+//! error is about 3%.
+
+use crate::error::AppError;
+use chrono::{DateTime, Utc};
+use sqlx::postgres::PgPool;
+
+/// 寄存器索引的位数
+const PRECISION: u32 = 10;
+/// 每个 sketch 的寄存器数量
+pub const REGISTERS: usize = 1 << PRECISION;
+
+/// 由 64 位哈希得到寄存器索引与秩（剩余位的前导零数加一）
+pub fn observe(hash: u64) -> (i16, i16) {
+    let register = (hash >> (64 - PRECISION)) as i16;
+    let rest = hash << PRECISION;
+    let rank = (rest.leading_zeros() + 1).min(64 - PRECISION + 1) as i16;
+    (register, rank)
+}
+
+/// 由各寄存器的最大秩估算基数，缺失的寄存器视为 0
+pub fn estimate(ranks: impl IntoIterator<Item = (i16, i16)>) -> i64 {
+    let mut registers = [0i16; REGISTERS];
+    for (register, rank) in ranks {
+        if let Some(slot) = registers.get_mut(register as usize) {
+            *slot = (*slot).max(rank);
+        }
+    }
+    let m = REGISTERS as f64;
+    let alpha = 0.7213 / (1.0 + 1.079 / m);
+    let sum: f64 = registers
+        .iter()
+        .map(|&rank| 2f64.powi(-i32::from(rank)))
+        .sum();
+    let raw = alpha * m * m / sum;
+    let zeros = registers.iter().filter(|&&rank| rank == 0).count();
+    // 小基数时改用线性计数
+    let estimate = if raw <= 2.5 * m && zeros > 0 {
+        m * (m / zeros as f64).ln()
+    } else {
+        raw
+    };
+    estimate.round() as i64
+}
+
+/// 数据库操作：把一次访问计入 key 当天的 sketch
+pub async fn record(
+    pool: &PgPool,
+    stored_key: &str,
+    hash: u64,
+    window_offset_minutes: i32,
+) -> Result<(), AppError> {
+    let (register, rank) = observe(hash);
+    sqlx::query!(
+        r#"
+        INSERT INTO visitor_sketches (key, day_window, register, rank)
+        VALUES (
+            $1,
+            DATE_TRUNC('day', NOW() - make_interval(mins => $2), 'UTC')
+                + make_interval(mins => $2),
+            $3,
+            $4
+        )
+        ON CONFLICT (key, day_window, register)
+        DO UPDATE SET rank = EXCLUDED.rank
+        WHERE visitor_sketches.rank < EXCLUDED.rank
+        "#,
+        stored_key,
+        window_offset_minutes,
+        register,
+        rank
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// 数据库操作：估算区间内（按天）的独立访客数
+pub async fn estimate_range(
+    pool: &PgPool,
+    stored_key: &str,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> Result<i64, AppError> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT register, MAX(rank) AS "rank!"
+        FROM visitor_sketches
+        WHERE key = $1 AND day_window >= $2 AND day_window < $3
+        GROUP BY register
+        "#,
+        stored_key,
+        from,
+        to
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(estimate(
+        rows.into_iter().map(|row| (row.register, row.rank)),
+    ))
+}
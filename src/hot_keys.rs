@@ -0,0 +1,165 @@
+//! 热点 key 的写入合并
+//!
+//! Every counted hit upserts the same `(key, minute_window)` row, so a viral badge makes
+//! concurrent requests queue on that row's lock in Postgres. With `COALESCE_WRITES`
+//! enabled, hits for a key that arrive while an upsert for it is already running are
+//! collected in memory and written together by one follow-up upsert. At most one upsert
+//! per key is in flight per process; the pending hits live in [`SHARDS`] mutex-protected
+//! maps selected by key hash, so unrelated keys rarely share a lock.
+//!
+//! Each caller still gets the total as it would have been right after its own hit: a
+//! batch of hits `a1, a2, …` written as one upsert returning `T` hands out
+//! `T - (a2 + …)`, `T - (a3 + …)`, …, `T`.
+
+use crate::api::handlers::add_to_count;
+use crate::config::Granularity;
+use crate::error::AppError;
+use sqlx::postgres::PgPool;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::oneshot;
+use tracing::debug;
+
+/// 分片数量
+pub const SHARDS: usize = 64;
+
+type Waiter = (i32, oneshot::Sender<Result<i64, String>>);
+
+/// 按 key 合并并发写入
+pub struct WriteCoalescer {
+    enabled: bool,
+    slow_query: Option<Duration>,
+    shards: Vec<Mutex<HashMap<String, Vec<Waiter>>>>,
+}
+
+impl WriteCoalescer {
+    pub fn new(enabled: bool, slow_query: Option<Duration>) -> Self {
+        Self {
+            enabled,
+            slow_query,
+            shards: (0..SHARDS).map(|_| Mutex::default()).collect(),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// 增加计数并返回本次增加后的总数，未开启时直接写入
+    pub async fn add(
+        self: &Arc<Self>,
+        pool: &PgPool,
+        stored_key: &str,
+        amount: i32,
+        granularity: Granularity,
+        (zone, offset_minutes): (&str, i32),
+    ) -> Result<i64, AppError> {
+        if !self.enabled {
+            return add_to_count(
+                pool,
+                stored_key,
+                amount,
+                granularity,
+                zone,
+                offset_minutes,
+                self.slow_query,
+            )
+            .await;
+        }
+        let (sender, receiver) = oneshot::channel();
+        let leader = {
+            let mut pending = self.shard(stored_key).lock().unwrap();
+            match pending.get_mut(stored_key) {
+                // 已有写入在进行，加入下一批
+                Some(waiters) => {
+                    waiters.push((amount, sender));
+                    false
+                }
+                None => {
+                    pending.insert(stored_key.to_string(), vec![(amount, sender)]);
+                    true
+                }
+            }
+        };
+        if leader {
+            // 写入放在独立任务中，请求被取消时其他等待者也能拿到结果
+            tokio::spawn(self.clone().flush(
+                pool.clone(),
+                stored_key.to_string(),
+                granularity,
+                (zone.to_string(), offset_minutes),
+            ));
+        }
+        match receiver.await {
+            Ok(Ok(total)) => Ok(total),
+            Ok(Err(message)) => Err(AppError::DatabaseError(sqlx::Error::Protocol(message))),
+            Err(_) => Err(AppError::DatabaseError(sqlx::Error::WorkerCrashed)),
+        }
+    }
+
+    /// 依次写入积攒的批次，直到没有新的等待者
+    ///
+    /// A key's granularity and day boundary come from the configuration, so every hit in its
+    /// batches shares the leader's.
+    async fn flush(
+        self: Arc<Self>,
+        pool: PgPool,
+        stored_key: String,
+        granularity: Granularity,
+        (zone, offset_minutes): (String, i32),
+    ) {
+        loop {
+            let batch = {
+                let mut pending = self.shard(&stored_key).lock().unwrap();
+                let Some(waiters) = pending.get_mut(&stored_key) else {
+                    return;
+                };
+                if waiters.is_empty() {
+                    pending.remove(&stored_key);
+                    return;
+                }
+                std::mem::take(waiters)
+            };
+            let amount = batch
+                .iter()
+                .fold(0i32, |sum, &(amount, _)| sum.saturating_add(amount));
+            if batch.len() > 1 {
+                debug!("Coalesced {} hits for {}", batch.len(), stored_key);
+            }
+            let total = add_to_count(
+                &pool,
+                &stored_key,
+                amount,
+                granularity,
+                &zone,
+                offset_minutes,
+                self.slow_query,
+            )
+            .await;
+            match total {
+                Ok(total) => {
+                    let mut after = total;
+                    for (amount, sender) in batch.into_iter().rev() {
+                        sender.send(Ok(after)).ok();
+                        after = after.saturating_sub(i64::from(amount));
+                    }
+                }
+                Err(err) => {
+                    let message = err.to_string();
+                    for (_, sender) in batch {
+                        sender.send(Err(message.clone())).ok();
+                    }
+                }
+            }
+        }
+    }
+
+    fn shard(&self, stored_key: &str) -> &Mutex<HashMap<String, Vec<Waiter>>> {
+        let mut hasher = DefaultHasher::new();
+        stored_key.hash(&mut hasher);
+        &self.shards[hasher.finish() as usize % SHARDS]
+    }
+}
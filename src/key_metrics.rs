@@ -0,0 +1,120 @@
+//! 按 key 导出的 Prometheus 指标快照
+//!
+//! Exporting every key would give Prometheus one series per key, which is unbounded on a
+//! public instance. Only keys whose total reaches `METRICS_KEYS_MIN_TOTAL` or that are
+//! listed in `METRICS_KEYS_ALLOWLIST` are exported, capped at [`MAX_EXPORTED_KEYS`]. The
+//! totals include seeds and `KEY_BASE_COUNTS`, like `/count`, and come from a snapshot
+//! refreshed in the background, so scrapes never hit the database. With `HASH_KEYS` only allowlisted keys can be shown by name; other keys are
+//! exported under their stored digest.
+
+use crate::config::AppConfig;
+use sqlx::postgres::PgPool;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use tracing::{debug, warn};
+
+/// 最多导出的 key 数量
+pub const MAX_EXPORTED_KEYS: i64 = 10_000;
+
+/// 最近一次快照
+#[derive(Default)]
+pub struct KeyMetrics {
+    totals: RwLock<Vec<(String, i64)>>,
+}
+
+impl KeyMetrics {
+    /// 以 Prometheus 文本格式输出快照
+    pub fn render(&self) -> String {
+        let totals = self.totals.read().unwrap();
+        let mut out =
+            String::from("# HELP hits_total Total hits per key.\n# TYPE hits_total gauge\n");
+        for (key, total) in totals.iter() {
+            out.push_str(&format!(
+                "hits_total{{key=\"{}\"}} {}\n",
+                escape_label_value(key),
+                total
+            ));
+        }
+        out
+    }
+
+    /// 快照中某个 key 的总数
+    pub fn get(&self, key: &str) -> Option<i64> {
+        let totals = self.totals.read().unwrap();
+        totals
+            .iter()
+            .find(|(exported, _)| exported == key)
+            .map(|&(_, total)| total)
+    }
+
+    /// 更新快照中已有 key 的总数，不在快照中的 key 不会被加入
+    pub fn set(&self, key: &str, total: i64) {
+        let mut totals = self.totals.write().unwrap();
+        if let Some(entry) = totals.iter_mut().find(|(exported, _)| exported == key) {
+            entry.1 = total;
+        }
+    }
+}
+
+/// 定期刷新快照的后台任务
+pub async fn run(pool: PgPool, config: Arc<AppConfig>, metrics: Arc<KeyMetrics>) {
+    let mut interval = tokio::time::interval(config.metrics_keys_interval);
+    // 数据库中存的是 stored key，白名单中的 key 可以还原为原文
+    let allowlist: HashMap<String, String> = config
+        .metrics_keys_allowlist
+        .iter()
+        .map(|key| (config.stored_key(key), key.clone()))
+        .collect();
+    let stored_allowlist: Vec<String> = allowlist.keys().cloned().collect();
+    let (base_keys, base_counts): (Vec<String>, Vec<i64>) = config
+        .key_base_counts
+        .iter()
+        .map(|(key, &count)| (config.stored_key(key), count))
+        .unzip();
+    loop {
+        interval.tick().await;
+        let result = sqlx::query!(
+            r#"
+            SELECT key AS "key!", SUM(count)::BIGINT AS "total!"
+            FROM (
+                SELECT key, count::BIGINT AS count FROM counters
+                UNION ALL
+                SELECT key, count FROM key_seeds
+                UNION ALL
+                SELECT * FROM UNNEST($4::TEXT[], $5::BIGINT[])
+            ) totals
+            GROUP BY key
+            HAVING ($1::BIGINT IS NOT NULL AND SUM(count) >= $1) OR key = ANY($2)
+            ORDER BY 2 DESC
+            LIMIT $3
+            "#,
+            config.metrics_keys_min_total,
+            &stored_allowlist,
+            MAX_EXPORTED_KEYS,
+            &base_keys,
+            &base_counts
+        )
+        .fetch_all(&pool)
+        .await;
+        match result {
+            Ok(records) => {
+                debug!("Refreshed key metrics snapshot with {} keys", records.len());
+                *metrics.totals.write().unwrap() = records
+                    .into_iter()
+                    .map(|record| {
+                        let key = allowlist.get(&record.key).cloned().unwrap_or(record.key);
+                        (key, record.total)
+                    })
+                    .collect();
+            }
+            Err(e) => warn!("Failed to refresh key metrics snapshot: {}", e),
+        }
+    }
+}
+
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
@@ -0,0 +1,113 @@
+//! 单个 key 的每分钟计数上限
+//!
+//! Limits are set through `/admin/rate-limits/{key}` and stored in the `key_rate_limits`
+//! table. Every instance keeps a copy in memory, updated immediately by its own admin
+//! requests and reloaded from the table every [`RELOAD_INTERVAL`], so counting never
+//! waits on the table. Usage is read from the current minute's counter row, which all
+//! instances share.
+
+use crate::error::AppError;
+use sqlx::postgres::PgPool;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tracing::{debug, warn};
+
+/// 从数据库重新加载上限的间隔
+pub const RELOAD_INTERVAL: Duration = Duration::from_secs(30);
+
+/// 内存中的上限，按存储 key 索引
+#[derive(Default)]
+pub struct KeyRateLimits {
+    per_minute: RwLock<HashMap<String, i32>>,
+}
+
+impl KeyRateLimits {
+    pub fn get(&self, stored_key: &str) -> Option<i32> {
+        self.per_minute.read().unwrap().get(stored_key).copied()
+    }
+
+    /// 数据库操作：设置上限
+    pub async fn set(
+        &self,
+        pool: &PgPool,
+        stored_key: &str,
+        per_minute: i32,
+    ) -> Result<(), AppError> {
+        sqlx::query!(
+            r#"
+            INSERT INTO key_rate_limits (key, per_minute)
+            VALUES ($1, $2)
+            ON CONFLICT (key) DO UPDATE SET per_minute = $2, updated_at = NOW()
+            "#,
+            stored_key,
+            per_minute
+        )
+        .execute(pool)
+        .await?;
+        self.per_minute
+            .write()
+            .unwrap()
+            .insert(stored_key.to_string(), per_minute);
+        Ok(())
+    }
+
+    /// 数据库操作：删除上限，返回之前是否存在
+    pub async fn remove(&self, pool: &PgPool, stored_key: &str) -> Result<bool, AppError> {
+        let result = sqlx::query!("DELETE FROM key_rate_limits WHERE key = $1", stored_key)
+            .execute(pool)
+            .await?;
+        self.per_minute.write().unwrap().remove(stored_key);
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// 重命名后把内存中的上限移到新 key，`per_minute` 为合并后的值
+    pub fn moved(&self, from: &str, to: &str, per_minute: i32) {
+        let mut limits = self.per_minute.write().unwrap();
+        limits.remove(from);
+        limits.insert(to.to_string(), per_minute);
+    }
+
+    async fn reload(&self, pool: &PgPool) -> Result<usize, AppError> {
+        let rows = sqlx::query!("SELECT key, per_minute FROM key_rate_limits")
+            .fetch_all(pool)
+            .await?;
+        let limits: HashMap<_, _> = rows
+            .into_iter()
+            .map(|row| (row.key, row.per_minute))
+            .collect();
+        let count = limits.len();
+        *self.per_minute.write().unwrap() = limits;
+        Ok(count)
+    }
+}
+
+/// 数据库操作：当前分钟内已计数的次数
+///
+/// Keys bucketed by hour or day have no per-minute rows, so their usage is not measured
+/// and per-key limits do not hold for them.
+pub async fn get_minute_count(pool: &PgPool, stored_key: &str) -> Result<i64, AppError> {
+    let record = sqlx::query!(
+        r#"
+        SELECT COALESCE(SUM(count), 0)::BIGINT AS "count!"
+        FROM counters
+        WHERE key = $1 AND minute_window >= DATE_TRUNC('minute', NOW() AT TIME ZONE 'UTC')
+        "#,
+        stored_key
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(record.count)
+}
+
+/// 定期重新加载上限的后台任务，查询失败时保留当前值
+pub async fn run(pool: PgPool, limits: Arc<KeyRateLimits>) {
+    let mut interval = tokio::time::interval(RELOAD_INTERVAL);
+    loop {
+        interval.tick().await;
+        match limits.reload(&pool).await {
+            Ok(count) => debug!("Loaded {} key rate limits", count),
+            Err(e) => warn!("Failed to reload key rate limits: {}", e),
+        }
+    }
+}
@@ -0,0 +1,80 @@
+//! 默认标签的本地化
+//!
+//! Badges without an explicit `label` show "Hits" in the language asked for with
+//! `?lang=`, or else the first language in `Accept-Language` that has a translation.
+//! English, unknown languages and requests without either keep the English label.
+//! Tags match by prefix, so `de-AT` uses `de` and `zh-Hant-TW` uses `zh-hant`.
+
+use axum::http::{header, HeaderMap};
+
+/// 内置翻译表：小写语言标签与对应的 "Hits"
+const HITS_LABELS: &[(&str, &str)] = &[
+    ("de", "Aufrufe"),
+    ("es", "Visitas"),
+    ("fr", "Visites"),
+    ("it", "Visite"),
+    ("ja", "アクセス数"),
+    ("ko", "조회수"),
+    ("nl", "Bezoeken"),
+    ("pl", "Odsłony"),
+    ("pt", "Visitas"),
+    ("ru", "Просмотры"),
+    ("tr", "Ziyaretler"),
+    ("uk", "Перегляди"),
+    ("zh", "访问量"),
+    ("zh-hant", "訪問量"),
+    ("zh-hk", "訪問量"),
+    ("zh-mo", "訪問量"),
+    ("zh-tw", "訪問量"),
+];
+
+/// 翻译后的默认标签，保持英文时为 `None`
+///
+/// Returns whether the label depends on `Accept-Language`, so callers can add `Vary`.
+pub fn hits_label(lang: Option<&str>, headers: &HeaderMap) -> (Option<&'static str>, bool) {
+    if let Some(lang) = lang {
+        return (lookup(lang), false);
+    }
+    let Some(accept_language) = headers
+        .get(header::ACCEPT_LANGUAGE)
+        .and_then(|value| value.to_str().ok())
+    else {
+        return (None, false);
+    };
+    let mut ranges: Vec<(&str, f32)> = accept_language
+        .split(',')
+        .filter_map(|range| {
+            let mut parts = range.split(';');
+            let tag = parts.next()?.trim();
+            let quality = parts
+                .find_map(|param| param.trim().strip_prefix("q="))
+                .map_or(Some(1.0), |q| q.trim().parse::<f32>().ok())?;
+            (!tag.is_empty() && tag != "*" && quality > 0.0).then_some((tag, quality))
+        })
+        .collect();
+    // 稳定排序，权重相同时保持原顺序
+    ranges.sort_by(|a, b| b.1.total_cmp(&a.1));
+    let label = ranges
+        .into_iter()
+        .map(|(tag, _)| tag)
+        .take_while(|tag| !is_english(tag))
+        .find_map(lookup);
+    (label, true)
+}
+
+fn is_english(tag: &str) -> bool {
+    let tag = tag.to_ascii_lowercase();
+    tag == "en" || tag.starts_with("en-")
+}
+
+/// 依次去掉末尾的子标签查找翻译
+fn lookup(tag: &str) -> Option<&'static str> {
+    let mut tag = tag.trim().to_ascii_lowercase().replace('_', "-");
+    loop {
+        if let Some((_, label)) = HITS_LABELS.iter().find(|(lang, _)| *lang == tag) {
+            return Some(label);
+        }
+        let end = tag.rfind('-')?;
+        tag.truncate(end);
+    }
+}
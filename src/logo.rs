@@ -0,0 +1,289 @@
+//! 自定义 logo
+//!
+//! Besides simple-icons slugs, `/svg/{key}` accepts `logo=data:image/svg+xml;base64,...`
+//! or `logo=data:image/png;base64,...` for an organization's own icon. The image is
+//! limited to [`MAX_LOGO_BYTES`] after decoding. SVG logos are rebuilt from an allowlist
+//! of shape elements: scripts, styles, foreign content, event handlers and references to
+//! anything outside the image are dropped. PNG logos are only checked for their signature.
+//!
+//! The renderer only knows simple-icons, so the badge is laid out with [`PLACEHOLDER`]
+//! and its `<image>` source is swapped for the custom logo afterwards; both are drawn in
+//! the same 14×14 box.
+
+use crate::error::AppError;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use xml::reader::{EventReader, XmlEvent};
+
+/// 解码后允许的最大字节数
+pub const MAX_LOGO_BYTES: usize = 16 * 1024;
+
+/// 用于排版的占位图标
+pub const PLACEHOLDER: &str = "github";
+
+const SVG_PREFIX: &str = "data:image/svg+xml;base64,";
+const PNG_PREFIX: &str = "data:image/png;base64,";
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// 保留的 SVG 元素，其余元素连同子节点一起丢弃
+const ALLOWED_ELEMENTS: &[&str] = &[
+    "svg",
+    "g",
+    "defs",
+    "symbol",
+    "use",
+    "title",
+    "desc",
+    "path",
+    "rect",
+    "circle",
+    "ellipse",
+    "line",
+    "polyline",
+    "polygon",
+    "linearGradient",
+    "radialGradient",
+    "stop",
+    "clipPath",
+    "mask",
+];
+
+pub fn is_data_uri(logo: &str) -> bool {
+    logo.trim_start().starts_with("data:")
+}
+
+/// 校验并清理 data URI，返回可以放进 `<image href>` 的值
+pub fn sanitize(logo: &str) -> Result<String, AppError> {
+    let logo = logo.trim();
+    let (prefix, data) = if let Some(data) = logo.strip_prefix(SVG_PREFIX) {
+        (SVG_PREFIX, data)
+    } else if let Some(data) = logo.strip_prefix(PNG_PREFIX) {
+        (PNG_PREFIX, data)
+    } else {
+        return Err(AppError::InvalidInput(
+            "`logo` data URIs must be base64-encoded image/svg+xml or image/png.".to_string(),
+        ));
+    };
+    // base64 每 4 个字符解码为 3 字节，先按长度拒绝过大的输入
+    if data.len() / 4 * 3 > MAX_LOGO_BYTES + 2 {
+        return Err(too_large());
+    }
+    let bytes = STANDARD
+        .decode(data)
+        .map_err(|_| AppError::InvalidInput("`logo` is not valid base64.".to_string()))?;
+    if bytes.len() > MAX_LOGO_BYTES {
+        return Err(too_large());
+    }
+    if prefix == PNG_PREFIX {
+        if !bytes.starts_with(PNG_SIGNATURE) {
+            return Err(AppError::InvalidInput(
+                "`logo` is not a PNG image.".to_string(),
+            ));
+        }
+        return Ok(format!("{}{}", PNG_PREFIX, STANDARD.encode(bytes)));
+    }
+    let svg = sanitize_svg(&bytes)?;
+    Ok(format!("{}{}", SVG_PREFIX, STANDARD.encode(svg)))
+}
+
+fn too_large() -> AppError {
+    AppError::InvalidInput(format!("`logo` must not exceed {} bytes.", MAX_LOGO_BYTES))
+}
+
+/// 按白名单重建 SVG
+fn sanitize_svg(bytes: &[u8]) -> Result<String, AppError> {
+    let invalid = || AppError::InvalidInput("`logo` is not a valid SVG image.".to_string());
+    let mut out = String::with_capacity(bytes.len());
+    // 大于 0 时表示正在跳过的元素层数
+    let mut skipping = 0usize;
+    let mut open = Vec::new();
+    for event in EventReader::new(bytes) {
+        match event.map_err(|_| invalid())? {
+            XmlEvent::StartElement {
+                name, attributes, ..
+            } => {
+                let allowed = ALLOWED_ELEMENTS.contains(&name.local_name.as_str())
+                    && (open.is_empty()) == (name.local_name == "svg");
+                if skipping > 0 || !allowed {
+                    skipping += 1;
+                    continue;
+                }
+                out.push('<');
+                out.push_str(&name.local_name);
+                if open.is_empty() {
+                    out.push_str(r#" xmlns="http://www.w3.org/2000/svg""#);
+                }
+                for attribute in attributes {
+                    let local = attribute.name.local_name.as_str();
+                    let value = attribute.value.as_str();
+                    let local = match attribute.name.prefix.as_deref() {
+                        None => local,
+                        // xlink:href 统一写成 SVG 2 的 href
+                        Some("xlink") if local == "href" => local,
+                        Some(_) => continue,
+                    };
+                    if is_safe_attribute(local, value) {
+                        out.push_str(&format!(" {}=\"{}\"", local, escape(value, true)));
+                    }
+                }
+                out.push('>');
+                open.push(name.local_name);
+            }
+            XmlEvent::EndElement { .. } => {
+                if skipping > 0 {
+                    skipping -= 1;
+                } else if let Some(name) = open.pop() {
+                    out.push_str(&format!("</{}>", name));
+                }
+            }
+            XmlEvent::Characters(text) | XmlEvent::CData(text)
+                if skipping == 0 && !open.is_empty() =>
+            {
+                out.push_str(&escape(&text, false));
+            }
+            _ => {}
+        }
+    }
+    if !out.starts_with("<svg") || !open.is_empty() {
+        return Err(invalid());
+    }
+    Ok(out)
+}
+
+/// 丢弃事件处理器、外部引用以及可能加载资源的值
+fn is_safe_attribute(name: &str, value: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    let value = value.to_ascii_lowercase();
+    if name.starts_with("on") || name == "style" || value.contains("javascript:") {
+        return false;
+    }
+    if name == "href" {
+        return value.trim_start().starts_with('#');
+    }
+    // 只允许引用文档内部的 `url(#id)`
+    value
+        .match_indices("url(")
+        .all(|(start, _)| value[start + 4..].trim_start().starts_with('#'))
+}
+
+fn escape(text: &str, attribute: bool) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' if attribute => out.push_str("&quot;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clean(svg: &str) -> String {
+        sanitize_svg(svg.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn scripts_and_foreign_content_are_dropped() {
+        let svg = clean(
+            r#"<svg viewBox="0 0 24 24"><script>alert(1)</script><path d="M0 0h24v24H0z"/><foreignObject><div xmlns="http://www.w3.org/1999/xhtml"><script>alert(2)</script></div></foreignObject></svg>"#,
+        );
+        assert_eq!(
+            svg,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 24 24"><path d="M0 0h24v24H0z"></path></svg>"#
+        );
+    }
+
+    #[test]
+    fn handlers_and_styles_are_dropped() {
+        let svg = clean(
+            r#"<svg onload="alert(1)" style="background:url(http://evil.example/x)"><rect ONCLICK="alert(2)" style="fill:red" width="4" height="4"/></svg>"#,
+        );
+        assert_eq!(
+            svg,
+            r#"<svg xmlns="http://www.w3.org/2000/svg"><rect width="4" height="4"></rect></svg>"#
+        );
+    }
+
+    #[test]
+    fn only_internal_references_are_kept() {
+        let svg = clean(
+            r##"<svg xmlns:xlink="http://www.w3.org/1999/xlink"><use href="https://evil.example/a.svg#icon"/><use xlink:href="#icon"/><rect fill="url(http://evil.example/paint)"/><rect fill="url( #grad)"/><path d="javascript:alert(1)"/></svg>"##,
+        );
+        assert_eq!(
+            svg,
+            r##"<svg xmlns="http://www.w3.org/2000/svg"><use></use><use href="#icon"></use><rect></rect><rect fill="url( #grad)"></rect><path></path></svg>"##
+        );
+    }
+
+    #[test]
+    fn text_is_escaped_again() {
+        let svg = clean("<svg><title>a &amp; &lt;b&gt;</title></svg>");
+        assert_eq!(
+            svg,
+            r#"<svg xmlns="http://www.w3.org/2000/svg"><title>a &amp; &lt;b&gt;</title></svg>"#
+        );
+    }
+
+    #[test]
+    fn non_svg_documents_are_rejected() {
+        for input in ["<html><svg></svg></html>", "<svg><path>", "not xml", ""] {
+            assert!(matches!(
+                sanitize_svg(input.as_bytes()),
+                Err(AppError::InvalidInput(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn data_uris_are_checked() {
+        let svg = format!("{}{}", SVG_PREFIX, STANDARD.encode("<svg><script/></svg>"));
+        let cleaned = sanitize(&svg).unwrap();
+        let decoded = STANDARD
+            .decode(cleaned.strip_prefix(SVG_PREFIX).unwrap())
+            .unwrap();
+        assert_eq!(
+            decoded,
+            br#"<svg xmlns="http://www.w3.org/2000/svg"></svg>"#
+        );
+
+        let png = [PNG_SIGNATURE, b"rest of the image"].concat();
+        let png = format!("{}{}", PNG_PREFIX, STANDARD.encode(png));
+        assert_eq!(sanitize(&png).unwrap(), png);
+        let gif = format!(
+            "{}{}",
+            PNG_PREFIX,
+            STANDARD.encode(b"GIF89a\x01\x00\x01\x00")
+        );
+        assert!(matches!(sanitize(&gif), Err(AppError::InvalidInput(_))));
+        let svg_as_png = format!("{}{}", PNG_PREFIX, STANDARD.encode("<svg></svg>"));
+        assert!(sanitize(&svg_as_png).is_err());
+
+        assert!(sanitize("data:image/gif;base64,R0lGODlh").is_err());
+        assert!(sanitize(&format!("{}not base64!", SVG_PREFIX)).is_err());
+    }
+
+    #[test]
+    fn oversized_logos_are_rejected() {
+        let limit = [
+            PNG_SIGNATURE,
+            &vec![0; MAX_LOGO_BYTES - PNG_SIGNATURE.len()],
+        ]
+        .concat();
+        assert!(sanitize(&format!("{}{}", PNG_PREFIX, STANDARD.encode(&limit))).is_ok());
+        let over = [limit.as_slice(), &[0]].concat();
+        let message = match sanitize(&format!("{}{}", PNG_PREFIX, STANDARD.encode(over))) {
+            Err(AppError::InvalidInput(message)) => message,
+            other => panic!("expected a size error, got {:?}", other.map(|_| ())),
+        };
+        assert!(message.contains("must not exceed"));
+        // 明显过大的输入在解码前按长度拒绝
+        let huge = "A".repeat(MAX_LOGO_BYTES * 2);
+        assert!(sanitize(&format!("{}{}", SVG_PREFIX, huge)).is_err());
+    }
+}
@@ -1,12 +1,45 @@
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
+use api::ws::HitEvent;
+use axum::{extract::Request, ServiceExt};
+use config::AppConfig;
 use dotenvy::dotenv;
+use grand_total::GrandTotal;
+use key_metrics::KeyMetrics;
 use sqlx::postgres::PgPool;
 use std::{env, net::SocketAddr, sync::Arc};
 use tokio::sync::broadcast;
+use tower::Layer;
+use tower_http::normalize_path::NormalizePathLayer;
 use tracing::info;
 
 mod api;
+mod badge;
+mod bot_ips;
+mod captcha;
+mod challenge;
+mod client_ip;
+mod coalesce;
+mod config;
+mod dedup;
+mod denylist;
 mod error;
+mod events;
+mod format;
+mod geo;
+mod grand_total;
+mod hll;
+mod hot_keys;
+mod key_metrics;
+mod key_rate_limits;
+mod locale;
+mod logo;
+mod noise;
+mod privacy;
+mod sampling;
+mod slow_query;
+mod spikes;
+mod svg;
+mod unique;
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -16,6 +49,14 @@ async fn main() -> Result<()> {
     // --- 配置 ---
     let database_url =
         env::var("DATABASE_URL").context("DATABASE_URL environment variable must be set")?;
+    // 查询依赖 Postgres 方言，其他数据库在启动时直接拒绝
+    let scheme = database_url.split_once("://").map(|(scheme, _)| scheme);
+    if !matches!(scheme, Some("postgres" | "postgresql")) {
+        bail!(
+            "Unsupported DATABASE_URL scheme `{}`: only PostgreSQL (postgres:// or postgresql://) is supported",
+            scheme.unwrap_or_default()
+        );
+    }
     let host = env::var("HOST").unwrap_or_else(|_| "127.0.0.1".to_string());
     let port_str = env::var("PORT").unwrap_or_else(|_| "3030".to_string());
     let port: u16 = port_str
@@ -24,6 +65,7 @@ async fn main() -> Result<()> {
     let addr: SocketAddr = format!("{}:{}", host, port)
         .parse()
         .with_context(|| format!("Invalid HOST/PORT combination: {}:{}", host, port))?;
+    let config = Arc::new(AppConfig::from_env()?);
 
     // --- 数据库连接池 ---
     info!("Connecting to database...");
@@ -31,13 +73,59 @@ async fn main() -> Result<()> {
         .await
         .context("Failed to create PostgreSQL connection pool")?;
     info!("Database connection pool established.");
+    for (key, zone) in &config.key_timezones {
+        if !api::stats::timezone_exists(&pool, zone).await? {
+            bail!("Unknown time zone in KEY_TIMEZONES for `{}`: {}", key, zone);
+        }
+    }
 
     // --- 广播通道 ---
-    let (tx, _) = broadcast::channel::<String>(100);
-    let broadcaster = Arc::new(tx);
+    let (tx, _) = broadcast::channel::<HitEvent>(100);
+    let ws_broadcaster = Arc::new(tx);
+    let broadcaster = if config.broadcast_coalesce.is_zero() {
+        ws_broadcaster.clone()
+    } else {
+        let (ingress_tx, ingress_rx) = broadcast::channel::<HitEvent>(4096);
+        tokio::spawn(coalesce::run(
+            ingress_rx,
+            ws_broadcaster.clone(),
+            config.broadcast_coalesce,
+        ));
+        Arc::new(ingress_tx)
+    };
+
+    // --- 后台任务 ---
+    let key_metrics = Arc::new(KeyMetrics::default());
+    if config.metrics_keys_enabled() {
+        tokio::spawn(key_metrics::run(
+            pool.clone(),
+            config.clone(),
+            key_metrics.clone(),
+        ));
+    }
+
+    let grand_total = Arc::new(GrandTotal::default());
+    tokio::spawn(grand_total::run(
+        pool.clone(),
+        config.clone(),
+        grand_total.clone(),
+    ));
+
+    tokio::spawn(unique::run(pool.clone()));
+    if config.hit_events_sample_rate.is_some() {
+        tokio::spawn(events::run(pool.clone(), config.hit_events_retention));
+    }
 
     // --- 路由与服务启动 ---
-    let app = api::create_router(pool, broadcaster.clone());
+    let strip_trailing_slash = config.key_strip_trailing_slash;
+    let app = api::create_router(
+        pool,
+        broadcaster,
+        ws_broadcaster,
+        config,
+        key_metrics,
+        grand_total,
+    );
 
     info!("Starting server, listening on http://{}", addr);
     info!("Access Scalar UI at http://{}/scalar", addr);
@@ -48,9 +136,22 @@ async fn main() -> Result<()> {
         .await
         .with_context(|| format!("Failed to bind to address {}", addr))?;
 
-    axum::serve(listener, app.into_make_service())
+    if strip_trailing_slash {
+        let app = NormalizePathLayer::trim_trailing_slash().layer(app);
+        axum::serve(
+            listener,
+            ServiceExt::<Request>::into_make_service_with_connect_info::<SocketAddr>(app),
+        )
+        .await
+        .context("Web server failed")?;
+    } else {
+        axum::serve(
+            listener,
+            app.into_make_service_with_connect_info::<SocketAddr>(),
+        )
         .await
         .context("Web server failed")?;
+    }
 
     Ok(())
 }
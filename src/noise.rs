@@ -0,0 +1,59 @@
+//! 公开计数的有界噪声
+//!
+//! For keys with a noise bound `b` (`COUNT_NOISE`, or a `KEY_COUNT_NOISE` entry), the
+//! totals shown by `/hits`, `/svg`, `/badge`, `/count`, `/stats`, `/sprite` and WebSocket
+//! broadcasts are shifted by a pseudo-random offset in `-b..=b`, clamped at 0. Stored counts
+//! stay exact, and so does the daily series of `/stats`, which therefore requires the admin
+//! token for these keys. The offset is an HMAC of the key and its exact total, so
+//! refreshing a badge returns the same value and averaging repeated requests reveals
+//! nothing more than one request does.
+//!
+//! The HMAC key is derived from `VISITOR_SALT` when set, so all instances show the same
+//! value; otherwise it is random, and each instance and restart picks different offsets.
+
+use crate::config::AppConfig;
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use sha2::Sha256;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// 生成噪声偏移
+pub struct CountNoise {
+    secret: Vec<u8>,
+}
+
+impl CountNoise {
+    pub fn new(config: &AppConfig) -> Self {
+        let secret = match &config.visitor_salt {
+            Some(salt) => {
+                let mut mac = HmacSha256::new_from_slice(salt.as_bytes())
+                    .expect("HMAC accepts any key length");
+                mac.update(b"count-noise");
+                mac.finalize().into_bytes().to_vec()
+            }
+            None => {
+                let mut secret = vec![0u8; 32];
+                rand::rng().fill_bytes(&mut secret);
+                secret
+            }
+        };
+        Self { secret }
+    }
+
+    /// 给总数加上 `-bound..=bound` 内的确定性偏移，结果不小于 0
+    pub fn apply(&self, stored_key: &str, total: i64, bound: u32) -> i64 {
+        if bound == 0 {
+            return total;
+        }
+        let mut mac =
+            HmacSha256::new_from_slice(&self.secret).expect("HMAC accepts any key length");
+        mac.update(stored_key.as_bytes());
+        mac.update(&total.to_be_bytes());
+        let digest = mac.finalize().into_bytes();
+        let value = u64::from_be_bytes(digest[..8].try_into().expect("digest is 32 bytes"));
+        let span = 2 * u64::from(bound) + 1;
+        let offset = (value % span) as i64 - i64::from(bound);
+        total.saturating_add(offset).max(0)
+    }
+}
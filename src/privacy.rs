@@ -0,0 +1,83 @@
+//! 带每日轮换盐值的客户端 IP 哈希
+//!
+//! Dedup windows, `?mode=unique` and the `TRACK_UNIQUES` sketches only need to tell
+//! visitors apart, never to recover their address. [`IpHasher`] hashes client IPs with
+//! HMAC-SHA256 under a salt that changes at every day boundary (following
+//! `WINDOW_OFFSET`) and is only ever held in memory, so a stored hash cannot be matched
+//! against a later one or brute-forced back to an address once its day has passed.
+//!
+//! With `VISITOR_SALT`, each day's salt is derived from it, so all instances and restarts
+//! agree on a day's hashes. Without it the salt is random, and every instance starts a
+//! fresh one after a restart.
+
+use crate::config::AppConfig;
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use sha2::Sha256;
+use std::net::IpAddr;
+use std::sync::Mutex;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// 每日轮换盐值的 IP 哈希器
+pub struct IpHasher {
+    secret: Option<Vec<u8>>,
+    window_offset_minutes: i32,
+    /// 当前日序号及其盐值
+    salt: Mutex<Option<(i64, [u8; 32])>>,
+}
+
+impl IpHasher {
+    pub fn new(config: &AppConfig) -> Self {
+        Self {
+            secret: config
+                .visitor_salt
+                .as_ref()
+                .map(|salt| salt.as_bytes().to_vec()),
+            window_offset_minutes: config.window_offset_minutes,
+            salt: Mutex::default(),
+        }
+    }
+
+    /// 当天盐值下的 HMAC-SHA256
+    pub fn digest(&self, ip: IpAddr) -> [u8; 32] {
+        let mut mac =
+            HmacSha256::new_from_slice(&self.day_salt()).expect("HMAC accepts any key length");
+        mac.update(ip.to_string().as_bytes());
+        mac.finalize().into_bytes().into()
+    }
+
+    /// 摘要的前 64 位
+    pub fn hash(&self, ip: IpAddr) -> u64 {
+        let digest = self.digest(ip);
+        u64::from_be_bytes(digest[..8].try_into().expect("digest is 32 bytes"))
+    }
+
+    /// 返回当天的盐值，跨过日界时生成新的
+    fn day_salt(&self) -> [u8; 32] {
+        let day =
+            (Utc::now().timestamp() - i64::from(self.window_offset_minutes) * 60).div_euclid(86400);
+        let mut current = self.salt.lock().unwrap();
+        if let Some((salt_day, salt)) = *current {
+            if salt_day == day {
+                return salt;
+            }
+        }
+        let salt = match &self.secret {
+            Some(secret) => {
+                let mut mac =
+                    HmacSha256::new_from_slice(secret).expect("HMAC accepts any key length");
+                mac.update(format!("day:{}", day).as_bytes());
+                mac.finalize().into_bytes().into()
+            }
+            None => {
+                let mut salt = [0u8; 32];
+                rand::rng().fill_bytes(&mut salt);
+                salt
+            }
+        };
+        *current = Some((day, salt));
+        salt
+    }
+}
@@ -0,0 +1,30 @@
+//! 热点 key 的抽样计数
+//!
+//! For keys listed in `KEY_SAMPLE_RATES` with rate `N`, only every `N`th admitted hit is
+//! written, with an increment of `N`, so the stored total stays close to the real one
+//! while the key's row is updated `N` times less often. The other hits are answered with
+//! the stored total. Request counts are kept per process, so with several instances
+//! each one writes every `N`th hit it sees.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// 每个抽样 key 自上次写入以来见过的请求数
+#[derive(Default)]
+pub struct Sampler {
+    seen: Mutex<HashMap<String, u32>>,
+}
+
+impl Sampler {
+    /// 本次请求是否应写入（每 `rate` 次一次）
+    pub fn admit(&self, key: &str, rate: u32) -> bool {
+        let mut seen = self.seen.lock().unwrap();
+        let count = seen.entry(key.to_string()).or_insert(0);
+        *count += 1;
+        if *count >= rate {
+            *count = 0;
+            return true;
+        }
+        false
+    }
+}
@@ -0,0 +1,43 @@
+//! 慢查询日志
+//!
+//! With `SLOW_QUERY_MS` set, the counter upserts are timed and any that take longer are
+//! logged at `warn` level with the stored key and the duration, which points at hot keys
+//! contending for the same `(key, minute_window)` row. The number of slow queries is
+//! exported on `/metrics/keys`. Without the setting the queries are not timed at all.
+
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+static SLOW_QUERIES: AtomicU64 = AtomicU64::new(0);
+
+/// 进程启动以来的慢查询次数
+pub fn count() -> u64 {
+    SLOW_QUERIES.load(Ordering::Relaxed)
+}
+
+/// 执行查询，耗时超过阈值时记录
+pub async fn timed<F: Future>(
+    threshold: Option<Duration>,
+    query: &str,
+    stored_key: &str,
+    future: F,
+) -> F::Output {
+    let Some(threshold) = threshold else {
+        return future.await;
+    };
+    let start = Instant::now();
+    let output = future.await;
+    let elapsed = start.elapsed();
+    if elapsed > threshold {
+        SLOW_QUERIES.fetch_add(1, Ordering::Relaxed);
+        warn!(
+            "Slow query {} for key {}: {} ms",
+            query,
+            stored_key,
+            elapsed.as_millis()
+        );
+    }
+    output
+}
@@ -0,0 +1,166 @@
+//! 流量突增检测与自动限流
+//!
+//! With `SPIKE_FACTOR` set, a background task checks the `counters` minute windows once a
+//! minute. A key is flagged when its hits in the last complete minute reach
+//! `SPIKE_MIN_HITS` and exceed `SPIKE_FACTOR` times its average minute over the hour
+//! before. Flagged keys are throttled for `SPIKE_THROTTLE_MINUTES`: per minute, only hits
+//! up to `SPIKE_FACTOR` times the baseline (at least `SPIKE_MIN_HITS`) are counted, the
+//! rest are answered without counting. A key that keeps spiking stays throttled.
+//!
+//! Every instance runs the check on the shared rows and throttles on its own. Admins can
+//! list throttled keys and lift a throttle through `/admin/spikes`; a lifted key is not
+//! flagged again for one throttle period.
+
+use crate::config::AppConfig;
+use chrono::{DateTime, DurationRound, TimeDelta, Utc};
+use sqlx::postgres::PgPool;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tracing::{debug, warn};
+
+/// 检测间隔
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+/// 计算基线的分钟数
+const BASELINE_MINUTES: i64 = 60;
+
+/// 被限流 key 的状态
+#[derive(Debug, Clone)]
+pub struct Throttle {
+    /// Hits counted per minute while throttled
+    pub cap_per_minute: i64,
+    /// Hits in the minute that triggered the throttle
+    pub spike_hits: i64,
+    /// Average hits per minute in the hour before the spike
+    pub baseline: f64,
+    pub until: DateTime<Utc>,
+}
+
+#[derive(Default)]
+struct State {
+    throttled: HashMap<String, Throttle>,
+    /// 管理员解除限流后暂不检测的 key
+    exempt: HashMap<String, DateTime<Utc>>,
+}
+
+/// 内存中的限流状态，按存储 key 索引
+#[derive(Default)]
+pub struct SpikeGuard {
+    state: RwLock<State>,
+}
+
+impl SpikeGuard {
+    /// 当前生效的每分钟上限
+    pub fn cap(&self, stored_key: &str) -> Option<i64> {
+        let state = self.state.read().unwrap();
+        state
+            .throttled
+            .get(stored_key)
+            .filter(|throttle| throttle.until > Utc::now())
+            .map(|throttle| throttle.cap_per_minute)
+    }
+
+    /// 当前被限流的 key
+    pub fn list(&self) -> Vec<(String, Throttle)> {
+        let now = Utc::now();
+        let state = self.state.read().unwrap();
+        let mut throttled: Vec<_> = state
+            .throttled
+            .iter()
+            .filter(|(_, throttle)| throttle.until > now)
+            .map(|(key, throttle)| (key.clone(), throttle.clone()))
+            .collect();
+        throttled.sort_by_key(|(_, throttle)| std::cmp::Reverse(throttle.spike_hits));
+        throttled
+    }
+
+    /// 解除限流，并在 `exempt_until` 之前不再检测该 key；返回之前是否被限流
+    pub fn lift(&self, stored_key: &str, exempt_until: DateTime<Utc>) -> bool {
+        let mut state = self.state.write().unwrap();
+        state.exempt.insert(stored_key.to_string(), exempt_until);
+        state
+            .throttled
+            .remove(stored_key)
+            .is_some_and(|throttle| throttle.until > Utc::now())
+    }
+}
+
+/// 定期检测突增的后台任务，查询失败时保留当前状态
+pub async fn run(pool: PgPool, config: Arc<AppConfig>, guard: Arc<SpikeGuard>) {
+    let Some(factor) = config.spike_factor else {
+        return;
+    };
+    let mut interval = tokio::time::interval(CHECK_INTERVAL);
+    loop {
+        interval.tick().await;
+        let now = Utc::now();
+        let minute = now
+            .duration_trunc(TimeDelta::minutes(1))
+            .expect("minute truncation is in range");
+        let last_minute = minute - TimeDelta::minutes(1);
+        let rows = sqlx::query!(
+            r#"
+            SELECT
+                key,
+                COALESCE(SUM(count) FILTER (WHERE minute_window >= $1), 0)::BIGINT AS "recent!",
+                COALESCE(SUM(count) FILTER (WHERE minute_window < $1), 0)::BIGINT AS "previous!"
+            FROM counters
+            WHERE minute_window >= $2 AND minute_window < $3
+            GROUP BY key
+            HAVING COALESCE(SUM(count) FILTER (WHERE minute_window >= $1), 0)::BIGINT >= $4
+            "#,
+            last_minute,
+            last_minute - TimeDelta::minutes(BASELINE_MINUTES),
+            minute,
+            config.spike_min_hits
+        )
+        .fetch_all(&pool)
+        .await;
+        let rows = match rows {
+            Ok(rows) => rows,
+            Err(e) => {
+                warn!("Failed to check for traffic spikes: {}", e);
+                continue;
+            }
+        };
+
+        let until = now + config.spike_throttle;
+        let mut state = guard.state.write().unwrap();
+        state.throttled.retain(|_, throttle| throttle.until > now);
+        state.exempt.retain(|_, exempt_until| *exempt_until > now);
+        for row in rows {
+            let baseline = row.previous as f64 / BASELINE_MINUTES as f64;
+            if (row.recent as f64) <= factor * baseline.max(1.0)
+                || state.exempt.contains_key(&row.key)
+            {
+                continue;
+            }
+            let cap_per_minute = ((factor * baseline).ceil() as i64).max(config.spike_min_hits);
+            match state.throttled.get_mut(&row.key) {
+                // 依然异常：延长限流，但不按被拉高的基线放宽上限
+                Some(throttle) => {
+                    throttle.until = until;
+                    debug!("Key {} is still spiking, throttle extended", row.key);
+                }
+                None => {
+                    warn!(
+                        "Traffic spike on {}: {} hits in the last minute against a baseline of {:.1}/minute, throttling to {}/minute until {}",
+                        row.key, row.recent, baseline, cap_per_minute, until
+                    );
+                    state.throttled.insert(
+                        row.key,
+                        Throttle {
+                            cap_per_minute,
+                            spike_hits: row.recent,
+                            baseline,
+                            until,
+                        },
+                    );
+                }
+            }
+        }
+        if !state.throttled.is_empty() {
+            debug!("{} keys are throttled", state.throttled.len());
+        }
+    }
+}
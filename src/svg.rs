@@ -0,0 +1,504 @@
+//! SVG 输出后处理
+
+use crate::format::escape_xml;
+use sha2::{Digest, Sha256};
+
+/// 压缩 SVG：去掉 XML 声明、首尾空白以及标签之间的缩进换行
+///
+/// Whitespace between tags is only dropped when it contains a line break, so
+/// intentional spaces inside `<text>` elements are preserved.
+pub fn minify(svg: &str) -> String {
+    let mut svg = svg.trim();
+    if svg.starts_with("<?xml") {
+        if let Some(end) = svg.find("?>") {
+            svg = svg[end + 2..].trim_start();
+        }
+    }
+
+    let mut out = String::with_capacity(svg.len());
+    let mut rest = svg;
+    while let Some(start) = rest.find('>') {
+        out.push_str(&rest[..=start]);
+        rest = &rest[start + 1..];
+        let gap = rest.len() - rest.trim_start().len();
+        if gap > 0 && rest.trim_start().starts_with('<') && rest[..gap].contains('\n') {
+            rest = &rest[gap..];
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// 给 SVG 内部的 `id` 及其引用加前缀，避免多个徽章内嵌到同一文档时冲突
+///
+/// The `hits-` class names and keyframes added by the dark theme and animations are
+/// prefixed as well, since inline `<style>` rules apply to the whole document.
+pub fn prefix_ids(svg: &str, prefix: &str) -> String {
+    let svg = svg
+        .replace(" id=\"", &format!(" id=\"{}", prefix))
+        .replace("url(#", &format!("url(#{}", prefix))
+        .replace("href=\"#", &format!("href=\"#{}", prefix));
+    let scoped = format!("{}hits-", prefix);
+    let mut out = String::with_capacity(svg.len());
+    let mut rest = svg.as_str();
+    loop {
+        let class = rest
+            .find(" class=\"")
+            .map(|start| (start, " class=\"", "\""));
+        let style = rest
+            .find("<style>")
+            .map(|start| (start, "<style>", "</style>"));
+        let next = match (class, style) {
+            (Some(class), Some(style)) => Some(if class.0 < style.0 { class } else { style }),
+            (class, style) => class.or(style),
+        };
+        let Some((start, open, close)) = next else {
+            break;
+        };
+        let value_start = start + open.len();
+        let Some(value_end) = rest[value_start..].find(close).map(|end| value_start + end) else {
+            break;
+        };
+        out.push_str(&rest[..value_start]);
+        out.push_str(&rest[value_start..value_end].replace("hits-", &scoped));
+        rest = &rest[value_end..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// 由内容生成的 `id` 前缀：相同徽章得到相同前缀，不同徽章的前缀不同
+pub fn content_prefix(svg: &str) -> String {
+    let digest = Sha256::digest(svg.as_bytes());
+    format!("h{}-", hex::encode(&digest[..4]))
+}
+
+/// XML 声明
+pub const XML_DECLARATION: &str = r#"<?xml version="1.0" encoding="UTF-8"?>"#;
+
+/// xlink 命名空间声明
+const XLINK_NAMESPACE: &str = r#" xmlns:xlink="http://www.w3.org/1999/xlink""#;
+
+/// 按配置调整输出：可选 XML 声明，可选 SVG 1.1 风格的 `xlink:href`
+///
+/// The renderer emits plain SVG 2 (`xmlns` only, `href` links, no declaration) for every
+/// style. `xlink` declares the namespace on the root element and rewrites every `href`
+/// to `xlink:href` for viewers and sanitizers that only understand SVG 1.1.
+pub fn with_namespaces(svg: &str, xml_declaration: bool, xlink: bool) -> String {
+    let mut svg = svg.to_string();
+    if xlink {
+        svg = svg.replace(" href=\"", " xlink:href=\"");
+        if let Some(start) = svg.find("<svg") {
+            if !svg[start..]
+                .split('>')
+                .next()
+                .unwrap_or_default()
+                .contains("xmlns:xlink")
+            {
+                svg.insert_str(start + 4, XLINK_NAMESPACE);
+            }
+        }
+    }
+    if xml_declaration && !svg.starts_with("<?xml") {
+        svg.insert_str(0, XML_DECLARATION);
+    }
+    svg
+}
+
+/// 在根 `<svg>` 元素后插入注释，`text` 中的 `--` 会被去掉以保证注释合法
+pub fn insert_comment(svg: &str, text: &str) -> String {
+    let Some(start) = svg.find("<svg") else {
+        return svg.to_string();
+    };
+    let Some(end) = svg[start..].find('>').map(|end| start + end + 1) else {
+        return svg.to_string();
+    };
+    format!(
+        "{}<!-- {} -->{}",
+        &svg[..end],
+        text.replace("--", ""),
+        &svg[end..]
+    )
+}
+
+/// 替换第一个 `<image>` 元素的 `href`，用于换上自定义 logo
+pub fn replace_image_href(svg: &str, href: &str) -> String {
+    let Some(image) = svg.find("<image ") else {
+        return svg.to_string();
+    };
+    let Some(tag_end) = svg[image..].find('>').map(|end| image + end) else {
+        return svg.to_string();
+    };
+    let Some(value_start) = svg[image..tag_end]
+        .find(" href=\"")
+        .map(|start| image + start + " href=\"".len())
+    else {
+        return svg.to_string();
+    };
+    let Some(value_end) = svg[value_start..].find('"').map(|end| value_start + end) else {
+        return svg.to_string();
+    };
+    format!("{}{}{}", &svg[..value_start], href, &svg[value_end..])
+}
+
+/// 标签字符（U+E0000 起）与 ASCII 的码位差
+const TAG_OFFSET: u32 = 0xE0000;
+
+/// 需要转义的字符
+const MARKUP_CHARS: [char; 5] = ['&', '<', '>', '"', '\''];
+
+/// 把需要转义的字符换成等宽的占位字符，作为渲染器的输入
+///
+/// The renderer inserts text verbatim and measures it character by character, so escaped
+/// text would be measured by its references (`&#38;` as five characters). Its width tables
+/// give the tag characters U+E0020..U+E007E the widths of their ASCII counterparts, so
+/// `&` is swapped for U+E0026 and so on: the badge is laid out for the real text, and
+/// upper-casing in `for-the-badge` leaves the placeholders alone. [`unmask_markup`] turns
+/// them into character references after rendering. `for-the-badge` adds letter spacing
+/// per UTF-8 byte rather than per character, so there each placeholder still widens the
+/// badge by a few pixels, as any non-ASCII character does. [`crate::format::sanitize_text`]
+/// drops tag characters from user text, so every placeholder comes from here.
+pub fn mask_markup(text: &str) -> String {
+    text.chars()
+        .map(|c| {
+            if MARKUP_CHARS.contains(&c) {
+                char::from_u32(TAG_OFFSET + u32::from(c)).unwrap_or(c)
+            } else {
+                c
+            }
+        })
+        .collect()
+}
+
+/// 把渲染结果中的占位字符换回字符引用
+pub fn unmask_markup(svg: &str) -> String {
+    let mut out = String::with_capacity(svg.len());
+    for c in svg.chars() {
+        let original = u32::from(c)
+            .checked_sub(TAG_OFFSET)
+            .and_then(char::from_u32)
+            .filter(|original| MARKUP_CHARS.contains(original));
+        match original {
+            Some(original) => out.push_str(&escape_xml(&original.to_string())),
+            None => out.push(c),
+        }
+    }
+    out
+}
+
+/// 计数动画每一帧的时长（秒）
+const FRAME_SECONDS: f64 = 0.08;
+
+/// 淡入的时长（秒）
+const FADE_SECONDS: f64 = 0.4;
+
+/// 给徽章右侧文本加上计数或淡入动画
+///
+/// Every `<text>` element showing `message` (the text and its shadow) gets one copy per
+/// entry of `frames`, shown one after another before the real text fades in. With no frames
+/// the text only fades in. Frame copies drop `textLength` so shorter values keep their
+/// natural width, and the animation is skipped for viewers who prefer reduced motion.
+/// Returns `svg` unchanged when the message cannot be found or also appears in the label.
+pub fn animate_message(svg: &str, message: &str, frames: &[String]) -> String {
+    let content = format!(">{}</text>", escape_xml(message));
+    let mut spans = Vec::new();
+    let mut search = 0;
+    while let Some(found) = svg[search..].find(&content) {
+        let end = search + found + content.len();
+        let Some(start) = svg[..search + found].rfind("<text") else {
+            return svg.to_string();
+        };
+        spans.push((start, end));
+        search = end;
+    }
+    // 标签与文本相同时无法区分两者
+    if spans.is_empty() || spans.len() == svg.matches("<text").count() {
+        return svg.to_string();
+    }
+    let mut out = String::with_capacity(svg.len() * (frames.len() + 1));
+    let mut last = 0;
+    for &(start, end) in &spans {
+        out.push_str(&svg[last..start]);
+        let element = &svg[start..end];
+        let tag_end = element.find('>').unwrap_or(0);
+        let (tag, _) = element.split_at(tag_end);
+        for (index, frame) in frames.iter().enumerate() {
+            let frame_tag = remove_attribute(&remove_attribute(tag, "textLength"), "id");
+            out.push_str(&add_class(
+                &frame_tag,
+                &format!("hits-frame hits-frame-{}", index),
+            ));
+            out.push_str(&format!(">{}</text>", escape_xml(frame)));
+        }
+        out.push_str(&add_class(tag, "hits-final"));
+        out.push_str(&element[tag_end..]);
+        last = end;
+    }
+    out.push_str(&svg[last..]);
+
+    let mut rules = String::from(
+        "@keyframes hits-show{from,to{opacity:1}}@keyframes hits-in{from{opacity:0}to{opacity:1}}",
+    );
+    for index in 0..frames.len() {
+        rules.push_str(&format!(
+            ".hits-frame-{}{{animation:hits-show {}s {:.2}s}}",
+            index,
+            FRAME_SECONDS,
+            index as f64 * FRAME_SECONDS
+        ));
+    }
+    rules.push_str(&format!(
+        ".hits-final{{animation:hits-in {}s {:.2}s both}}",
+        FADE_SECONDS,
+        frames.len() as f64 * FRAME_SECONDS
+    ));
+    let style = format!(
+        "<style>.hits-frame{{opacity:0}}@media (prefers-reduced-motion:no-preference){{{}}}</style>",
+        rules
+    );
+    let Some(root) = out.find("<svg") else {
+        return svg.to_string();
+    };
+    let Some(root_end) = out[root..].find('>').map(|end| root + end + 1) else {
+        return svg.to_string();
+    };
+    out.insert_str(root_end, &style);
+    out
+}
+
+/// 去掉开始标签中的属性
+fn remove_attribute(tag: &str, name: &str) -> String {
+    let needle = format!(" {}=\"", name);
+    let Some(start) = tag.find(&needle) else {
+        return tag.to_string();
+    };
+    let value_start = start + needle.len();
+    match tag[value_start..].find('"') {
+        Some(end) => format!("{}{}", &tag[..start], &tag[value_start + end + 1..]),
+        None => tag.to_string(),
+    }
+}
+
+/// 给开始标签追加 class，已有 `class` 属性时合并
+fn add_class(tag: &str, class: &str) -> String {
+    match tag.find(" class=\"") {
+        Some(start) => {
+            let value_start = start + " class=\"".len();
+            format!("{}{} {}", &tag[..value_start], class, &tag[value_start..])
+        }
+        None => format!("{} class=\"{}\"", tag, class),
+    }
+}
+
+/// 深色模式规则使用的 class 前缀
+const DARK_CLASS: &str = "hits-dark-";
+
+/// 加上随 `prefers-color-scheme` 切换的深色配色
+///
+/// `light` and `dark` must be the same badge rendered with the light and dark color sets.
+/// Every `fill` that differs between them is tagged with a class, and a `<style>` block
+/// switches those elements to their dark value when the viewer prefers a dark scheme.
+/// Returns `light` unchanged when the two renders do not line up.
+pub fn with_dark_theme(light: &str, dark: &str) -> String {
+    let light_parts: Vec<&str> = light.split("fill=\"").collect();
+    let dark_parts: Vec<&str> = dark.split("fill=\"").collect();
+    if light_parts.len() != dark_parts.len() {
+        return light.to_string();
+    }
+    let value = |part: &str| part.find('"').map(|end| part[..end].to_string());
+    let mut out = String::with_capacity(light.len() + 256);
+    let mut rules = String::new();
+    out.push_str(light_parts[0]);
+    for (index, (light_part, dark_part)) in light_parts.iter().zip(&dark_parts).enumerate().skip(1)
+    {
+        out.push_str("fill=\"");
+        let (Some(light_fill), Some(dark_fill)) = (value(light_part), value(dark_part)) else {
+            return light.to_string();
+        };
+        out.push_str(&light_fill);
+        out.push('"');
+        // 只接受颜色值中会出现的字符，防止注入样式表
+        let safe = dark_fill
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "#(),.% ".contains(c));
+        if light_fill != dark_fill && safe {
+            out.push_str(&format!(" class=\"{}{}\"", DARK_CLASS, index));
+            rules.push_str(&format!(".{}{}{{fill:{}}}", DARK_CLASS, index, dark_fill));
+        }
+        out.push_str(&light_part[light_fill.len() + 1..]);
+    }
+    if rules.is_empty() {
+        return out;
+    }
+    let Some(start) = out.find("<svg") else {
+        return light.to_string();
+    };
+    let Some(end) = out[start..].find('>').map(|end| start + end + 1) else {
+        return light.to_string();
+    };
+    out.insert_str(
+        end,
+        &format!(
+            "<style>@media (prefers-color-scheme:dark){{{}}}</style>",
+            rules
+        ),
+    );
+    out
+}
+
+/// 读取根 `<svg>` 元素的 `width` 与 `height`
+pub fn dimensions(svg: &str) -> Option<(f64, f64)> {
+    let start = svg.find("<svg")?;
+    let end = start + svg[start..].find('>')?;
+    let tag = &svg[start..end];
+    let attribute = |name: &str| -> Option<f64> {
+        let needle = format!(" {}=\"", name);
+        let value_start = tag.find(&needle)? + needle.len();
+        let value_end = value_start + tag[value_start..].find('"')?;
+        tag[value_start..value_end].parse().ok()
+    };
+    Some((attribute("width")?, attribute("height")?))
+}
+
+/// 把根 `<svg>` 元素的 `width`/`height` 乘以 `scale`
+///
+/// Must run after [`with_view_box`], whose viewBox keeps the original coordinates, so
+/// fonts, padding and shapes grow in proportion. Badges without a fixed size are returned
+/// unchanged.
+pub fn with_scale(svg: &str, scale: f64) -> String {
+    let Some((width, height)) = dimensions(svg) else {
+        return svg.to_string();
+    };
+    let Some(start) = svg.find("<svg") else {
+        return svg.to_string();
+    };
+    let Some(end) = svg[start..].find('>').map(|end| start + end) else {
+        return svg.to_string();
+    };
+    let mut tag = svg[start..end].to_string();
+    for (name, value) in [("width", width), ("height", height)] {
+        tag = remove_attribute(&tag, name);
+        tag.push_str(&format!(" {}=\"{}\"", name, value * scale));
+    }
+    format!("{}{}{}", &svg[..start], tag, &svg[end..])
+}
+
+/// 给根 `<svg>` 元素补上 `viewBox`，`scalable` 时再去掉固定的 `width`/`height`
+///
+/// The viewBox matches the original size, so text and shapes keep their positions and
+/// the badge scales uniformly with its container. Nested elements are left untouched.
+pub fn with_view_box(svg: &str, scalable: bool) -> String {
+    let Some((width, height)) = dimensions(svg) else {
+        return svg.to_string();
+    };
+    let Some(start) = svg.find("<svg") else {
+        return svg.to_string();
+    };
+    let Some(end) = svg[start..].find('>').map(|end| start + end) else {
+        return svg.to_string();
+    };
+    let mut tag = svg[start..end].to_string();
+    if scalable {
+        for name in ["width", "height"] {
+            let needle = format!(" {}=\"", name);
+            if let Some(value_start) = tag.find(&needle) {
+                let value_start = value_start + needle.len();
+                if let Some(value_end) = tag[value_start..].find('"') {
+                    tag.replace_range(value_start - needle.len()..value_start + value_end + 1, "");
+                }
+            }
+        }
+    }
+    if !tag.contains(" viewBox=\"") {
+        tag.insert_str(4, &format!(" viewBox=\"0 0 {} {}\"", width, height));
+    }
+    format!("{}{}{}", &svg[..start], tag, &svg[end..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::types::BadgeStyle;
+    use crate::badge;
+
+    fn params(label: &str) -> shields::BadgeParams<'_> {
+        shields::BadgeParams {
+            style: shields::BadgeStyle::Flat,
+            label: Some(label),
+            message: Some("42"),
+            label_color: None,
+            message_color: None,
+            link: None,
+            extra_link: None,
+            logo: None,
+            logo_color: None,
+        }
+    }
+
+    /// 与 `/svg` 相同的处理：占位排版后换回字符引用
+    fn render(label: &str, style: BadgeStyle) -> String {
+        let label = mask_markup(label);
+        let svg = badge::render(
+            style,
+            &shields::BadgeParams {
+                style: style.into(),
+                ..params(&label)
+            },
+        );
+        unmask_markup(&svg)
+    }
+
+    #[test]
+    fn escaped_text_is_not_widened() {
+        assert_eq!(
+            dimensions(&render("a&b", BadgeStyle::Flat)),
+            dimensions(&render("aXb", BadgeStyle::Flat))
+        );
+    }
+
+    #[test]
+    fn escaped_text_is_measured_as_displayed() {
+        let styles = BadgeStyle::ALL
+            .into_iter()
+            .filter(|style| !matches!(style, BadgeStyle::ForTheBadge));
+        for style in styles {
+            for label in ["a&b", "R&D", r#"<script>"a&b'"#] {
+                // 未转义的文本不是合法 SVG，但宽度正是显示时需要的宽度
+                let raw = badge::render(
+                    style,
+                    &shields::BadgeParams {
+                        style: style.into(),
+                        ..params(label)
+                    },
+                );
+                assert_eq!(
+                    dimensions(&render(label, style)),
+                    dimensions(&raw),
+                    "{label} in {style:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn for_the_badge_spacing_counts_bytes() {
+        // 字母间距按字节计算：每个占位字符多出 3 字节，宽度最多多出 3 × 1.25px
+        let width = |label| {
+            dimensions(&render(label, BadgeStyle::ForTheBadge))
+                .unwrap()
+                .0
+        };
+        let extra = width("a&b") - width("aXb");
+        assert!((0.0..=5.0).contains(&extra), "{extra}");
+    }
+
+    #[test]
+    fn placeholders_become_references() {
+        let svg = render(r#"<script>"a&b'"#, BadgeStyle::Flat);
+        assert!(svg.contains("&#60;script&#62;&#34;a&#38;b&#39;"));
+        assert!(!svg.contains("<script>"));
+        assert!(!svg
+            .chars()
+            .any(|c| ('\u{E0000}'..='\u{E007F}').contains(&c)));
+    }
+}
@@ -0,0 +1,76 @@
+//! 独立访客计数
+//!
+//! Hits sent with `?mode=unique` only count once per client and day. Clients are
+//! identified by the [`IpHasher`](crate::privacy::IpHasher) digest of their resolved IP,
+//! whose salt rotates with the day; the raw address is never stored. Days follow
+//! `WINDOW_OFFSET`, and rows older than the previous day are pruned in the background
+//! since they can no longer match.
+
+use crate::config::AppConfig;
+use crate::error::AppError;
+use sqlx::postgres::PgPool;
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// 清理过期访客记录的间隔
+const PRUNE_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// 按天记录访客
+pub struct UniqueVisitors {
+    window_offset_minutes: i32,
+}
+
+impl UniqueVisitors {
+    pub fn new(config: &AppConfig) -> Self {
+        Self {
+            window_offset_minutes: config.window_offset_minutes,
+        }
+    }
+
+    /// 记录访客摘要，返回是否为该 key 当天的首次访问
+    pub async fn first_visit(
+        &self,
+        pool: &PgPool,
+        stored_key: &str,
+        visitor: &[u8; 32],
+    ) -> Result<bool, AppError> {
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO unique_visitors (key, day_window, visitor)
+            VALUES (
+                $1,
+                DATE_TRUNC('day', NOW() - make_interval(mins => $2), 'UTC')
+                    + make_interval(mins => $2),
+                $3
+            )
+            ON CONFLICT (key, day_window, visitor) DO NOTHING
+            "#,
+            stored_key,
+            self.window_offset_minutes,
+            hex::encode(visitor)
+        )
+        .execute(pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+/// 定期删除前一天之前的访客记录
+pub async fn run(pool: PgPool) {
+    let mut interval = tokio::time::interval(PRUNE_INTERVAL);
+    loop {
+        interval.tick().await;
+        let result = sqlx::query!(
+            "DELETE FROM unique_visitors WHERE day_window < NOW() - INTERVAL '2 days'"
+        )
+        .execute(&pool)
+        .await;
+        match result {
+            Ok(result) if result.rows_affected() > 0 => {
+                info!("Pruned {} unique visitor rows", result.rows_affected());
+            }
+            Ok(_) => {}
+            Err(e) => warn!("Failed to prune unique visitors: {}", e),
+        }
+    }
+}